@@ -80,6 +80,8 @@ config :ama, :seed64, (case System.get_env("SEED64") do nil -> nil; seed64 -> Ba
 
 
 config :ama, :archival_node, System.get_env("ARCHIVALNODE") in ["true", "y", "yes"]
+#parallel speculative tx execution in apply_entry, off until replay has checked it against mainnet
+config :ama, :speculative_apply, System.get_env("SPECULATIVE_APPLY") in ["true", "y", "yes"]
 config :ama, :autoupdate, System.get_env("AUTOUPDATE") in ["true", "y", "yes"]
 config :ama, :computor_type, (case System.get_env("COMPUTOR") do nil -> nil; "trainer" -> :trainer; _ -> :default end)
 
@@ -18,15 +18,21 @@ defmodule API.Contract do
       view_pk = if view_pk do view_pk else @default_view_pk end
       %{db: db} = :persistent_term.get({:rocksdb, Fabric})
       tip = DB.Chain.tip_entry() |> RDB.vecpak_encode()
-      RDB.contract_view(db, tip, view_pk, contract, function, args, !!Application.fetch_env!(:ama, :testnet))
+      case RDB.contract_view(db, tip, view_pk, contract, function, args, !!Application.fetch_env!(:ama, :testnet)) do
+        {:error, reason} -> {false, (is_binary(reason) && reason) || inspect(reason), []}
+        result -> result
+      end
     end
 
     def validate(bytecode) do
       %{db: db} = :persistent_term.get({:rocksdb, Fabric})
       tip = DB.Chain.tip_entry() |> RDB.vecpak_encode()
-      {error, logs} = RDB.contract_validate(db, tip, bytecode, !!Application.fetch_env!(:ama, :testnet))
-      logs = Enum.map(logs, & RocksDB.ascii_dump(&1))
-      %{error: error, logs: logs}
+      case RDB.contract_validate(db, tip, bytecode, !!Application.fetch_env!(:ama, :testnet)) do
+        {:error, reason} -> %{error: reason, logs: []}
+        {error, logs} ->
+          logs = Enum.map(logs, & RocksDB.ascii_dump(&1))
+          %{error: error, logs: logs}
+      end
     end
 
     def richlist() do
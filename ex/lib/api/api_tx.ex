@@ -133,7 +133,7 @@ defmodule API.TX do
     end
 
     def submit(tx_packed) do
-        result = TX.validate(tx_packed |> TX.unpack())
+        result = TX.validate_packed(tx_packed)
         if result[:error] == :ok do
             txu = result.txu
             TXPool.insert_and_broadcast(txu)
@@ -144,7 +144,7 @@ defmodule API.TX do
     end
 
     def submit_and_wait(tx_packed, wait_finalized \\ false, broadcast \\ true) do
-      result = TX.validate(tx_packed |> TX.unpack())
+      result = TX.validate_packed(tx_packed)
       if result[:error] == :ok do
           txu = result.txu
           if broadcast do TXPool.insert_and_broadcast(txu) else TXPool.insert(txu) end
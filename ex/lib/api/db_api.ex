@@ -37,7 +37,8 @@ defmodule DB.API do
       "contractstate", "contractstate_tree"
     ]
     try do
-      {:ok, db_ref, cf_ref_list} = RDB.open_transaction_db(path, cfs)
+      db_opts = Application.get_env(:ama, :rocksdb_opts, %{})
+      {:ok, db_ref, cf_ref_list} = RDB.open_transaction_db(path, cfs, db_opts)
       [
         default_cf,
         sysconf_cf,
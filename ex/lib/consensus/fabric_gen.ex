@@ -183,14 +183,15 @@ defmodule FabricGen do
       entry ->
         start_ts = :os.system_time(1000)
         task = Task.async(fn -> FabricGen.apply_entry(entry) end)
-        %{error: :ok, mutations_hash: m_hash, receipts: r, muts: m
-        } = case Task.await(task, :infinity) do
-          result = %{error: :ok} -> result
+        case Task.await(task, :infinity) do
+          %{error: :ok, mutations_hash: m_hash, receipts: r, muts: m} ->
+            Application.fetch_env!(:ama, :rpc_events) && FabricEventGen.event_applied(entry, m_hash, m, r)
+            TXPool.delete_packed(entry.txs)
+          %{error: :apply_entry_failed} ->
+            #never pick it again, another entry for the height may still apply
+            :persistent_term.put(SoftforkDenyHash, [entry.hash | softfork_deny_hash])
         end
 
-        Application.fetch_env!(:ama, :rpc_events) && FabricEventGen.event_applied(entry, m_hash, m, r)
-        TXPool.delete_packed(entry.txs)
-
         proc_entries()
     end
   end
@@ -292,15 +293,25 @@ defmodule FabricGen do
       end
   end
   def apply_entry_1(next_entry) do
-      %{db: db, cf: cf} = :persistent_term.get({:rocksdb, Fabric})
+      %{db: db} = :persistent_term.get({:rocksdb, Fabric})
 
       start_contract_exec = :os.system_time(1000)
 
       entry = next_entry
-      {rtx, m, m_rev, receipts, root_receipts, root_contractstate} = RDB.apply_entry(db, RDB.vecpak_encode(entry),
+      case RDB.apply_entry(db, RDB.vecpak_encode(entry),
         Application.fetch_env!(:ama, :trainer_pk), Application.fetch_env!(:ama, :trainer_sk),
-        !!Application.fetch_env!(:ama, :testnet), Map.keys(Application.fetch_env!(:ama, :keys_by_pk))
-      )
+        !!Application.fetch_env!(:ama, :testnet), Map.keys(Application.fetch_env!(:ama, :keys_by_pk)),
+        !!Application.get_env(:ama, :speculative_apply)
+      ) do
+        {:error, reason} ->
+          IO.puts "apply_entry rejected #{Base58.encode(next_entry.hash)} #{next_entry.header.height}: #{inspect reason}"
+          %{error: :apply_entry_failed, reason: reason}
+        {rtx, m, m_rev, receipts, root_receipts, root_contractstate, _trace} ->
+          apply_entry_2(next_entry, start_contract_exec, {rtx, m, m_rev, receipts, root_receipts, root_contractstate})
+      end
+  end
+  def apply_entry_2(next_entry, start_contract_exec, {rtx, m, m_rev, receipts, root_receipts, root_contractstate}) do
+      %{cf: cf} = :persistent_term.get({:rocksdb, Fabric})
 
       took_contract_exec = :os.system_time(1000) - start_contract_exec
       if took_contract_exec > 100 do
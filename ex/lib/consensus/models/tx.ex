@@ -113,6 +113,14 @@ defmodule TX do
     end
    end
 
+   # Raw bytes from a client, the hash only covers the canonical encoding so refuse any other
+   def validate_packed(tx_packed, is_special_meeting_block \\ false) do
+    case RDB.validate_canonical(:txu, tx_packed) do
+      :ok -> validate(unpack(tx_packed), is_special_meeting_block)
+      {:error, _reason} -> %{error: :tx_not_canonical}
+    end
+   end
+
    def build(sk, contract, function, args, nonce \\ nil, attached_symbol \\ nil, attached_amount \\ nil) do
      pk = BlsEx.get_public_key!(sk)
      nonce = if !nonce do :os.system_time(:nanosecond) else nonce end
@@ -107,6 +107,8 @@ defmodule Ama.MultiServer do
                 prometheus_reply(state, fn -> HTTP.Prometheus.metrics_kpi() end)
             r.method == "GET" and r.path == "/metrics/validators" ->
                 prometheus_reply(state, fn -> HTTP.Prometheus.metrics_validators() end)
+            r.method == "GET" and r.path == "/metrics/rocksdb" ->
+                prometheus_reply(state, fn -> HTTP.Prometheus.metrics_rocksdb() end)
 
             r.method == "GET" and String.starts_with?(r.path, "/api/peer/anr/") ->
                 pk = String.replace(r.path, "/api/peer/anr/", "")
@@ -106,11 +106,56 @@ defmodule HTTP.Prometheus do
     header <> metrics <> "\n"
   end
 
+  def metrics_rocksdb() do
+    %{db: db, cf: cf} = :persistent_term.get({:rocksdb, Fabric})
+    {:ok, stats} = RDB.statistics(db)
+
+    tickers = Enum.map(stats.tickers, fn {name, count} ->
+      "amadeus_rocksdb_ticker_total{name=\"#{name}\"} #{count}"
+    end)
+
+    histograms = Enum.flat_map(stats.histograms, fn {name, h} ->
+      [
+        "amadeus_rocksdb_histogram{name=\"#{name}\",quantile=\"0.5\"} #{format_float(h.p50)}",
+        "amadeus_rocksdb_histogram{name=\"#{name}\",quantile=\"0.95\"} #{format_float(h.p95)}",
+        "amadeus_rocksdb_histogram{name=\"#{name}\",quantile=\"0.99\"} #{format_float(h.p99)}",
+        "amadeus_rocksdb_histogram_sum{name=\"#{name}\"} #{h.sum}",
+        "amadeus_rocksdb_histogram_count{name=\"#{name}\"} #{h.count}"
+      ]
+    end)
+
+    cf_gauges = Enum.flat_map(cf, fn {cf_name, cf_ref} ->
+      {:ok, cf_stats} = RDB.statistics_cf(cf_ref)
+      Enum.map(cf_stats, fn {key, value} ->
+        "amadeus_rocksdb_cf_#{key}{cf=\"#{cf_name}\"} #{value}"
+      end)
+    end)
+
+    """
+    # HELP amadeus_rocksdb_ticker_total RocksDB ticker counters
+    # TYPE amadeus_rocksdb_ticker_total counter
+    #{Enum.join(tickers, "\n")}
+
+    # HELP amadeus_rocksdb_histogram RocksDB histogram percentiles
+    # TYPE amadeus_rocksdb_histogram summary
+    #{Enum.join(histograms, "\n")}
+
+    # HELP amadeus_rocksdb_cache_usage_bytes RocksDB cache usage
+    # TYPE amadeus_rocksdb_cache_usage_bytes gauge
+    amadeus_rocksdb_cache_usage_bytes{cache="block"} #{stats.block_cache_usage}
+    amadeus_rocksdb_cache_usage_bytes{cache="block_pinned"} #{stats.block_cache_pinned_usage}
+    amadeus_rocksdb_cache_usage_bytes{cache="row"} #{stats.row_cache_usage}
+
+    #{Enum.join(cf_gauges, "\n")}
+    """
+  end
+
   def metrics_all() do
     [
       metrics_stats(),
       metrics_kpi(),
-      metrics_validators()
+      metrics_validators(),
+      metrics_rocksdb()
     ]
     |> Enum.join("\n")
   end
@@ -4,6 +4,7 @@ defmodule RocksDB do
         cf = opts[:cf]
         rtx = opts[:rtx]
         cond do
+            !!rtx and !!cf and opts[:for_update] -> RDB.transaction_get_for_update_cf(rtx, cf, key)
             !!rtx and !!cf -> RDB.transaction_get_cf(rtx, cf, key)
             !!rtx -> RDB.transaction_get(rtx, key)
             !!db and !!cf -> RDB.get_cf(cf, key)
@@ -213,8 +214,19 @@ defmodule RocksDB do
         end
     end
 
-    def transaction(db) do
-      {:ok, rtx} = RDB.transaction(db)
+    # Waits on a RDB.*_async call, dropping its progress messages along the way
+    def await_async({:ok, ref}, timeout \\ :infinity) do
+      receive do
+        {:rdb_progress, ^ref, _stage} -> await_async({:ok, ref}, timeout)
+        {:rdb_done, ^ref, result} -> result
+      after
+        timeout -> {:error, :timeout}
+      end
+    end
+    def await_async(error, _timeout), do: error
+
+    def transaction(db, opts \\ []) do
+      {:ok, rtx} = RDB.transaction(db, opts)
       rtx
     end
 
@@ -278,6 +290,18 @@ defmodule RocksDB do
     def restore_from_snapshot(path) do
     end
 
+    def verify_integrity() do
+        %{db: db} = :persistent_term.get({:rocksdb, Fabric})
+        RDB.verify_integrity(db)
+    end
+
+    # For a stopped node's DB, opened read only. cfs must name every CF on disk
+    def verify_integrity_offline(path, cfs) do
+        with {:ok, db, _cf_list} <- RDB.open_read_only(path, cfs, %{}) do
+            RDB.ro_verify_integrity(db)
+        end
+    end
+
     def flush_all(cfs) do
         Enum.each(Map.values(cfs), fn(cf)->
             RDB.flush_cf(cf)
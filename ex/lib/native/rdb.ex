@@ -5,16 +5,39 @@ defmodule RDB do
     otp_app: :ama,
     crate: "rdb"
 
-  def open_transaction_db(_path, _cf_names), do: :erlang.nif_error(:nif_not_loaded)
+  def open_transaction_db(_path, _cf_names, _opts \\ %{}), do: :erlang.nif_error(:nif_not_loaded)
   def close_db(_db), do: :erlang.nif_error(:nif_not_loaded)
+  def open_read_only(_path, _cf_names, _opts \\ %{}), do: :erlang.nif_error(:nif_not_loaded)
+  def open_secondary(_path, _secondary_path, _cf_names, _opts \\ %{}), do: :erlang.nif_error(:nif_not_loaded)
+  def try_catch_up_with_primary(_db), do: :erlang.nif_error(:nif_not_loaded)
+  def ro_get_cf(_cf, _key), do: :erlang.nif_error(:nif_not_loaded)
+  def ro_multi_get_cf(_cf, _keys), do: :erlang.nif_error(:nif_not_loaded)
+  def ro_scan_cf(_cf, _start, _end_or_prefix, _limit, _direction), do: :erlang.nif_error(:nif_not_loaded)
+  def ro_property_value_cf(_cf, _key), do: :erlang.nif_error(:nif_not_loaded)
+  def ro_verify_integrity(_db), do: :erlang.nif_error(:nif_not_loaded)
+  def create_cf(_db, _name, _opts \\ %{}), do: :erlang.nif_error(:nif_not_loaded)
   def drop_cf(_db, _cf), do: :erlang.nif_error(:nif_not_loaded)
+  def list_cf(_db), do: :erlang.nif_error(:nif_not_loaded)
   def property_value(_db, _key), do: :erlang.nif_error(:nif_not_loaded)
   def property_value_cf(_cf, _key), do: :erlang.nif_error(:nif_not_loaded)
+  def statistics(_db), do: :erlang.nif_error(:nif_not_loaded)
+  def statistics_cf(_cf), do: :erlang.nif_error(:nif_not_loaded)
+  def disk_usage_cf(_cf, _prefixes, _mode \\ :approximate), do: :erlang.nif_error(:nif_not_loaded)
+  def verify_integrity(_db), do: :erlang.nif_error(:nif_not_loaded)
   def compact_range_cf_all(_cf), do: :erlang.nif_error(:nif_not_loaded)
   def checkpoint(_db, _path), do: :erlang.nif_error(:nif_not_loaded)
+  def backup_create(_db, _backup_dir), do: :erlang.nif_error(:nif_not_loaded)
+  def backup_list(_backup_dir), do: :erlang.nif_error(:nif_not_loaded)
+  def backup_verify(_backup_dir, _backup_id), do: :erlang.nif_error(:nif_not_loaded)
+  def backup_purge(_backup_dir, _keep), do: :erlang.nif_error(:nif_not_loaded)
+  def backup_restore(_backup_dir, _backup_id, _db_path), do: :erlang.nif_error(:nif_not_loaded)
   def flush_wal(_db), do: :erlang.nif_error(:nif_not_loaded)
   def flush(_db), do: :erlang.nif_error(:nif_not_loaded)
   def flush_cf(_cf), do: :erlang.nif_error(:nif_not_loaded)
+  def compact_range_cf_all_async(_cf), do: :erlang.nif_error(:nif_not_loaded)
+  def checkpoint_async(_db, _path), do: :erlang.nif_error(:nif_not_loaded)
+  def flush_async(_db), do: :erlang.nif_error(:nif_not_loaded)
+  def delete_range_cf_async(_cf, _start_key, _end_key, _compact), do: :erlang.nif_error(:nif_not_loaded)
   def get(_db, _key), do: :erlang.nif_error(:nif_not_loaded)
   def get_cf(_cf, _key), do: :erlang.nif_error(:nif_not_loaded)
   def exists(_db, _key), do: :erlang.nif_error(:nif_not_loaded)
@@ -24,16 +47,21 @@ defmodule RDB do
   def delete(_db, _key), do: :erlang.nif_error(:nif_not_loaded)
   def delete_cf(_cf, _key), do: :erlang.nif_error(:nif_not_loaded)
   def delete_range_cf(_cf, _start_key, _end_key, _compact), do: :erlang.nif_error(:nif_not_loaded)
+  def multi_get_cf(_cf, _keys), do: :erlang.nif_error(:nif_not_loaded)
+  def scan_cf(_cf, _start, _end_or_prefix, _limit, _direction), do: :erlang.nif_error(:nif_not_loaded)
+  def sst_export_cf(_cf, _dir, _start, _end, _max_file_size \\ 256 * 1024 * 1024), do: :erlang.nif_error(:nif_not_loaded)
+  def sst_ingest_cf(_cf, _files, _move_files \\ false), do: :erlang.nif_error(:nif_not_loaded)
   def iterator(_db), do: :erlang.nif_error(:nif_not_loaded)
   def iterator_cf(_cf), do: :erlang.nif_error(:nif_not_loaded)
   def iterator_move(_it, _action), do: :erlang.nif_error(:nif_not_loaded)
-  def transaction(_db), do: :erlang.nif_error(:nif_not_loaded)
+  def transaction(_db, _opts \\ []), do: :erlang.nif_error(:nif_not_loaded)
   def transaction_commit(_tx), do: :erlang.nif_error(:nif_not_loaded)
   def transaction_rollback(_tx), do: :erlang.nif_error(:nif_not_loaded)
   def transaction_set_savepoint(_tx), do: :erlang.nif_error(:nif_not_loaded)
   def transaction_rollback_to_savepoint(_tx), do: :erlang.nif_error(:nif_not_loaded)
   def transaction_get(_tx, _key), do: :erlang.nif_error(:nif_not_loaded)
   def transaction_get_cf(_tx, _cf, _key), do: :erlang.nif_error(:nif_not_loaded)
+  def transaction_get_for_update_cf(_tx, _cf, _key, _exclusive \\ true), do: :erlang.nif_error(:nif_not_loaded)
   def transaction_exists(_tx, _key), do: :erlang.nif_error(:nif_not_loaded)
   def transaction_exists_cf(_tx, _cf, _key), do: :erlang.nif_error(:nif_not_loaded)
   def transaction_put(_tx, _key, _value), do: :erlang.nif_error(:nif_not_loaded)
@@ -43,13 +71,30 @@ defmodule RDB do
   def transaction_iterator(_tx), do: :erlang.nif_error(:nif_not_loaded)
   def transaction_iterator_cf(_tx, _cf), do: :erlang.nif_error(:nif_not_loaded)
   def transaction_iterator_move(_it, _action), do: :erlang.nif_error(:nif_not_loaded)
+  def snapshot(_db), do: :erlang.nif_error(:nif_not_loaded)
+  def snapshot_release(_snap), do: :erlang.nif_error(:nif_not_loaded)
+  def snapshot_get(_snap, _key), do: :erlang.nif_error(:nif_not_loaded)
+  def snapshot_get_cf(_snap, _cf, _key), do: :erlang.nif_error(:nif_not_loaded)
+  def snapshot_multi_get_cf(_snap, _cf, _keys), do: :erlang.nif_error(:nif_not_loaded)
+  def snapshot_iterator(_snap), do: :erlang.nif_error(:nif_not_loaded)
+  def snapshot_iterator_cf(_snap, _cf), do: :erlang.nif_error(:nif_not_loaded)
+  def write_batch(_db), do: :erlang.nif_error(:nif_not_loaded)
+  def write_batch_put(_wb, _key, _value), do: :erlang.nif_error(:nif_not_loaded)
+  def write_batch_put_cf(_wb, _cf, _key, _value), do: :erlang.nif_error(:nif_not_loaded)
+  def write_batch_delete(_wb, _key), do: :erlang.nif_error(:nif_not_loaded)
+  def write_batch_delete_cf(_wb, _cf, _key), do: :erlang.nif_error(:nif_not_loaded)
+  def write_batch_count(_wb), do: :erlang.nif_error(:nif_not_loaded)
+  def write_batch_write(_wb, _flags \\ []), do: :erlang.nif_error(:nif_not_loaded)
 
-  def apply_entry(_db, _entry, _pk, _sk, _testnet, _testnet_peddlebike), do: :erlang.nif_error(:nif_not_loaded)
-  def contract_view(_db, _entry, _view_pk, _contract, _function, _args, _testnet), do: :erlang.nif_error(:nif_not_loaded)
+  def apply_entry(_db, _entry, _pk, _sk, _testnet, _testnet_peddlebike, _speculate \\ false, _trace \\ false), do: :erlang.nif_error(:nif_not_loaded)
+  def contract_view(_db, _entry, _view_pk, _contract, _function, _args, _testnet, _snapshot \\ nil), do: :erlang.nif_error(:nif_not_loaded)
   def contract_validate(_db, _entry, _wasmbytes, _testnet), do: :erlang.nif_error(:nif_not_loaded)
+  def simulate_txs(_db, _entry, _txus, _testnet, _trace \\ false), do: :erlang.nif_error(:nif_not_loaded)
+  def validate_txus(_txus_packed, _tx_size), do: :erlang.nif_error(:nif_not_loaded)
 
   def vecpak_encode(_map), do: :erlang.nif_error(:nif_not_loaded)
   def vecpak_decode(_bin), do: :erlang.nif_error(:nif_not_loaded)
+  def validate_canonical(_kind, _bin), do: :erlang.nif_error(:nif_not_loaded)
 
   def freivalds(_tensor, _vr), do: :erlang.nif_error(:nif_not_loaded)
 
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rdb::model::_codec::EncodeIntoBuf;
+
+// Anything that decodes must encode and decode again
+fuzz_target!(|data: &[u8]| {
+    if let Ok(v) = rdb::model::attestation::from_bytes(data) {
+        let mut buf = Vec::new();
+        v.encode_into_buf(&mut buf).unwrap();
+        rdb::model::attestation::from_bytes(&buf).unwrap();
+    }
+});
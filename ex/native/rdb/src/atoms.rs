@@ -3,7 +3,41 @@ rustler::atoms! {
     error,
     nil,
     mutex_closed,
+    db_closed,
+
+    // RocksDB error kinds
+    not_found,
+    corruption,
+    not_supported,
+    invalid_argument,
+    io_error,
+    merge_in_progress,
+    incomplete,
+    shutdown_in_progress,
+    timed_out,
+    aborted,
+    busy,
+    expired,
+    try_again,
+    compaction_too_large,
+    column_family_dropped,
+    unknown,
+    panic,
     busy_iterators,
+    cf_wrong_db,
+    snapshot_wrong_db,
+    not_secondary,
+    badarg,
+
+    // Async maintenance
+    rdb_done,
+    rdb_progress,
+    worker_down,
+    started,
+    compacting,
+    checkpointing,
+    flushing,
+    deleting,
 
     invalid_iterator,
     // Iterator control atoms
@@ -15,6 +49,76 @@ rustler::atoms! {
     reverse,
     next,
 
+    // DB open options
+    cf,
+    key,
+    invalid_option,
+    unknown_option,
+    unknown_cf,
+    write_buffer_size,
+    max_write_buffer_number,
+    min_write_buffer_number_to_merge,
+    target_file_size_base,
+    max_compaction_bytes,
+    compression_per_level,
+    bloom_bits,
+    prefix_extractor,
+    memtable_prefix_bloom_ratio,
+    enable_blob_files,
+    min_blob_size,
+    blob_file_size,
+    blob_compression,
+    enable_blob_gc,
+    blob_gc_age_cutoff,
+    none,
+    snappy,
+    zlib,
+    bz2,
+    lz4,
+    lz4hc,
+    zstd,
+
+    // Backups
+    backup_id,
+    timestamp,
+    size,
+    num_files,
+    restore_path_not_empty,
+
+    // SST export / ingest
+    files,
+    keys,
+    checksum,
+    first_key,
+    last_key,
+    checksum_mismatch,
+
+    // Statistics
+    tickers,
+    histograms,
+    count,
+    sum,
+    min,
+    max,
+    avg,
+    p50,
+    p95,
+    p99,
+    row_cache_usage,
+    block_cache_usage,
+    block_cache_pinned_usage,
+    mem_table_total,
+    mem_table_unflushed,
+    mem_table_active,
+    table_readers_mem,
+    pending_compaction_bytes,
+    estimate_num_keys,
+    total_sst_files_size,
+    live_sst_files_size,
+    num_blob_files,
+    total_blob_file_size,
+    live_blob_file_size,
+    live_blob_file_garbage_size,
 
     //ama stuff because cant compile as rlib :()
     hash,
@@ -93,4 +197,63 @@ rustler::atoms! {
     exec_used,
     result,
     logs,
+
+    prefix,
+    prefixes,
+    sst_size,
+    memtable_size,
+    key_bytes,
+    value_bytes,
+
+    healthy,
+    cfs,
+    status,
+    contractstate,
+    leaves,
+    computed_root,
+    stored_root,
+    root_match,
+    mismatch_count,
+    mismatches,
+    issue,
+    missing,
+    extra,
+    hash_mismatch,
+    bad_entry,
+
+    receipts,
+    muts,
+    historical_cost,
+    exec_cost,
+    storage_cost,
+
+    trace,
+    calls,
+    caller,
+    callee,
+    events,
+    read,
+    scan,
+    write,
+    set_bit,
+    call,
+    value,
+    old,
+    new,
+    found,
+    bit,
+    was_set,
+    index,
+    tx_hash,
+    failed,
+    unwound,
+
+    // validate_txus
+    tx_not_canonical,
+
+    // Canonical encoding model kinds
+    entry,
+    txu,
+    attestation,
+    tx_receipt,
 }
@@ -0,0 +1,98 @@
+use rustler::{Encoder, Env, Error, Term};
+use rust_rocksdb::backup::{BackupEngine, BackupEngineInfo, BackupEngineOptions, RestoreOptions};
+use rust_rocksdb::{DB, Env as RocksEnv};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::atoms;
+use crate::to_nif_rdb_err;
+use crate::{TransactionDB, MultiThreaded, Options};
+
+fn io_err(err: std::io::Error) -> Error {
+    Error::Term(Box::new((atoms::io_error(), err.to_string())))
+}
+
+fn open_engine(backup_dir: &str) -> Result<BackupEngine, Error> {
+    let opts = BackupEngineOptions::new(backup_dir).map_err(to_nif_rdb_err)?;
+    let env = RocksEnv::new().map_err(to_nif_rdb_err)?;
+    BackupEngine::open(&opts, &env).map_err(to_nif_rdb_err)
+}
+
+// One create at a time: they share the staging dir and the BackupEngine dir
+static CREATE_LOCK: Mutex<()> = Mutex::new(());
+
+// Sibling of the DB dir so the checkpoint can hardlink the live SSTs
+fn staging_path(db_path: &Path) -> PathBuf {
+    let mut name = db_path.as_os_str().to_os_string();
+    name.push(".backup_staging");
+    PathBuf::from(name)
+}
+
+// BackupEngine wants a plain DB handle, TransactionDB does not expose one.
+// Take a hardlinked checkpoint, open it read only and back that up. SST file names
+// are kept by the checkpoint so the backup stays incremental against older ones.
+pub fn create(db: &TransactionDB<MultiThreaded>, backup_dir: &str) -> Result<BackupEngineInfo, Error> {
+    let _lock = CREATE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let staging = staging_path(db.path());
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging).map_err(io_err)?;
+    }
+    db.create_checkpoint(&staging).map_err(to_nif_rdb_err)?;
+
+    let result = (|| {
+        let opts = Options::default();
+        let cf_names = DB::list_cf(&opts, &staging).map_err(to_nif_rdb_err)?;
+        let ro_db = DB::open_cf_for_read_only(&opts, &staging, cf_names, false).map_err(to_nif_rdb_err)?;
+
+        let mut engine = open_engine(backup_dir)?;
+        engine.create_new_backup(&ro_db).map_err(to_nif_rdb_err)?;
+        drop(ro_db);
+
+        engine.get_backup_info().into_iter()
+            .max_by_key(|info| info.backup_id)
+            .ok_or_else(|| Error::Term(Box::new(atoms::nil())))
+    })();
+
+    let _ = std::fs::remove_dir_all(&staging);
+    result
+}
+
+pub fn list(backup_dir: &str) -> Result<Vec<BackupEngineInfo>, Error> {
+    let engine = open_engine(backup_dir)?;
+    let mut infos = engine.get_backup_info();
+    infos.sort_by_key(|info| info.backup_id);
+    Ok(infos)
+}
+
+pub fn verify(backup_dir: &str, backup_id: u32) -> Result<(), Error> {
+    let engine = open_engine(backup_dir)?;
+    engine.verify_backup(backup_id).map_err(to_nif_rdb_err)
+}
+
+pub fn purge(backup_dir: &str, keep: usize) -> Result<(), Error> {
+    let mut engine = open_engine(backup_dir)?;
+    engine.purge_old_backups(keep).map_err(to_nif_rdb_err)
+}
+
+// Restores only into a missing or empty dir, never over a live DB
+pub fn restore(backup_dir: &str, backup_id: u32, db_path: &str) -> Result<(), Error> {
+    let path = Path::new(db_path);
+    if path.exists() && std::fs::read_dir(path).map_err(io_err)?.next().is_some() {
+        return Err(Error::Term(Box::new(atoms::restore_path_not_empty())));
+    }
+
+    let mut engine = open_engine(backup_dir)?;
+    engine.verify_backup(backup_id).map_err(to_nif_rdb_err)?;
+    let mut opts = RestoreOptions::default();
+    opts.set_keep_log_files(false);
+    engine.restore_from_backup(db_path, db_path, &opts, backup_id).map_err(to_nif_rdb_err)
+}
+
+pub fn info_to_term<'a>(env: Env<'a>, info: &BackupEngineInfo) -> Term<'a> {
+    let mut map = Term::map_new(env);
+    map = map.map_put(atoms::backup_id(), info.backup_id).ok().unwrap();
+    map = map.map_put(atoms::timestamp(), info.timestamp).ok().unwrap();
+    map = map.map_put(atoms::size(), info.size).ok().unwrap();
+    map = map.map_put(atoms::num_files(), info.num_files).ok().unwrap();
+    map.encode(env)
+}
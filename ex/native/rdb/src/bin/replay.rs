@@ -0,0 +1,104 @@
+// Re-executes main chain entries of the node DB with the current consensus code and
+// reports the first entry whose roots or mutations differ from the recorded ones.
+//
+//   replay <db_path> <from_height> [to_height] [--testnet] [--speculate] [--peddlebike <base58 pk>]...
+//
+// to_height defaults to the tip. db_path is only opened read only, to take a hardlinked
+// checkpoint next to it. The replay runs on that checkpoint, which is removed at the end.
+// --speculate turns on the parallel speculative execution the node gates behind
+// :speculative_apply, to check it against history.
+
+use std::path::Path;
+use std::process::ExitCode;
+
+use rdb::replay::{self, ReplayOpts};
+
+const USAGE: &str = "usage: replay <db_path> <from_height> [to_height] [--testnet] [--speculate] [--peddlebike <base58 pk>]...";
+
+struct Args {
+    db_path: String,
+    from: u64,
+    to: Option<u64>,
+    opts: ReplayOpts,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut positional = Vec::new();
+    let mut opts = ReplayOpts { testnet: false, testnet_peddlebikes: Vec::new(), speculate: false };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--testnet" => opts.testnet = true,
+            "--speculate" => opts.speculate = true,
+            "--peddlebike" => {
+                let pk = args.next().ok_or("--peddlebike needs a public key")?;
+                let pk = bs58::decode(&pk).into_vec().map_err(|_| format!("invalid base58 {}", pk))?;
+                opts.testnet_peddlebikes.push(pk);
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let height = |s: &String| s.parse::<u64>().map_err(|_| format!("invalid height {}", s));
+    match positional.as_slice() {
+        [db_path, from] => Ok(Args { db_path: db_path.clone(), from: height(from)?, to: None, opts }),
+        [db_path, from, to] => Ok(Args { db_path: db_path.clone(), from: height(from)?, to: Some(height(to)?), opts }),
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn run(args: &Args, scratch: &Path) -> Result<bool, String> {
+    eprintln!("checkpointing {} to {}", args.db_path, scratch.display());
+    replay::checkpoint(Path::new(&args.db_path), scratch)?;
+    let db = replay::open(scratch)?;
+    let txn = db.transaction();
+
+    let tip = replay::tip_height(&txn, &db)?;
+    let to = args.to.unwrap_or(tip);
+    if args.from == 0 || args.from > to || to > tip {
+        return Err(format!("height range must be within 1..={}", tip));
+    }
+
+    eprintln!("rewinding {} entries from tip {}", tip - args.from + 1, tip);
+    replay::rewind(&txn, &db, args.from)?;
+    txn.commit().map_err(|e| e.to_string())?;
+
+    for height in args.from..=to {
+        let txn = db.transaction();
+        let entry = replay::main_chain_entry(&txn, &db, height)?;
+        match replay::replay_entry(&db, txn, entry, &args.opts) {
+            Ok((txn, None)) => txn.commit().map_err(|e| e.to_string())?,
+            Ok((_, Some(divergence))) | Err(divergence) => {
+                println!("diverged at height {} entry {} on {}: {}", divergence.height,
+                    bs58::encode(&divergence.entry_hash).into_string(), divergence.field, divergence.detail);
+                return Ok(false);
+            }
+        }
+        if height % 1000 == 0 {
+            eprintln!("replayed up to {}", height);
+        }
+    }
+    println!("heights {}..={} match", args.from, to);
+    Ok(true)
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(2);
+        }
+    };
+    let scratch = replay::scratch_path(Path::new(&args.db_path));
+    let res = run(&args, &scratch);
+    let _ = std::fs::remove_dir_all(&scratch);
+    match res {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
@@ -1,6 +1,6 @@
-use std::panic::panic_any;
 use crate::{bcat, consensus};
 use crate::consensus::consensus_kv::{kv_get, kv_put, kv_increment, kv_exists};
+use crate::consensus::consensus_error::{ExecError, ExecResult};
 use vecpak::{encode, decode, Term};
 
 pub const DECIMALS: u32 = 9;
@@ -22,100 +22,98 @@ pub fn from_flat(coins: i128) -> f64 {
     (x * 1e9).round() / 1e9
 }
 
-pub fn balance_burnt(env: &mut crate::consensus::consensus_apply::ApplyEnv, symbol: &[u8]) -> i128 {
+pub fn balance_burnt(env: &mut crate::consensus::consensus_apply::ApplyEnv, symbol: &[u8]) -> ExecResult<i128> {
     balance(env, &BURN_ADDRESS, symbol)
 }
 
-pub fn balance(env: &mut crate::consensus::consensus_apply::ApplyEnv, address: &[u8], symbol: &[u8]) -> i128 {
-    match kv_get(env, &bcat(&[b"account:", address, b":balance:", symbol])) {
-        Some(amount) => std::str::from_utf8(&amount).unwrap().parse::<i128>().unwrap_or_else(|_| panic_any("invalid_balance")),
-        None => 0
+pub fn balance(env: &mut crate::consensus::consensus_apply::ApplyEnv, address: &[u8], symbol: &[u8]) -> ExecResult<i128> {
+    match kv_get(env, &bcat(&[b"account:", address, b":balance:", symbol]))? {
+        Some(amount) => std::str::from_utf8(&amount).unwrap().parse::<i128>().map_err(|_| ExecError::Bic("invalid_balance")),
+        None => Ok(0)
     }
 }
 
-pub fn mintable(env: &mut crate::consensus::consensus_apply::ApplyEnv, symbol: &[u8]) -> bool {
-    match kv_get(env, &bcat(&[b"coin:", symbol, b":mintable"])).as_deref() {
-        Some(b"true") => true,
-        _ => false
+pub fn mintable(env: &mut crate::consensus::consensus_apply::ApplyEnv, symbol: &[u8]) -> ExecResult<bool> {
+    match kv_get(env, &bcat(&[b"coin:", symbol, b":mintable"]))?.as_deref() {
+        Some(b"true") => Ok(true),
+        _ => Ok(false)
     }
 }
 
-pub fn pausable(env: &mut crate::consensus::consensus_apply::ApplyEnv, symbol: &[u8]) -> bool {
-    match kv_get(env, &bcat(&[b"coin:", symbol, b":pausable"])).as_deref() {
-        Some(b"true") => true,
-        _ => false
+pub fn pausable(env: &mut crate::consensus::consensus_apply::ApplyEnv, symbol: &[u8]) -> ExecResult<bool> {
+    match kv_get(env, &bcat(&[b"coin:", symbol, b":pausable"]))?.as_deref() {
+        Some(b"true") => Ok(true),
+        _ => Ok(false)
     }
 }
 
-pub fn paused(env: &mut crate::consensus::consensus_apply::ApplyEnv, symbol: &[u8]) -> bool {
-    match kv_get(env, &bcat(&[b"coin:", symbol, b":paused"])).as_deref() {
+pub fn paused(env: &mut crate::consensus::consensus_apply::ApplyEnv, symbol: &[u8]) -> ExecResult<bool> {
+    match kv_get(env, &bcat(&[b"coin:", symbol, b":paused"]))?.as_deref() {
         Some(b"true") => pausable(env, symbol),
-        _ => false
+        _ => Ok(false)
     }
 }
 
-pub fn soulbound(env: &mut crate::consensus::consensus_apply::ApplyEnv, symbol: &[u8]) -> bool {
-    match kv_get(env, &bcat(&[b"coin:", symbol, b":soulbound"])).as_deref() {
-        Some(b"true") => true,
-        _ => false
+pub fn soulbound(env: &mut crate::consensus::consensus_apply::ApplyEnv, symbol: &[u8]) -> ExecResult<bool> {
+    match kv_get(env, &bcat(&[b"coin:", symbol, b":soulbound"]))?.as_deref() {
+        Some(b"true") => Ok(true),
+        _ => Ok(false)
     }
 }
 
-pub fn total_supply(env: &mut crate::consensus::consensus_apply::ApplyEnv, symbol: &[u8]) -> i128 {
-    match kv_get(env, &bcat(&[b"coin:", symbol, b":totalSupply"])) {
-        Some(amount) => std::str::from_utf8(&amount).unwrap().parse::<i128>().unwrap_or_else(|_| panic_any("invalid_total_supply")),
-        None => 0
+pub fn total_supply(env: &mut crate::consensus::consensus_apply::ApplyEnv, symbol: &[u8]) -> ExecResult<i128> {
+    match kv_get(env, &bcat(&[b"coin:", symbol, b":totalSupply"]))? {
+        Some(amount) => std::str::from_utf8(&amount).unwrap().parse::<i128>().map_err(|_| ExecError::Bic("invalid_total_supply")),
+        None => Ok(0)
     }
 }
 
-pub fn exists(env: &mut crate::consensus::consensus_apply::ApplyEnv, symbol: &[u8]) -> bool {
-    match kv_get(env, &bcat(&[b"coin:", symbol, b":totalSupply"])) {
-        Some(_) => true,
-        None => false
-    }
+pub fn exists(env: &mut crate::consensus::consensus_apply::ApplyEnv, symbol: &[u8]) -> ExecResult<bool> {
+    Ok(kv_get(env, &bcat(&[b"coin:", symbol, b":totalSupply"]))?.is_some())
 }
 
-pub fn has_permission(env: &mut crate::consensus::consensus_apply::ApplyEnv, symbol: &[u8], signer: &[u8]) -> bool {
-    match kv_get(env, &bcat(&[b"coin:", symbol, b":permission"])) {
-        None => false,
+pub fn has_permission(env: &mut crate::consensus::consensus_apply::ApplyEnv, symbol: &[u8], signer: &[u8]) -> ExecResult<bool> {
+    match kv_get(env, &bcat(&[b"coin:", symbol, b":permission"]))? {
+        None => Ok(false),
         Some(permission_list) => {
             let term = decode(permission_list.as_slice()).unwrap();
             match term {
-                Term::List(term_list) => term_list.iter().any(|el| {
+                Term::List(term_list) => Ok(term_list.iter().any(|el| {
                     matches!(el, Term::Binary(b) if b.as_slice() == signer)
-                }),
-                _ => false
+                })),
+                _ => Ok(false)
             }
         }
     }
 }
 
-pub fn call_transfer(env: &mut crate::consensus::consensus_apply::ApplyEnv, args: Vec<Vec<u8>>) {
-    if args.len() != 3 { panic_any("invalid_args") }
+pub fn call_transfer(env: &mut crate::consensus::consensus_apply::ApplyEnv, args: Vec<Vec<u8>>) -> ExecResult<()> {
+    if args.len() != 3 { return Err(ExecError::Bic("invalid_args")) }
     let receiver = args[0].as_slice();
     let amount = args[1].as_slice();
-    let amount = std::str::from_utf8(&amount).ok().and_then(|s| s.parse::<i128>().ok()).unwrap_or_else(|| panic_any("invalid_amount"));
+    let amount = std::str::from_utf8(&amount).ok().and_then(|s| s.parse::<i128>().ok()).ok_or(ExecError::Bic("invalid_amount"))?;
     let symbol = args[2].as_slice();
 
-    if receiver.len() != 48 { panic_any("invalid_receiver_pk") }
-    if !(consensus::bls12_381::validate_public_key(receiver) || receiver == &BURN_ADDRESS) { panic_any("invalid_receiver_pk") }
-    if amount <= 0 { panic_any("invalid_amount") }
-    if amount > balance(env, &env.caller_env.account_caller.clone(), &symbol) { panic_any("insufficient_funds") }
+    if receiver.len() != 48 { return Err(ExecError::Bic("invalid_receiver_pk")) }
+    if !(consensus::bls12_381::validate_public_key(receiver) || receiver == &BURN_ADDRESS) { return Err(ExecError::Bic("invalid_receiver_pk")) }
+    if amount <= 0 { return Err(ExecError::Bic("invalid_amount")) }
+    if amount > balance(env, &env.caller_env.account_caller.clone(), &symbol)? { return Err(ExecError::Bic("insufficient_funds")) }
 
-    if paused(env, symbol) { panic_any("paused") }
-    if soulbound(env, symbol) { panic_any("soulbound") }
+    if paused(env, symbol)? { return Err(ExecError::Bic("paused")) }
+    if soulbound(env, symbol)? { return Err(ExecError::Bic("soulbound")) }
 
-    kv_increment(env, &bcat(&[b"account:", &env.caller_env.account_caller, b":balance:", symbol]), -amount);
-    kv_increment(env, &bcat(&[b"account:", receiver, b":balance:", symbol]), amount);
+    kv_increment(env, &bcat(&[b"account:", &env.caller_env.account_caller, b":balance:", symbol]), -amount)?;
+    kv_increment(env, &bcat(&[b"account:", receiver, b":balance:", symbol]), amount)?;
 
     //Account burnt coins
     if symbol != b"AMA" && receiver == &BURN_ADDRESS {
-        kv_increment(env, &bcat(&[b"coin:", symbol, b":totalSupply"]), -amount);
+        kv_increment(env, &bcat(&[b"coin:", symbol, b":totalSupply"]), -amount)?;
     }
+    Ok(())
 }
 
-pub fn call_create_and_mint(env: &mut crate::consensus::consensus_apply::ApplyEnv, args: Vec<Vec<u8>>) {
-    if args.len() < 2 { panic_any("invalid_args") }
+pub fn call_create_and_mint(env: &mut crate::consensus::consensus_apply::ApplyEnv, args: Vec<Vec<u8>>) -> ExecResult<()> {
+    if args.len() < 2 { return Err(ExecError::Bic("invalid_args")) }
     let symbol_original = args[0].as_slice();
     let amount = args[1].as_slice();
     let decimals = args.get(2).and_then(|v| if v.is_empty() { None } else { Some(v.as_slice()) }).unwrap_or(b"9");
@@ -124,68 +122,71 @@ pub fn call_create_and_mint(env: &mut crate::consensus::consensus_apply::ApplyEn
     let soulbound = args.get(5).and_then(|v| if v.is_empty() { None } else { Some(v.as_slice()) }).unwrap_or(b"false");
 
     let symbol: Vec<u8> = symbol_original.iter().copied().filter(u8::is_ascii_alphanumeric).collect();
-    if symbol_original != symbol.as_slice() { panic_any("invalid_symbol") }
-    if symbol.len() < 1 { panic_any("symbol_too_short") }
-    if symbol.len() > 32 { panic_any("symbol_too_long") }
+    if symbol_original != symbol.as_slice() { return Err(ExecError::Bic("invalid_symbol")) }
+    if symbol.len() < 1 { return Err(ExecError::Bic("symbol_too_short")) }
+    if symbol.len() > 32 { return Err(ExecError::Bic("symbol_too_long")) }
 
-    if !consensus::bic::coin_symbol_reserved::is_free(&symbol, &env.caller_env.account_caller) { panic_any("symbol_reserved") }
-    if exists(env, &symbol) { panic_any("symbol_exists") }
+    if !consensus::bic::coin_symbol_reserved::is_free(&symbol, &env.caller_env.account_caller) { return Err(ExecError::Bic("symbol_reserved")) }
+    if exists(env, &symbol)? { return Err(ExecError::Bic("symbol_exists")) }
 
-    let amount = std::str::from_utf8(&amount).ok().and_then(|s| s.parse::<i128>().ok()).unwrap_or_else(|| panic_any("invalid_amount"));
-    if amount <= 0 { panic_any("invalid_amount") }
+    let amount = std::str::from_utf8(&amount).ok().and_then(|s| s.parse::<i128>().ok()).ok_or(ExecError::Bic("invalid_amount"))?;
+    if amount <= 0 { return Err(ExecError::Bic("invalid_amount")) }
 
-    let decimals = std::str::from_utf8(&decimals).ok().and_then(|s| s.parse::<u64>().ok()).unwrap_or_else(|| panic_any("invalid_decimals"));
+    let decimals = std::str::from_utf8(&decimals).ok().and_then(|s| s.parse::<u64>().ok()).ok_or(ExecError::Bic("invalid_decimals"))?;
     //if decimals < 0 { panic_any("invalid_decimals") }
-    if decimals >= 10 { panic_any("invalid_decimals") }
+    if decimals >= 10 { return Err(ExecError::Bic("invalid_decimals")) }
 
-    kv_increment(env, &bcat(&[b"account:", &env.caller_env.account_caller, b":balance:", &symbol]), amount);
-    kv_increment(env, &bcat(&[b"coin:", &symbol, b":totalSupply"]), amount);
+    kv_increment(env, &bcat(&[b"account:", &env.caller_env.account_caller, b":balance:", &symbol]), amount)?;
+    kv_increment(env, &bcat(&[b"coin:", &symbol, b":totalSupply"]), amount)?;
 
     let mut admin = Vec::new();
     admin.push(Term::Binary(env.caller_env.account_caller.to_vec()));
     let buf = encode(Term::List(admin));
-    kv_put(env, &bcat(&[b"coin:", &symbol, b":permission"]), &buf);
+    kv_put(env, &bcat(&[b"coin:", &symbol, b":permission"]), &buf)?;
 
-    if mintable == b"true" { kv_put(env, &bcat(&[b"coin:", &symbol, b":mintable"]), b"true") }
-    if pausable == b"true" { kv_put(env, &bcat(&[b"coin:", &symbol, b":pausable"]), b"true") }
-    if soulbound == b"true" { kv_put(env, &bcat(&[b"coin:", &symbol, b":soulbound"]), b"true") }
+    if mintable == b"true" { kv_put(env, &bcat(&[b"coin:", &symbol, b":mintable"]), b"true")?; }
+    if pausable == b"true" { kv_put(env, &bcat(&[b"coin:", &symbol, b":pausable"]), b"true")?; }
+    if soulbound == b"true" { kv_put(env, &bcat(&[b"coin:", &symbol, b":soulbound"]), b"true")?; }
+    Ok(())
 }
 
-pub fn call_mint(env: &mut crate::consensus::consensus_apply::ApplyEnv, args: Vec<Vec<u8>>) {
-    if args.len() != 3 { panic_any("invalid_args") }
+pub fn call_mint(env: &mut crate::consensus::consensus_apply::ApplyEnv, args: Vec<Vec<u8>>) -> ExecResult<()> {
+    if args.len() != 3 { return Err(ExecError::Bic("invalid_args")) }
     let receiver = args[0].as_slice();
     let amount = args[1].as_slice();
-    let amount = std::str::from_utf8(&amount).ok().and_then(|s| s.parse::<i128>().ok()).unwrap_or_else(|| panic_any("invalid_amount"));
+    let amount = std::str::from_utf8(&amount).ok().and_then(|s| s.parse::<i128>().ok()).ok_or(ExecError::Bic("invalid_amount"))?;
     let symbol = args[2].as_slice();
-    if receiver.len() != 48 { panic_any("invalid_receiver_pk") }
+    if receiver.len() != 48 { return Err(ExecError::Bic("invalid_receiver_pk")) }
 
-    if !has_permission(env, &symbol, &env.caller_env.account_caller.clone()) { panic_any("no_permissions") }
+    if !has_permission(env, &symbol, &env.caller_env.account_caller.clone())? { return Err(ExecError::Bic("no_permissions")) }
 
-    mint(env, receiver, amount, symbol);
+    mint(env, receiver, amount, symbol)
 }
 
-pub fn mint(env: &mut crate::consensus::consensus_apply::ApplyEnv, receiver: &[u8], amount: i128, symbol: &[u8]) {
-    if !(consensus::bls12_381::validate_public_key(receiver)) { panic_any("invalid_receiver_pk") }
-    if amount <= 0 { panic_any("invalid_amount") }
+pub fn mint(env: &mut crate::consensus::consensus_apply::ApplyEnv, receiver: &[u8], amount: i128, symbol: &[u8]) -> ExecResult<()> {
+    if !(consensus::bls12_381::validate_public_key(receiver)) { return Err(ExecError::Bic("invalid_receiver_pk")) }
+    if amount <= 0 { return Err(ExecError::Bic("invalid_amount")) }
 
-    if !exists(env, &symbol) { panic_any("symbol_doesnt_exist") }
-    if !mintable(env, &symbol) { panic_any("not_mintable") }
-    if paused(env, &symbol) { panic_any("paused") }
+    if !exists(env, &symbol)? { return Err(ExecError::Bic("symbol_doesnt_exist")) }
+    if !mintable(env, &symbol)? { return Err(ExecError::Bic("not_mintable")) }
+    if paused(env, &symbol)? { return Err(ExecError::Bic("paused")) }
 
-    kv_increment(env, &bcat(&[b"account:", receiver, b":balance:", symbol]), amount);
-    kv_increment(env, &bcat(&[b"coin:", symbol, b":totalSupply"]), amount);
+    kv_increment(env, &bcat(&[b"account:", receiver, b":balance:", symbol]), amount)?;
+    kv_increment(env, &bcat(&[b"coin:", symbol, b":totalSupply"]), amount)?;
+    Ok(())
 }
 
-pub fn call_pause(env: &mut crate::consensus::consensus_apply::ApplyEnv, args: Vec<Vec<u8>>) {
-    if args.len() != 2 { panic_any("invalid_args") }
+pub fn call_pause(env: &mut crate::consensus::consensus_apply::ApplyEnv, args: Vec<Vec<u8>>) -> ExecResult<()> {
+    if args.len() != 2 { return Err(ExecError::Bic("invalid_args")) }
     let symbol = args[0].as_slice();
     let direction = args[1].as_slice();
 
-    if direction != b"true" && direction != b"false" { panic_any("invalid_direction") }
+    if direction != b"true" && direction != b"false" { return Err(ExecError::Bic("invalid_direction")) }
 
-    if !exists(env, &symbol) { panic_any("symbol_doesnt_exist") }
-    if !has_permission(env, &symbol, &env.caller_env.account_caller.clone()) { panic_any("no_permissions") }
-    if !pausable(env, &symbol) { panic_any("not_pausable") }
+    if !exists(env, &symbol)? { return Err(ExecError::Bic("symbol_doesnt_exist")) }
+    if !has_permission(env, &symbol, &env.caller_env.account_caller.clone())? { return Err(ExecError::Bic("no_permissions")) }
+    if !pausable(env, &symbol)? { return Err(ExecError::Bic("not_pausable")) }
 
-    kv_put(env, &bcat(&[b"coin:", &symbol, b":paused"]), &direction);
+    kv_put(env, &bcat(&[b"coin:", &symbol, b":paused"]), &direction)?;
+    Ok(())
 }
@@ -1,13 +1,13 @@
-use std::panic::panic_any;
 use crate::consensus::consensus_kv::{kv_get, kv_put};
+use crate::consensus::consensus_error::{ExecError, ExecResult};
 use crate::consensus::bic::wasm::{validate_contract};
 use crate::{bcat};
 
-pub fn call_deploy(env: &mut crate::consensus::consensus_apply::ApplyEnv, args: Vec<Vec<u8>>) {
-    if args.len() == 0 { panic_any("invalid_args") }
+pub fn call_deploy(env: &mut crate::consensus::consensus_apply::ApplyEnv, args: Vec<Vec<u8>>) -> ExecResult<()> {
+    if args.len() == 0 { return Err(ExecError::Bic("invalid_args")) }
     let wasmbytes = args[0].as_slice();
-    validate_contract(env, wasmbytes);
-    kv_put(env, &bcat(&[b"account:", &env.caller_env.account_caller, b":attribute:bytecode"]), wasmbytes);
+    validate_contract(env, wasmbytes)?;
+    kv_put(env, &bcat(&[b"account:", &env.caller_env.account_caller, b":attribute:bytecode"]), wasmbytes)?;
     if args.len() >= 2 {
         let og_account_current = env.caller_env.account_current.clone();
         let og_account_caller = env.caller_env.account_caller.clone();
@@ -19,13 +19,14 @@ pub fn call_deploy(env: &mut crate::consensus::consensus_apply::ApplyEnv, args:
 
         let init_function = args[1].as_slice().to_vec();
         let init_args = args[2..].to_vec();
-        crate::consensus::consensus_apply::call_wasmvm(env, og_account_caller.clone(), init_function, init_args, None, None);
+        crate::consensus::consensus_apply::call_wasmvm(env, og_account_caller.clone(), init_function, init_args, None, None)?;
 
         env.caller_env.account_current = og_account_current;
         env.caller_env.account_caller = og_account_caller;
     }
+    Ok(())
 }
 
-pub fn bytecode(env: &mut crate::consensus::consensus_apply::ApplyEnv, account: &[u8]) -> Option<Vec<u8>> {
+pub fn bytecode(env: &mut crate::consensus::consensus_apply::ApplyEnv, account: &[u8]) -> ExecResult<Option<Vec<u8>>> {
     kv_get(env, &bcat(&[b"account:", &account, b":attribute:bytecode"]))
 }
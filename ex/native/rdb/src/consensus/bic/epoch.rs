@@ -1,6 +1,6 @@
-use std::panic::panic_any;
 use std::collections::HashSet;
 use crate::consensus::aggsig::DST_MOTION;
+use crate::consensus::consensus_error::{ExecError, ExecResult};
 use crate::{bcat, consensus};
 
 use crate::consensus::consensus_kv::{kv_get, kv_get_next, kv_put, kv_exists, kv_delete, kv_set_bit, kv_increment, kv_get_prev_or_first};
@@ -172,51 +172,53 @@ pub const PEDDLEBIKE67: &[[u8; 48]; 67] = &[
     [0x95, 0x81, 0xAB, 0x66, 0x9D, 0xDE, 0x3B, 0xDE, 0xDE, 0x43, 0xCE, 0x68, 0x14, 0xD1, 0xEE, 0x3B, 0x54, 0x9A, 0x48, 0x6B, 0x01, 0xD5, 0x96, 0xD8, 0xC2, 0x09, 0xF7, 0x8F, 0x87, 0xBE, 0x0A, 0x3F, 0x6A, 0x66, 0x8E, 0x62, 0xE1, 0x5A, 0x8F, 0x31, 0xCE, 0xD9, 0xBF, 0x86, 0xA3, 0x61, 0x37, 0x5D],
 ];
 
-pub fn call_set_emission_address(env: &mut crate::consensus::consensus_apply::ApplyEnv, args: Vec<Vec<u8>>) {
-    if args.len() != 1 { panic_any("invalid_args") }
+pub fn call_set_emission_address(env: &mut crate::consensus::consensus_apply::ApplyEnv, args: Vec<Vec<u8>>) -> ExecResult<()> {
+    if args.len() != 1 { return Err(ExecError::Bic("invalid_args")) }
     let address = args[0].as_slice();
-    if address.len() != 48 { panic_any("invalid_address_pk") }
+    if address.len() != 48 { return Err(ExecError::Bic("invalid_address_pk")) }
 
-    kv_put(env, &bcat(&[b"account:", &env.caller_env.account_caller, b":attribute:emission_address"]), address);
+    kv_put(env, &bcat(&[b"account:", &env.caller_env.account_caller, b":attribute:emission_address"]), address)?;
+    Ok(())
 }
 
-pub fn call_submit_sol(env: &mut crate::consensus::consensus_apply::ApplyEnv, args: Vec<Vec<u8>>) {
-    if args.len() != 1 { panic_any("invalid_args") }
+pub fn call_submit_sol(env: &mut crate::consensus::consensus_apply::ApplyEnv, args: Vec<Vec<u8>>) -> ExecResult<()> {
+    if args.len() != 1 { return Err(ExecError::Bic("invalid_args")) }
     let sol = args[0].as_slice();
-    if sol.len() != consensus::bic::sol::SOL_SIZE { panic_any("invalid_sol_seed_size") }
+    if sol.len() != consensus::bic::sol::SOL_SIZE { return Err(ExecError::Bic("invalid_sol_seed_size")) }
     let sol: [u8; consensus::bic::sol::SOL_SIZE] = sol.try_into().unwrap();
 
     let hash = blake3::hash(&sol);
     let mut flips = 0;
     for seg in consensus::bic::sol_bloom::segs_from_digest(hash.as_bytes()) {
         let key = format!("bic:epoch:solbloom:{}", seg.page).into_bytes();
-        if kv_set_bit(env, &key, seg.bit_offset) { flips += 1 }
+        if kv_set_bit(env, &key, seg.bit_offset)? { flips += 1 }
     }
-    if flips == 0 { panic_any("sol_exists") }
+    if flips == 0 { return Err(ExecError::Bic("sol_exists")) }
 
     let usol = consensus::bic::sol::unpack(&sol);
-    if env.caller_env.entry_epoch != usol.epoch { panic_any("invalid_epoch") }
+    if env.caller_env.entry_epoch != usol.epoch { return Err(ExecError::Bic("invalid_epoch")) }
 
-    let segment_vr_hash = kv_get(env, b"bic:epoch:segment_vr_hash").unwrap();
-    let diff_bits = kv_get(env, b"bic:epoch:diff_bits").unwrap();
-    let diff_bits_int = std::str::from_utf8(&diff_bits).ok().and_then(|s| s.parse::<u64>().ok()).unwrap_or_else(|| panic_any("invalid_diff_bits"));
+    let segment_vr_hash = kv_get(env, b"bic:epoch:segment_vr_hash")?.unwrap();
+    let diff_bits = kv_get(env, b"bic:epoch:diff_bits")?.unwrap();
+    let diff_bits_int = std::str::from_utf8(&diff_bits).ok().and_then(|s| s.parse::<u64>().ok()).ok_or(ExecError::Bic("invalid_diff_bits"))?;
     if !consensus::bic::sol::verify(&sol, hash.as_bytes(), &segment_vr_hash, &env.caller_env.entry_vr_b3, diff_bits_int).unwrap_or(false) {
-        panic_any("invalid_sol");
+        return Err(ExecError::Bic("invalid_sol"));
     }
 
-    if !kv_exists(env, &bcat(&[b"account:", &usol.pk, b":attribute:pop"])) {
+    if !kv_exists(env, &bcat(&[b"account:", &usol.pk, b":attribute:pop"]))? {
         match consensus::bls12_381::verify(&usol.pk, &usol.pop, &usol.pk, consensus::aggsig::DST_POP) {
-            Ok(()) => kv_put(env, &bcat(&[b"account:", &usol.pk, b":attribute:pop"]), &usol.pop),
-            Err(_) => panic_any("invalid_pop")
+            Ok(()) => kv_put(env, &bcat(&[b"account:", &usol.pk, b":attribute:pop"]), &usol.pop)?,
+            Err(_) => return Err(ExecError::Bic("invalid_pop"))
         }
     }
-    kv_increment(env, &bcat(&[b"bic:epoch:solutions_count:", usol.pk.as_slice()]), 1);
+    kv_increment(env, &bcat(&[b"bic:epoch:solutions_count:", usol.pk.as_slice()]), 1)?;
+    Ok(())
 }
 
-pub fn kv_get_trainers(env: &mut crate::consensus::consensus_apply::ApplyEnv, height: u64) -> Vec<Vec<u8>> {
+pub fn kv_get_trainers(env: &mut crate::consensus::consensus_apply::ApplyEnv, height: u64) -> ExecResult<Vec<Vec<u8>>> {
     let height_padded = format!("{:012}", height).into_bytes();
-    match kv_get_prev_or_first(env, b"bic:epoch:validators:height:", &height_padded) {
-        None => Vec::new(),
+    match kv_get_prev_or_first(env, b"bic:epoch:validators:height:", &height_padded)? {
+        None => Ok(Vec::new()),
         Some((_key_suffix, trainer_list)) => {
             let term = vecpak::decode(trainer_list.as_slice()).unwrap();
             match term {
@@ -226,62 +228,63 @@ pub fn kv_get_trainers(env: &mut crate::consensus::consensus_apply::ApplyEnv, he
                         if let vecpak::Term::Binary(b) = el {
                             out.push(b);
                         } else {
-                            panic_any("invalid_trainer_list_element");
+                            return Err(ExecError::Bic("invalid_trainer_list_element"));
                         }
                     }
-                    out
+                    Ok(out)
                 }
-                _ => panic_any("invalid_trainer_list_term")
+                _ => return Err(ExecError::Bic("invalid_trainer_list_term"))
             }
         }
     }
 }
 
-pub fn kv_get_trainers_removed(env: &mut crate::consensus::consensus_apply::ApplyEnv) -> Vec<Vec<u8>> {
-    let trainers_start = kv_get_trainers(env, env.caller_env.entry_epoch * 100_000);
-    let trainers_now = kv_get_trainers(env, env.caller_env.entry_height);
+pub fn kv_get_trainers_removed(env: &mut crate::consensus::consensus_apply::ApplyEnv) -> ExecResult<Vec<Vec<u8>>> {
+    let trainers_start = kv_get_trainers(env, env.caller_env.entry_epoch * 100_000)?;
+    let trainers_now = kv_get_trainers(env, env.caller_env.entry_height)?;
 
     let trainers_now_set: HashSet<Vec<u8>> = trainers_now.into_iter().collect();
-    trainers_start
+    Ok(trainers_start
         .into_iter()
         .filter(|t| !trainers_now_set.contains(t))
-        .collect()
+        .collect())
 }
 
-pub fn call_slash_trainer(env: &mut crate::consensus::consensus_apply::ApplyEnv, args: Vec<Vec<u8>>) {
-    if args.len() != 5 { panic_any("invalid_args") }
+pub fn call_slash_trainer(env: &mut crate::consensus::consensus_apply::ApplyEnv, args: Vec<Vec<u8>>) -> ExecResult<()> {
+    if args.len() != 5 { return Err(ExecError::Bic("invalid_args")) }
     let malicious_pk = args[0].as_slice();
     let epoch = args[1].as_slice();
-    let epoch = std::str::from_utf8(&epoch).ok().and_then(|s| s.parse::<u64>().ok()).unwrap_or_else(|| panic_any("invalid_epoch"));
+    let epoch = std::str::from_utf8(&epoch).ok().and_then(|s| s.parse::<u64>().ok()).ok_or(ExecError::Bic("invalid_epoch"))?;
     let signature = args[2].as_slice();
     let mask_size = args[3].as_slice();
-    let mask_size = std::str::from_utf8(&mask_size).ok().and_then(|s| s.parse::<u64>().ok()).unwrap_or_else(|| panic_any("invalid_mask_size"));
+    let mask_size = std::str::from_utf8(&mask_size).ok().and_then(|s| s.parse::<u64>().ok()).ok_or(ExecError::Bic("invalid_mask_size"))?;
     let mask = args[4].to_vec();
 
-    if epoch != env.caller_env.entry_epoch { panic_any("invalid_epoch") }
+    if epoch != env.caller_env.entry_epoch { return Err(ExecError::Bic("invalid_epoch")) }
 
-    let mut trainers = kv_get_trainers(env, env.caller_env.entry_height);
-    if !trainers.iter().any(|v| v.as_slice() == malicious_pk) { panic_any("invalid_trainer_pk") }
+    let mut trainers = kv_get_trainers(env, env.caller_env.entry_height)?;
+    if !trainers.iter().any(|v| v.as_slice() == malicious_pk) { return Err(ExecError::Bic("invalid_trainer_pk")) }
 
     let signers = consensus::aggsig::unmask_trainers(&trainers, &mask, mask_size as usize);
     let consensus_pct = signers.len() as f64 / trainers.len() as f64;
-    if consensus_pct < 0.67 { panic_any("invalid_amount_of_signatures") }
+    if consensus_pct < 0.67 { return Err(ExecError::Bic("invalid_amount_of_signatures")) }
 
-    let apk = consensus::bls12_381::aggregate_public_keys(signers).unwrap_or_else(|_| panic_any("invalid_aggregation"));
+    let apk = consensus::bls12_381::aggregate_public_keys(signers).map_err(|_| ExecError::Bic("invalid_aggregation"))?;
     let msg = bcat(&[b"slash_trainer", (epoch as u32).to_le_bytes().as_slice(), malicious_pk]);
     let signature_valid = match consensus::bls12_381::verify(&apk, signature, msg.as_slice(), DST_MOTION) {
         Ok(()) => true,
         _ => false
     };
-    if !signature_valid { panic_any("invalid_signature") }
+    if !signature_valid { return Err(ExecError::Bic("invalid_signature")) }
 
     trainers.retain(|pk| pk.as_slice() != malicious_pk);
     let term_trainers = consensus::bic::list_of_binaries_to_vecpak(trainers);
     let height_next = format!("{:012}", env.caller_env.entry_height.saturating_add(1)).into_bytes();
-    kv_put(env, &bcat(&[b"bic:epoch:validators:height:", &height_next]), term_trainers.as_slice());
+    kv_put(env, &bcat(&[b"bic:epoch:validators:height:", &height_next]), term_trainers.as_slice())?;
+    Ok(())
 }
 
-pub fn next(env: &mut ApplyEnv) {
+pub fn next(env: &mut ApplyEnv) -> ExecResult<()> {
     let epoch_cur = env.caller_env.entry_epoch;
     let epoch_next = env.caller_env.entry_epoch + 1;
 
@@ -292,15 +295,15 @@ pub fn next(env: &mut ApplyEnv) {
 
     // slash sols for malicious trainers
     //let trainers = kv_get_trainers(env, &bcat(&[b"bic:epoch:trainers:", epoch_cur.to_string().as_bytes()]));
-    let trainers = kv_get_trainers(env, env.caller_env.entry_height);
+    let trainers = kv_get_trainers(env, env.caller_env.entry_height)?;
     let trainers_map: HashSet<Vec<u8>> = trainers.into_iter().collect();
-    let trainers_removed = kv_get_trainers_removed(env);
+    let trainers_removed = kv_get_trainers_removed(env)?;
     let trainers_removed_map: HashSet<Vec<u8>> = trainers_removed.into_iter().collect();
     let mut leaders: Vec<(Vec<u8>, i128)> = Vec::new();
     let mut cursor: Vec<u8> = Vec::new();
-    while let Some((next_key_wo_prefix, val)) = kv_get_next(env, b"bic:epoch:solutions_count:", &cursor) {
+    while let Some((next_key_wo_prefix, val)) = kv_get_next(env, b"bic:epoch:solutions_count:", &cursor)? {
         if !trainers_removed_map.contains(&next_key_wo_prefix) {
-            let count = std::str::from_utf8(&val).ok().and_then(|s| s.parse::<i128>().ok()).unwrap_or_else(|| panic_any("invalid_solutions_count"));
+            let count = std::str::from_utf8(&val).ok().and_then(|s| s.parse::<i128>().ok()).ok_or(ExecError::Bic("invalid_solutions_count"))?;
             leaders.push((next_key_wo_prefix.clone(), count));
         }
         cursor = next_key_wo_prefix;
@@ -323,41 +326,42 @@ pub fn next(env: &mut ApplyEnv) {
     let epoch_early_adopter_emission = epoch_total_emission / 7;
     let epoch_communityfund_emission = epoch_total_emission - epoch_early_adopter_emission;
 
-    distribute_peddlebike67_community_fund(env, epoch_communityfund_emission);
+    distribute_peddlebike67_community_fund(env, epoch_communityfund_emission)?;
 
     let total_sols: i128 = trainers_to_recv_emissions.iter().map(|(_, count)| count).sum();
-    distribute_emissions_to_trainers(env, &trainers_to_recv_emissions, epoch_early_adopter_emission, total_sols);
+    distribute_emissions_to_trainers(env, &trainers_to_recv_emissions, epoch_early_adopter_emission, total_sols)?;
 
     //Update validators for next epoch
     let new_validators = build_and_shuffle_new_validators(env, &leaders);
     let new_validators = consensus::bic::list_of_binaries_to_vecpak(new_validators);
     let height_next = format!("{:012}", env.caller_env.entry_height.saturating_add(1)).into_bytes();
-    let _ = kv_put(env, &bcat(&[b"bic:epoch:validators:height:", &height_next]), &new_validators);
+    kv_put(env, &bcat(&[b"bic:epoch:validators:height:", &height_next]), &new_validators)?;
 
-    update_difficulty_and_log_sols(env, epoch_cur, epoch_next, total_sols);
-    clear_epoch_data(env);
+    update_difficulty_and_log_sols(env, epoch_cur, epoch_next, total_sols)?;
+    clear_epoch_data(env)
 }
 
-fn distribute_emissions_to_trainers(env: &mut ApplyEnv, trainers_to_recv: &Vec<(Vec<u8>, i128)>, total_emission: i128, total_sols: i128) {
+fn distribute_emissions_to_trainers(env: &mut ApplyEnv, trainers_to_recv: &Vec<(Vec<u8>, i128)>, total_emission: i128, total_sols: i128) -> ExecResult<()> {
     if total_sols == 0 {
-        return;
+        return Ok(());
     }
 
     for (trainer, trainer_sols) in trainers_to_recv {
         let coins = (trainer_sols * total_emission) / total_sols;
 
-        let emission_address = kv_get(env, &bcat(&[b"account:", trainer, b":attribute:emission_address"]));
+        let emission_address = kv_get(env, &bcat(&[b"account:", trainer, b":attribute:emission_address"]))?;
         let balance_key = if let Some(addr) = emission_address {
             bcat(&[b"account:", &addr, b":balance:AMA"])
         } else {
             bcat(&[b"account:", trainer, b":balance:AMA"])
         };
 
-        let _ = kv_increment(env, &balance_key, coins);
+        kv_increment(env, &balance_key, coins)?;
     }
+    Ok(())
 }
 
-fn distribute_peddlebike67_community_fund(env: &mut ApplyEnv, total_emission: i128) {
+fn distribute_peddlebike67_community_fund(env: &mut ApplyEnv, total_emission: i128) -> ExecResult<()> {
     let n_count = PEDDLEBIKE67.len() as i128;
     let q = total_emission / n_count;
     let r = total_emission % n_count;
@@ -365,15 +369,16 @@ fn distribute_peddlebike67_community_fund(env: &mut ApplyEnv, total_emission: i1
     for (i, peddle_pk) in PEDDLEBIKE67.iter().enumerate() {
         let coins = if (i as i128) < r { q + 1 } else { q };
 
-        let emission_address = kv_get(env, &bcat(&[b"account:", peddle_pk, b":attribute:emission_address"]));
+        let emission_address = kv_get(env, &bcat(&[b"account:", peddle_pk, b":attribute:emission_address"]))?;
         let balance_key = if let Some(addr) = emission_address {
             bcat(&[b"account:", &addr, b":balance:AMA"])
         } else {
             bcat(&[b"account:", peddle_pk, b":balance:AMA"])
         };
 
-        let _ = kv_increment(env, &balance_key, coins);
+        kv_increment(env, &balance_key, coins)?;
     }
+    Ok(())
 }
 
 fn build_and_shuffle_new_validators(env: &ApplyEnv, leaders: &Vec<(Vec<u8>, i128)>) -> Vec<Vec<u8>> {
@@ -405,37 +410,39 @@ fn build_and_shuffle_new_validators(env: &ApplyEnv, leaders: &Vec<(Vec<u8>, i128
     new_validators
 }
 
-fn update_difficulty_and_log_sols(env: &mut ApplyEnv, epoch_cur: u64, epoch_next: u64, total_sols: i128) {
+fn update_difficulty_and_log_sols(env: &mut ApplyEnv, epoch_cur: u64, epoch_next: u64, total_sols: i128) -> ExecResult<()> {
     use crate::consensus::consensus_kv::kv_put;
-    let old_diff_bits = kv_get(env, b"bic:epoch:diff_bits").unwrap();
-    let old_diff_bits = std::str::from_utf8(&old_diff_bits).ok().and_then(|s| s.parse::<u32>().ok()).unwrap_or_else(|| panic_any("invalid_diff_bits"));
+    let old_diff_bits = kv_get(env, b"bic:epoch:diff_bits")?.unwrap();
+    let old_diff_bits = std::str::from_utf8(&old_diff_bits).ok().and_then(|s| s.parse::<u32>().ok()).ok_or(ExecError::Bic("invalid_diff_bits"))?;
 
     let next_diff_bits = crate::consensus::bic::sol_difficulty::next(old_diff_bits, total_sols as u64);
-    let _ = kv_put(env, b"bic:epoch:diff_bits", next_diff_bits.to_string().as_bytes());
-    let _ = kv_put(env, format!("bic:epoch:diff_bits:{}", epoch_next).as_bytes(), next_diff_bits.to_string().as_bytes());
-    let _ = kv_put(env, format!("bic:epoch:total_sols:{}", epoch_cur).as_bytes(), total_sols.to_string().as_bytes());
+    kv_put(env, b"bic:epoch:diff_bits", next_diff_bits.to_string().as_bytes())?;
+    kv_put(env, format!("bic:epoch:diff_bits:{}", epoch_next).as_bytes(), next_diff_bits.to_string().as_bytes())?;
+    kv_put(env, format!("bic:epoch:total_sols:{}", epoch_cur).as_bytes(), total_sols.to_string().as_bytes())?;
+    Ok(())
 }
 
-fn clear_epoch_data(env: &mut ApplyEnv) {
+fn clear_epoch_data(env: &mut ApplyEnv) -> ExecResult<()> {
     let mut cursor: Vec<u8> = Vec::new();
 
     let prefix = b"bic:epoch:solbloom:";
-    while let Some((next_key_wo_prefix, _val)) = kv_get_next(env, prefix, &cursor) {
+    while let Some((next_key_wo_prefix, _val)) = kv_get_next(env, prefix, &cursor)? {
         let mut key = Vec::with_capacity(prefix.len() + next_key_wo_prefix.len());
         key.extend_from_slice(prefix);
         key.extend_from_slice(&next_key_wo_prefix);
 
-        kv_delete(env, &key);
+        kv_delete(env, &key)?;
         cursor = next_key_wo_prefix;
     }
 
     let prefix = b"bic:epoch:solutions_count:";
-    while let Some((next_key_wo_prefix, _val)) = kv_get_next(env, prefix, &cursor) {
+    while let Some((next_key_wo_prefix, _val)) = kv_get_next(env, prefix, &cursor)? {
         let mut key = Vec::with_capacity(prefix.len() + next_key_wo_prefix.len());
         key.extend_from_slice(prefix);
         key.extend_from_slice(&next_key_wo_prefix);
 
-        kv_delete(env, &key);
+        kv_delete(env, &key)?;
         cursor = next_key_wo_prefix;
     }
+    Ok(())
 }
@@ -1,110 +1,113 @@
-use std::panic::panic_any;
 use crate::{bcat, consensus};
+use crate::consensus::consensus_error::{ExecError, ExecResult};
 use crate::consensus::{bic::{coin::BURN_ADDRESS}};
 use crate::consensus::consensus_kv::{kv_get, kv_put, kv_increment, kv_exists};
 use vecpak::{encode, decode, Term};
 
-pub fn balance_burnt(env: &mut crate::consensus::consensus_apply::ApplyEnv, collection: &[u8], token: &[u8]) -> i128 {
+pub fn balance_burnt(env: &mut crate::consensus::consensus_apply::ApplyEnv, collection: &[u8], token: &[u8]) -> ExecResult<i128> {
     balance(env, &BURN_ADDRESS, collection, token)
 }
 
-pub fn balance(env: &mut crate::consensus::consensus_apply::ApplyEnv, address: &[u8], collection: &[u8], token: &[u8]) -> i128 {
-    match kv_get(env, &bcat(&[b"account:", address, b":nft:", collection, b":", token])) {
-        Some(amount) => std::str::from_utf8(&amount).unwrap().parse::<i128>().unwrap_or_else(|_| panic_any("invalid_balance")),
-        None => 0
+pub fn balance(env: &mut crate::consensus::consensus_apply::ApplyEnv, address: &[u8], collection: &[u8], token: &[u8]) -> ExecResult<i128> {
+    match kv_get(env, &bcat(&[b"account:", address, b":nft:", collection, b":", token]))? {
+        Some(amount) => std::str::from_utf8(&amount).unwrap().parse::<i128>().map_err(|_| ExecError::Bic("invalid_balance")),
+        None => Ok(0)
     }
 }
 
-pub fn view_account(env: &mut crate::consensus::consensus_apply::ApplyEnv, collection: &[u8]) -> Option<Vec<u8>> {
+pub fn view_account(env: &mut crate::consensus::consensus_apply::ApplyEnv, collection: &[u8]) -> ExecResult<Option<Vec<u8>>> {
     kv_get(env, &bcat(&[b"nft:", collection, b":view_account"]))
 }
 
-pub fn exists(env: &mut crate::consensus::consensus_apply::ApplyEnv, collection: &[u8]) -> bool {
-    kv_get(env, &bcat(&[b"nft:", collection, b":view_account"])).is_some()
+pub fn exists(env: &mut crate::consensus::consensus_apply::ApplyEnv, collection: &[u8]) -> ExecResult<bool> {
+    Ok(kv_get(env, &bcat(&[b"nft:", collection, b":view_account"]))?.is_some())
 }
 
-pub fn soulbound(env: &mut crate::consensus::consensus_apply::ApplyEnv, collection: &[u8]) -> bool {
-    match kv_get(env, &bcat(&[b"nft:", collection, b":soulbound"])).as_deref() {
-        Some(b"true") => true,
-        _ => false
+pub fn soulbound(env: &mut crate::consensus::consensus_apply::ApplyEnv, collection: &[u8]) -> ExecResult<bool> {
+    match kv_get(env, &bcat(&[b"nft:", collection, b":soulbound"]))?.as_deref() {
+        Some(b"true") => Ok(true),
+        _ => Ok(false)
     }
 }
 
-pub fn has_permission(env: &mut crate::consensus::consensus_apply::ApplyEnv, collection: &[u8], signer: &[u8]) -> bool {
-    match view_account(env, collection) {
-        None => false,
+pub fn has_permission(env: &mut crate::consensus::consensus_apply::ApplyEnv, collection: &[u8], signer: &[u8]) -> ExecResult<bool> {
+    match view_account(env, collection)? {
+        None => Ok(false),
         Some(account) => {
-            account == signer
+            Ok(account == signer)
         }
     }
 }
 
-pub fn call_transfer(env: &mut crate::consensus::consensus_apply::ApplyEnv, args: Vec<Vec<u8>>) {
-    if args.len() != 4 { panic_any("invalid_args") }
+pub fn call_transfer(env: &mut crate::consensus::consensus_apply::ApplyEnv, args: Vec<Vec<u8>>) -> ExecResult<()> {
+    if args.len() != 4 { return Err(ExecError::Bic("invalid_args")) }
     let receiver = args[0].as_slice();
     let amount = args[1].as_slice();
-    let amount = std::str::from_utf8(&amount).ok().and_then(|s| s.parse::<i128>().ok()).unwrap_or_else(|| panic_any("invalid_amount"));
+    let amount = std::str::from_utf8(&amount).ok().and_then(|s| s.parse::<i128>().ok()).ok_or(ExecError::Bic("invalid_amount"))?;
     let collection = args[2].as_slice();
     let token = args[3].as_slice();
 
-    if receiver.len() != 48 { panic_any("invalid_receiver_pk") }
-    if !(consensus::bls12_381::validate_public_key(receiver) || receiver == &BURN_ADDRESS) { panic_any("invalid_receiver_pk") }
-    if amount <= 0 { panic_any("invalid_amount") }
-    if amount > balance(env, &env.caller_env.account_caller.clone(), &collection, &token) { panic_any("insufficient_tokens") }
+    if receiver.len() != 48 { return Err(ExecError::Bic("invalid_receiver_pk")) }
+    if !(consensus::bls12_381::validate_public_key(receiver) || receiver == &BURN_ADDRESS) { return Err(ExecError::Bic("invalid_receiver_pk")) }
+    if amount <= 0 { return Err(ExecError::Bic("invalid_amount")) }
+    if amount > balance(env, &env.caller_env.account_caller.clone(), &collection, &token)? { return Err(ExecError::Bic("insufficient_tokens")) }
 
-    if soulbound(env, collection) { panic_any("soulbound") }
+    if soulbound(env, collection)? { return Err(ExecError::Bic("soulbound")) }
 
-    kv_increment(env, &bcat(&[b"account:", &env.caller_env.account_caller, b":nft:", collection, b":", token]), -amount);
-    kv_increment(env, &bcat(&[b"account:", receiver, b":nft:", collection, b":", token]), amount);
+    kv_increment(env, &bcat(&[b"account:", &env.caller_env.account_caller, b":nft:", collection, b":", token]), -amount)?;
+    kv_increment(env, &bcat(&[b"account:", receiver, b":nft:", collection, b":", token]), amount)?;
+    Ok(())
 }
 
-pub fn call_create_collection(env: &mut crate::consensus::consensus_apply::ApplyEnv, args: Vec<Vec<u8>>) {
-    if args.len() < 2 { panic_any("invalid_args") }
+pub fn call_create_collection(env: &mut crate::consensus::consensus_apply::ApplyEnv, args: Vec<Vec<u8>>) -> ExecResult<()> {
+    if args.len() < 2 { return Err(ExecError::Bic("invalid_args")) }
     let collection_original = args[0].as_slice();
     let soulbound = args.get(1).and_then(|v| if v.is_empty() { None } else { Some(v.as_slice()) }).unwrap_or(b"false");
 
     let collection: Vec<u8> = collection_original.iter().copied().filter(u8::is_ascii_alphanumeric).collect();
-    if collection_original != collection.as_slice() { panic_any("invalid_collection") }
-    if collection.len() < 1 { panic_any("collection_too_short") }
-    if collection.len() > 32 { panic_any("collection_too_long") }
+    if collection_original != collection.as_slice() { return Err(ExecError::Bic("invalid_collection")) }
+    if collection.len() < 1 { return Err(ExecError::Bic("collection_too_short")) }
+    if collection.len() > 32 { return Err(ExecError::Bic("collection_too_long")) }
 
-    if !consensus::bic::coin_symbol_reserved::is_free(&collection, &env.caller_env.account_caller) { panic_any("collection_reserved") }
-    if exists(env, &collection) { panic_any("collection_exists") }
+    if !consensus::bic::coin_symbol_reserved::is_free(&collection, &env.caller_env.account_caller) { return Err(ExecError::Bic("collection_reserved")) }
+    if exists(env, &collection)? { return Err(ExecError::Bic("collection_exists")) }
 
-    kv_put(env, &bcat(&[b"nft:", &collection, b":view_account"]), &env.caller_env.account_caller.clone());
+    kv_put(env, &bcat(&[b"nft:", &collection, b":view_account"]), &env.caller_env.account_caller.clone())?;
 
-    if soulbound == b"true" { kv_put(env, &bcat(&[b"nft:", &collection, b":soulbound"]), b"true") }
+    if soulbound == b"true" { kv_put(env, &bcat(&[b"nft:", &collection, b":soulbound"]), b"true")?; }
+    Ok(())
 }
 
-pub fn call_mint(env: &mut crate::consensus::consensus_apply::ApplyEnv, args: Vec<Vec<u8>>) {
-    if args.len() != 4 { panic_any("invalid_args") }
+pub fn call_mint(env: &mut crate::consensus::consensus_apply::ApplyEnv, args: Vec<Vec<u8>>) -> ExecResult<()> {
+    if args.len() != 4 { return Err(ExecError::Bic("invalid_args")) }
     let receiver = args[0].as_slice();
     let amount = args[1].as_slice();
-    let amount = std::str::from_utf8(&amount).ok().and_then(|s| s.parse::<i128>().ok()).unwrap_or_else(|| panic_any("invalid_amount"));
+    let amount = std::str::from_utf8(&amount).ok().and_then(|s| s.parse::<i128>().ok()).ok_or(ExecError::Bic("invalid_amount"))?;
     let collection = args[2].as_slice();
     let token = args[3].as_slice();
-    if receiver.len() != 48 { panic_any("invalid_receiver_pk") }
+    if receiver.len() != 48 { return Err(ExecError::Bic("invalid_receiver_pk")) }
 
-    if token.len() < 1 { panic_any("token_too_short") }
-    if token.len() > 32 { panic_any("token_too_long") }
+    if token.len() < 1 { return Err(ExecError::Bic("token_too_short")) }
+    if token.len() > 32 { return Err(ExecError::Bic("token_too_long")) }
 
-    match view_account(env, collection) {
-        None => panic_any("collection_doesnt_exist"),
+    match view_account(env, collection)? {
+        None => return Err(ExecError::Bic("collection_doesnt_exist")),
         Some(account) => {
             if account != env.caller_env.account_caller {
-                panic_any("no_permissions")
+                return Err(ExecError::Bic("no_permissions"))
             }
         }
     }
 
-    mint(env, receiver, amount, collection, token);
+    mint(env, receiver, amount, collection, token)
 }
 
-pub fn mint(env: &mut crate::consensus::consensus_apply::ApplyEnv, receiver: &[u8], amount: i128, collection: &[u8], token: &[u8]) {
-    if !(consensus::bls12_381::validate_public_key(receiver)) { panic_any("invalid_receiver_pk") }
-    if amount <= 0 { panic_any("invalid_amount") }
+pub fn mint(env: &mut crate::consensus::consensus_apply::ApplyEnv, receiver: &[u8], amount: i128, collection: &[u8], token: &[u8]) -> ExecResult<()> {
+    if !(consensus::bls12_381::validate_public_key(receiver)) { return Err(ExecError::Bic("invalid_receiver_pk")) }
+    if amount <= 0 { return Err(ExecError::Bic("invalid_amount")) }
 
-    if !exists(env, &collection) { panic_any("collection_doesnt_exist") }
+    if !exists(env, &collection)? { return Err(ExecError::Bic("collection_doesnt_exist")) }
 
-    kv_increment(env, &bcat(&[b"account:", receiver, b":nft:", collection, b":", token]), amount);
+    kv_increment(env, &bcat(&[b"account:", receiver, b":nft:", collection, b":", token]), amount)?;
+    Ok(())
 }
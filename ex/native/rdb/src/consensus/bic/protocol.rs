@@ -1,5 +1,6 @@
 use crate::consensus::bic::coin;
 use crate::consensus::consensus_kv;
+use crate::consensus::consensus_error::ExecResult;
 
 pub const FORKHEIGHT: u64 = 490_00000;
 pub const FORKHEIGHT_TESTNET: u64 = 0;
@@ -69,11 +70,12 @@ pub const WASM_MAX_GLOBALS: u32 = 100;
 pub const WASM_MAX_EXPORTS: u32 = 50;
 pub const WASM_MAX_IMPORTS: u32 = 50;
 
-pub fn pay_cost(env: &mut crate::consensus::consensus_apply::ApplyEnv, cost: i128) {
-    consensus_kv::kv_increment(env, &crate::bcat(&[b"account:", &env.caller_env.account_origin, b":balance:AMA"]), -cost);
+pub fn pay_cost(env: &mut crate::consensus::consensus_apply::ApplyEnv, cost: i128) -> ExecResult<()> {
+    consensus_kv::kv_increment(env, &crate::bcat(&[b"account:", &env.caller_env.account_origin, b":balance:AMA"]), -cost)?;
     // Increment validator / burn
-    consensus_kv::kv_increment(env, &crate::bcat(&[b"account:", &env.caller_env.entry_signer, b":balance:AMA"]), cost/2);
-    consensus_kv::kv_increment(env, &crate::bcat(&[b"account:", &coin::BURN_ADDRESS, b":balance:AMA"]), cost/2);
+    consensus_kv::kv_increment(env, &crate::bcat(&[b"account:", &env.caller_env.entry_signer, b":balance:AMA"]), cost/2)?;
+    consensus_kv::kv_increment(env, &crate::bcat(&[b"account:", &coin::BURN_ADDRESS, b":balance:AMA"]), cost/2)?;
+    Ok(())
 }
 
 pub fn tx_historical_cost(txu: &crate::model::tx::TXU) -> i128 {
@@ -1,10 +1,10 @@
 use crate::consensus::bic::protocol;
 use crate::consensus::consensus_apply::{ApplyEnv};
 use crate::consensus::consensus_kv::{kv_get, kv_get_prev, kv_get_next, kv_put, kv_exists, kv_delete, kv_set_bit, kv_increment, kv_get_prev_or_first};
+use crate::consensus::consensus_error::{ExecError, ExecResult};
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex, OnceLock};
 use std::time::{Duration, SystemTime};
-use std::panic::panic_any;
 use std::time::Instant;
 use lazy_static::lazy_static;
 use sha2::{Sha256, Digest};
@@ -47,54 +47,56 @@ lazy_static! {
     static ref ARTIFACT_CACHE: Mutex<HashMap<Vec<u8>, Vec<u8>>> = Mutex::new(HashMap::new());
 }
 
-fn set_return_value(applyenv: &mut ApplyEnv, return_value: Vec<u8>) {
+fn set_return_value(applyenv: &mut ApplyEnv, return_value: Vec<u8>) -> ExecResult<()> {
     if return_value.len() > protocol::WASM_MAX_PANIC_MSG_SIZE {
-        panic_any("exec_return_value_too_large")
+        return Err(ExecError::Wasm("exec_return_value_too_large"))
     }
-    applyenv.caller_env.call_return_value = return_value
+    applyenv.caller_env.call_return_value = return_value;
+    Ok(())
 }
 
-fn import_log_implementation(mut env: FunctionEnvMut<HostEnv>, ptr: i32, len: i32) {
+fn import_log_implementation(mut env: FunctionEnvMut<HostEnv>, ptr: i32, len: i32) -> Result<(), RuntimeError> {
     let (data, mut store) = env.data_and_store_mut();
-    let instance = data.instance.clone().unwrap_or_else(|| panic_any("exec_instance_not_injected"));
+    let instance = data.instance.clone().ok_or(ExecError::Wasm("exec_instance_not_injected"))?;
     let applyenv = unsafe { data.applyenv_ptr.as_mut() };
     let len = len as usize;
 
     if len <= 0 {
-        panic_any("exec_ptr_term_too_short")
+        return Err(ExecError::Wasm("exec_ptr_term_too_short").into())
     }
     if len > protocol::WASM_MAX_PTR_LEN {
-        panic_any("exec_ptr_term_too_long")
+        return Err(ExecError::Wasm("exec_ptr_term_too_long").into())
     }
 
-    crate::consensus::consensus_kv::storage_budget_decr(applyenv, protocol::COST_PER_BYTE_HISTORICAL * len as i128);
+    crate::consensus::consensus_kv::storage_budget_decr(applyenv, protocol::COST_PER_BYTE_HISTORICAL * len as i128)?;
     set_remaining_points(&mut store, &instance, applyenv.exec_left.max(0) as u64);
 
     let view = data.memory.clone().view(&store);
 
     let mut buffer = vec![0u8; len as usize];
-    view.read(ptr as u64, &mut buffer).unwrap_or_else(|_| panic_any("exec_log_invalid_ptr"));
-    log_line(applyenv, buffer.to_vec())
+    view.read(ptr as u64, &mut buffer).map_err(|_| ExecError::Wasm("exec_log_invalid_ptr"))?;
+    log_line(applyenv, buffer.to_vec())?;
+    Ok(())
 }
 
 fn import_return_implementation(mut env: FunctionEnvMut<HostEnv>, ptr: i32, len: i32) -> Result<(), RuntimeError> {
     let (data, mut store) = env.data_and_store_mut();
-    let instance = data.instance.clone().unwrap_or_else(|| panic_any("exec_instance_not_injected"));
+    let instance = data.instance.clone().ok_or(ExecError::Wasm("exec_instance_not_injected"))?;
     let applyenv = unsafe { data.applyenv_ptr.as_mut() };
     let len = len as usize;
 
     if len > protocol::WASM_MAX_PTR_LEN {
-        panic_any("exec_ptr_term_too_long")
+        return Err(ExecError::Wasm("exec_ptr_term_too_long").into())
     }
 
-    crate::consensus::consensus_kv::exec_budget_decr(applyenv, protocol::COST_PER_BYTE_HISTORICAL * len as i128);
+    crate::consensus::consensus_kv::exec_budget_decr(applyenv, protocol::COST_PER_BYTE_HISTORICAL * len as i128)?;
     set_remaining_points(&mut store, &instance, applyenv.exec_left.max(0) as u64);
 
     let view = data.memory.clone().view(&store);
 
     let mut buffer = vec![0u8; len as usize];
-    view.read(ptr as u64, &mut buffer).unwrap_or_else(|_| panic_any("exec_log_invalid_ptr"));
-    set_return_value(applyenv, buffer.to_vec());
+    view.read(ptr as u64, &mut buffer).map_err(|_| ExecError::Wasm("exec_log_invalid_ptr"))?;
+    set_return_value(applyenv, buffer.to_vec())?;
     Err(RuntimeError::new("EXIT_IMPORT_RETURN"))
 }
 
@@ -105,47 +107,47 @@ fn import_call_implementation(mut env: FunctionEnvMut<HostEnv>, table_ptr: i32,
 
         // Read table
         let mut count_buf = [0u8; 4];
-        view.read(table_ptr as u64, &mut count_buf).unwrap_or_else(|_| panic_any("exec_call_table_invalid_ptr"));
+        view.read(table_ptr as u64, &mut count_buf).map_err(|_| ExecError::Wasm("exec_call_table_invalid_ptr"))?;
         let arg_count = i32::from_le_bytes(count_buf) as usize;
-        if arg_count > 16 { panic_any("exec_call_too_many_args") }
+        if arg_count > 16 { return Err(ExecError::Wasm("exec_call_too_many_args").into()) }
 
         let mut final_args: Vec<Vec<u8>> = Vec::with_capacity(arg_count);
         for i in 0..arg_count {
             let offset = (table_ptr as u64) + 4 + (i as u64 * 8);
             let mut row_buf = [0u8; 8];
-            view.read(offset, &mut row_buf).unwrap_or_else(|_| panic_any("exec_read_call_table_error"));
+            view.read(offset, &mut row_buf).map_err(|_| ExecError::Wasm("exec_read_call_table_error"))?;
             let arg_ptr = i32::from_le_bytes(row_buf[0..4].try_into().unwrap());
             let arg_len = i32::from_le_bytes(row_buf[4..8].try_into().unwrap());
 
-            if arg_len as usize > protocol::WASM_MAX_PTR_LEN { panic_any("exec_call_ptr_term_too_long") }
+            if arg_len as usize > protocol::WASM_MAX_PTR_LEN { return Err(ExecError::Wasm("exec_call_ptr_term_too_long").into()) }
 
             let mut arg_data = vec![0u8; arg_len as usize];
-            view.read(arg_ptr as u64, &mut arg_data).unwrap_or_else(|_| panic_any("exec_read_call_table_data_error"));
+            view.read(arg_ptr as u64, &mut arg_data).map_err(|_| ExecError::Wasm("exec_read_call_table_data_error"))?;
             final_args.push(arg_data);
         }
 
         // Read extra table
         let mut final_args_extra: Vec<Vec<u8>> = Vec::new();
         if extra_table_ptr != 0 {
-            view.read(extra_table_ptr as u64, &mut count_buf).unwrap_or_else(|_| panic_any("exec_call_extra_invalid"));
+            view.read(extra_table_ptr as u64, &mut count_buf).map_err(|_| ExecError::Wasm("exec_call_extra_invalid"))?;
             let extra_count = i32::from_le_bytes(count_buf) as usize;
-            if extra_count > 16 { panic_any("exec_call_extra_too_many") }
+            if extra_count > 16 { return Err(ExecError::Wasm("exec_call_extra_too_many").into()) }
 
             for i in 0..extra_count {
                 let offset = (extra_table_ptr as u64) + 4 + (i as u64 * 8);
                 let mut row_buf = [0u8; 8];
-                view.read(offset, &mut row_buf).unwrap_or_else(|_| panic_any("exec_read_extra_row"));
+                view.read(offset, &mut row_buf).map_err(|_| ExecError::Wasm("exec_read_extra_row"))?;
                 let arg_ptr = i32::from_le_bytes(row_buf[0..4].try_into().unwrap());
                 let arg_len = i32::from_le_bytes(row_buf[4..8].try_into().unwrap());
 
                 let mut arg_data = vec![0u8; arg_len as usize];
-                view.read(arg_ptr as u64, &mut arg_data).unwrap_or_else(|_| panic_any("exec_read_extra_data"));
+                view.read(arg_ptr as u64, &mut arg_data).map_err(|_| ExecError::Wasm("exec_read_extra_data"))?;
                 final_args_extra.push(arg_data);
             }
         }
 
         // Process Arguments
-        if final_args.len() < 2 { panic_any("exec_call_missing_args"); }
+        if final_args.len() < 2 { return Err(ExecError::Wasm("exec_call_missing_args").into()) }
         let contract = final_args[0].clone();
         let function = final_args[1].clone();
         let args = final_args[2..].to_vec();
@@ -161,10 +163,10 @@ fn import_call_implementation(mut env: FunctionEnvMut<HostEnv>, table_ptr: i32,
 
 
     let (data, mut store) = env.data_and_store_mut();
-    let instance = data.instance.clone().unwrap_or_else(|| panic_any("exec_instance_not_injected"));
+    let instance = data.instance.clone().ok_or(ExecError::Wasm("exec_instance_not_injected"))?;
     let applyenv = unsafe { data.applyenv_ptr.as_mut() };
 
-    crate::consensus::consensus_kv::exec_budget_decr(applyenv, protocol::COST_PER_CALL);
+    crate::consensus::consensus_kv::exec_budget_decr(applyenv, protocol::COST_PER_CALL)?;
     set_remaining_points(&mut store, &instance, applyenv.exec_left.max(0) as u64);
 
     let og_account_caller = applyenv.caller_env.account_caller.clone();
@@ -177,11 +179,11 @@ fn import_call_implementation(mut env: FunctionEnvMut<HostEnv>, table_ptr: i32,
 
     let result = match crate::consensus::bls12_381::validate_public_key(contract.as_slice()) {
         false => {
-            crate::consensus::consensus_apply::call_bic(applyenv, contract, function, args, attached_symbol, attached_amount);
+            crate::consensus::consensus_apply::call_bic(applyenv, contract, function, args, attached_symbol, attached_amount)?;
             b"ok".to_vec()
         }
         true => {
-            crate::consensus::consensus_apply::call_wasmvm(applyenv, contract, function, args, attached_symbol, attached_amount)
+            crate::consensus::consensus_apply::call_wasmvm(applyenv, contract, function, args, attached_symbol, attached_amount)?
         }
     };
 
@@ -191,102 +193,102 @@ fn import_call_implementation(mut env: FunctionEnvMut<HostEnv>, table_ptr: i32,
     applyenv.caller_env.account_current = og_account_current;
 
     let view = data.memory.clone().view(&store);
-    view.write(10_000, &(result.len() as u32).to_le_bytes()).unwrap_or_else(|_| panic_any("exec_memwrite"));
-    view.write(10_004, &result).unwrap_or_else(|_| panic_any("exec_memwrite"));
+    view.write(10_000, &(result.len() as u32).to_le_bytes()).map_err(|_| ExecError::Wasm("exec_memwrite"))?;
+    view.write(10_004, &result).map_err(|_| ExecError::Wasm("exec_memwrite"))?;
 
     Ok(10_000)
 }
 
-fn build_prefixed_key(applyenv: &mut ApplyEnv, view: &MemoryView, ptr: i32, len: i32) -> Vec<u8> {
+fn build_prefixed_key(applyenv: &mut ApplyEnv, view: &MemoryView, ptr: i32, len: i32) -> ExecResult<Vec<u8>> {
     let mut key = vec![0u8; len as usize];
-    view.read(ptr as u64, &mut key).unwrap_or_else(|_| panic_any("exec_log_invalid_ptr"));
+    view.read(ptr as u64, &mut key).map_err(|_| ExecError::Wasm("exec_log_invalid_ptr"))?;
 
-    crate::bcat(&[&b"account:"[..], &applyenv.caller_env.account_current, &b":storage:"[..], &key])
+    Ok(crate::bcat(&[&b"account:"[..], &applyenv.caller_env.account_current, &b":storage:"[..], &key]))
 }
 
 fn import_storage_kv_put_implementation(mut env: FunctionEnvMut<HostEnv>, key_ptr: i32, key_len: i32, val_ptr: i32, val_len: i32) -> Result<(), RuntimeError> {
     let (data, mut store) = env.data_and_store_mut();
-    let instance = data.instance.clone().unwrap_or_else(|| panic_any("exec_instance_not_injected"));
+    let instance = data.instance.clone().ok_or(ExecError::Wasm("exec_instance_not_injected"))?;
     let applyenv = unsafe { data.applyenv_ptr.as_mut() };
 
     if key_len as usize > protocol::WASM_MAX_PTR_LEN {
-        panic_any("exec_ptr_term_too_long")
+        return Err(ExecError::Wasm("exec_ptr_term_too_long").into())
     }
     if val_len as usize > protocol::WASM_MAX_PTR_LEN {
-        panic_any("exec_ptr_term_too_long")
+        return Err(ExecError::Wasm("exec_ptr_term_too_long").into())
     }
 
     let view = data.memory.clone().view(&store);
-    let key = build_prefixed_key(applyenv, &view, key_ptr, key_len);
+    let key = build_prefixed_key(applyenv, &view, key_ptr, key_len)?;
     let mut value = vec![0u8; val_len as usize];
-    view.read(val_ptr as u64, &mut value).unwrap_or_else(|_| panic_any("exec_log_invalid_ptr"));
+    view.read(val_ptr as u64, &mut value).map_err(|_| ExecError::Wasm("exec_log_invalid_ptr"))?;
 
-    kv_put(applyenv, &key, &value);
+    kv_put(applyenv, &key, &value)?;
     Ok(())
 }
 
 fn import_storage_kv_increment_implementation(mut env: FunctionEnvMut<HostEnv>, key_ptr: i32, key_len: i32, val_ptr: i32, val_len: i32) -> Result<i32, RuntimeError> {
     let (data, mut store) = env.data_and_store_mut();
-    let instance = data.instance.clone().unwrap_or_else(|| panic_any("exec_instance_not_injected"));
+    let instance = data.instance.clone().ok_or(ExecError::Wasm("exec_instance_not_injected"))?;
     let applyenv = unsafe { data.applyenv_ptr.as_mut() };
 
     if key_len as usize > protocol::WASM_MAX_PTR_LEN {
-        panic_any("exec_ptr_term_too_long")
+        return Err(ExecError::Wasm("exec_ptr_term_too_long").into())
     }
     if val_len as usize > protocol::WASM_MAX_PTR_LEN {
-        panic_any("exec_ptr_term_too_long")
+        return Err(ExecError::Wasm("exec_ptr_term_too_long").into())
     }
 
     let view = data.memory.clone().view(&store);
-    let key = build_prefixed_key(applyenv, &view, key_ptr, key_len);
+    let key = build_prefixed_key(applyenv, &view, key_ptr, key_len)?;
     let mut value = vec![0u8; val_len as usize];
-    view.read(val_ptr as u64, &mut value).unwrap_or_else(|_| panic_any("exec_log_invalid_ptr"));
+    view.read(val_ptr as u64, &mut value).map_err(|_| ExecError::Wasm("exec_log_invalid_ptr"))?;
 
-    let value_int128 = std::str::from_utf8(&value).ok().and_then(|s| s.parse::<i128>().ok()).unwrap_or_else(|| panic_any("invalid_integer"));
-    let new_value = kv_increment(applyenv, &key, value_int128).to_string();
+    let value_int128 = std::str::from_utf8(&value).ok().and_then(|s| s.parse::<i128>().ok()).ok_or(ExecError::Wasm("invalid_integer"))?;
+    let new_value = kv_increment(applyenv, &key, value_int128)?.to_string();
     let new_value = new_value.as_bytes();
 
-    view.write(10_000, &(new_value.len() as u32).to_le_bytes()).unwrap_or_else(|_| panic_any("exec_memwrite"));
-    view.write(10_004, &new_value).unwrap_or_else(|_| panic_any("exec_memwrite"));
+    view.write(10_000, &(new_value.len() as u32).to_le_bytes()).map_err(|_| ExecError::Wasm("exec_memwrite"))?;
+    view.write(10_004, &new_value).map_err(|_| ExecError::Wasm("exec_memwrite"))?;
 
     Ok(10_000)
 }
 
 fn import_storage_kv_delete_implementation(mut env: FunctionEnvMut<HostEnv>, key_ptr: i32, key_len: i32) -> Result<(), RuntimeError> {
     let (data, mut store) = env.data_and_store_mut();
-    let instance = data.instance.clone().unwrap_or_else(|| panic_any("exec_instance_not_injected"));
+    let instance = data.instance.clone().ok_or(ExecError::Wasm("exec_instance_not_injected"))?;
     let applyenv = unsafe { data.applyenv_ptr.as_mut() };
 
     if key_len as usize > protocol::WASM_MAX_PTR_LEN {
-        panic_any("exec_ptr_term_too_long")
+        return Err(ExecError::Wasm("exec_ptr_term_too_long").into())
     }
 
     let view = data.memory.clone().view(&store);
-    let key = build_prefixed_key(applyenv, &view, key_ptr, key_len);
+    let key = build_prefixed_key(applyenv, &view, key_ptr, key_len)?;
 
-    kv_delete(applyenv, &key);
+    kv_delete(applyenv, &key)?;
 
     Ok(())
 }
 
 fn import_storage_kv_get_implementation(mut env: FunctionEnvMut<HostEnv>, ptr: i32, len: i32) -> Result<i32, RuntimeError> {
     let (data, mut store) = env.data_and_store_mut();
-    let instance = data.instance.clone().unwrap_or_else(|| panic_any("exec_instance_not_injected"));
+    let instance = data.instance.clone().ok_or(ExecError::Wasm("exec_instance_not_injected"))?;
     let applyenv = unsafe { data.applyenv_ptr.as_mut() };
 
     if len as usize > protocol::WASM_MAX_PTR_LEN {
-        panic_any("exec_ptr_term_too_long")
+        return Err(ExecError::Wasm("exec_ptr_term_too_long").into())
     }
 
     let view = data.memory.clone().view(&store);
-    let key = build_prefixed_key(applyenv, &view, ptr, len);
-    match kv_get(applyenv, &key) {
+    let key = build_prefixed_key(applyenv, &view, ptr, len)?;
+    match kv_get(applyenv, &key)? {
         None => {
-            view.write(10_000, &(-1i32).to_le_bytes()).unwrap_or_else(|_| panic_any("exec_memwrite"));
+            view.write(10_000, &(-1i32).to_le_bytes()).map_err(|_| ExecError::Wasm("exec_memwrite"))?;
         },
         Some(value) => {
-            view.write(10_000, &(value.len() as u32).to_le_bytes()).unwrap_or_else(|_| panic_any("exec_memwrite"));
-            view.write(10_004, &value).unwrap_or_else(|_| panic_any("exec_memwrite"));
+            view.write(10_000, &(value.len() as u32).to_le_bytes()).map_err(|_| ExecError::Wasm("exec_memwrite"))?;
+            view.write(10_004, &value).map_err(|_| ExecError::Wasm("exec_memwrite"))?;
         }
     }
     set_remaining_points(&mut store, &instance, applyenv.exec_left.max(0) as u64);
@@ -295,31 +297,31 @@ fn import_storage_kv_get_implementation(mut env: FunctionEnvMut<HostEnv>, ptr: i
 
 fn import_storage_kv_get_prev_implementation(mut env: FunctionEnvMut<HostEnv>, prefix_ptr: i32, prefix_len: i32, key_ptr: i32, key_len: i32) -> Result<i32, RuntimeError> {
     let (data, mut store) = env.data_and_store_mut();
-    let instance = data.instance.clone().unwrap_or_else(|| panic_any("exec_instance_not_injected"));
+    let instance = data.instance.clone().ok_or(ExecError::Wasm("exec_instance_not_injected"))?;
     let applyenv = unsafe { data.applyenv_ptr.as_mut() };
 
     if prefix_len as usize > protocol::WASM_MAX_PTR_LEN {
-        panic_any("exec_ptr_term_too_long")
+        return Err(ExecError::Wasm("exec_ptr_term_too_long").into())
     }
     if key_len as usize > protocol::WASM_MAX_PTR_LEN {
-        panic_any("exec_ptr_term_too_long")
+        return Err(ExecError::Wasm("exec_ptr_term_too_long").into())
     }
 
     let view = data.memory.clone().view(&store);
-    let prefix = build_prefixed_key(applyenv, &view, prefix_ptr, prefix_len);
+    let prefix = build_prefixed_key(applyenv, &view, prefix_ptr, prefix_len)?;
     let mut key = vec![0u8; key_len as usize];
-    view.read(key_ptr as u64, &mut key).unwrap_or_else(|_| panic_any("exec_log_invalid_ptr"));
+    view.read(key_ptr as u64, &mut key).map_err(|_| ExecError::Wasm("exec_log_invalid_ptr"))?;
 
-    match kv_get_prev(applyenv, &prefix, &key) {
+    match kv_get_prev(applyenv, &prefix, &key)? {
         None => {
-            view.write(10_000, &(-1i32).to_le_bytes()).unwrap_or_else(|_| panic_any("exec_memwrite"));
+            view.write(10_000, &(-1i32).to_le_bytes()).map_err(|_| ExecError::Wasm("exec_memwrite"))?;
         },
         Some((prev_key, value)) => {
-            view.write(10_000, &(prev_key.len() as u32).to_le_bytes()).unwrap_or_else(|_| panic_any("exec_memwrite"));
-            view.write(10_000 + 4, &prev_key).unwrap_or_else(|_| panic_any("exec_memwrite"));
+            view.write(10_000, &(prev_key.len() as u32).to_le_bytes()).map_err(|_| ExecError::Wasm("exec_memwrite"))?;
+            view.write(10_000 + 4, &prev_key).map_err(|_| ExecError::Wasm("exec_memwrite"))?;
 
-            view.write(10_000 + 4 + prev_key.len() as u64, &(value.len() as u32).to_le_bytes()).unwrap_or_else(|_| panic_any("exec_memwrite"));
-            view.write(10_000 + 4 + prev_key.len() as u64 + 4, &value).unwrap_or_else(|_| panic_any("exec_memwrite"));
+            view.write(10_000 + 4 + prev_key.len() as u64, &(value.len() as u32).to_le_bytes()).map_err(|_| ExecError::Wasm("exec_memwrite"))?;
+            view.write(10_000 + 4 + prev_key.len() as u64 + 4, &value).map_err(|_| ExecError::Wasm("exec_memwrite"))?;
         }
     }
     set_remaining_points(&mut store, &instance, applyenv.exec_left.max(0) as u64);
@@ -328,31 +330,31 @@ fn import_storage_kv_get_prev_implementation(mut env: FunctionEnvMut<HostEnv>, p
 
 fn import_storage_kv_get_next_implementation(mut env: FunctionEnvMut<HostEnv>, prefix_ptr: i32, prefix_len: i32, key_ptr: i32, key_len: i32) -> Result<i32, RuntimeError> {
     let (data, mut store) = env.data_and_store_mut();
-    let instance = data.instance.clone().unwrap_or_else(|| panic_any("exec_instance_not_injected"));
+    let instance = data.instance.clone().ok_or(ExecError::Wasm("exec_instance_not_injected"))?;
     let applyenv = unsafe { data.applyenv_ptr.as_mut() };
 
     if prefix_len as usize > protocol::WASM_MAX_PTR_LEN {
-        panic_any("exec_ptr_term_too_long")
+        return Err(ExecError::Wasm("exec_ptr_term_too_long").into())
     }
     if key_len as usize > protocol::WASM_MAX_PTR_LEN {
-        panic_any("exec_ptr_term_too_long")
+        return Err(ExecError::Wasm("exec_ptr_term_too_long").into())
     }
 
     let view = data.memory.clone().view(&store);
-    let prefix = build_prefixed_key(applyenv, &view, prefix_ptr, prefix_len);
+    let prefix = build_prefixed_key(applyenv, &view, prefix_ptr, prefix_len)?;
     let mut key = vec![0u8; key_len as usize];
-    view.read(key_ptr as u64, &mut key).unwrap_or_else(|_| panic_any("exec_log_invalid_ptr"));
+    view.read(key_ptr as u64, &mut key).map_err(|_| ExecError::Wasm("exec_log_invalid_ptr"))?;
 
-    match kv_get_next(applyenv, &prefix, &key) {
+    match kv_get_next(applyenv, &prefix, &key)? {
         None => {
-            view.write(10_000, &(-1i32).to_le_bytes()).unwrap_or_else(|_| panic_any("exec_memwrite"));
+            view.write(10_000, &(-1i32).to_le_bytes()).map_err(|_| ExecError::Wasm("exec_memwrite"))?;
         },
         Some((next_key, value)) => {
-            view.write(10_000, &(next_key.len() as u32).to_le_bytes()).unwrap_or_else(|_| panic_any("exec_memwrite"));
-            view.write(10_000 + 4, &next_key).unwrap_or_else(|_| panic_any("exec_memwrite"));
+            view.write(10_000, &(next_key.len() as u32).to_le_bytes()).map_err(|_| ExecError::Wasm("exec_memwrite"))?;
+            view.write(10_000 + 4, &next_key).map_err(|_| ExecError::Wasm("exec_memwrite"))?;
 
-            view.write(10_000 + 4 + next_key.len() as u64, &(value.len() as u32).to_le_bytes()).unwrap_or_else(|_| panic_any("exec_memwrite"));
-            view.write(10_000 + 4 + next_key.len() as u64 + 4, &value).unwrap_or_else(|_| panic_any("exec_memwrite"));
+            view.write(10_000 + 4 + next_key.len() as u64, &(value.len() as u32).to_le_bytes()).map_err(|_| ExecError::Wasm("exec_memwrite"))?;
+            view.write(10_000 + 4 + next_key.len() as u64 + 4, &value).map_err(|_| ExecError::Wasm("exec_memwrite"))?;
         }
     }
     set_remaining_points(&mut store, &instance, applyenv.exec_left.max(0) as u64);
@@ -361,17 +363,17 @@ fn import_storage_kv_get_next_implementation(mut env: FunctionEnvMut<HostEnv>, p
 
 fn import_storage_kv_exists_implementation(mut env: FunctionEnvMut<HostEnv>, ptr: i32, len: i32) -> Result<i32, RuntimeError> {
     let (data, mut store) = env.data_and_store_mut();
-    let instance = data.instance.clone().unwrap_or_else(|| panic_any("exec_instance_not_injected"));
+    let instance = data.instance.clone().ok_or(ExecError::Wasm("exec_instance_not_injected"))?;
     let applyenv = unsafe { data.applyenv_ptr.as_mut() };
 
     if len as usize > protocol::WASM_MAX_PTR_LEN {
-        panic_any("exec_ptr_term_too_long")
+        return Err(ExecError::Wasm("exec_ptr_term_too_long").into())
     }
 
     let view = data.memory.clone().view(&store);
-    let key = build_prefixed_key(applyenv, &view, ptr, len);
+    let key = build_prefixed_key(applyenv, &view, ptr, len)?;
 
-    let result = kv_exists(applyenv, &key);
+    let result = kv_exists(applyenv, &key)?;
     set_remaining_points(&mut store, &instance, applyenv.exec_left.max(0) as u64);
     match result {
         true => Ok(1),
@@ -410,7 +412,7 @@ fn as_read_string(view: &MemoryView, ptr: i32) -> String {
 
 fn as_abort_implementation(mut env: FunctionEnvMut<HostEnv>, msg_ptr: i32, filename_ptr: i32, line: i32, column: i32) -> Result<(), RuntimeError> {
     let (data, mut store) = env.data_and_store_mut();
-    let instance = data.instance.clone().unwrap_or_else(|| panic_any("exec_instance_not_injected"));
+    let instance = data.instance.clone().ok_or(ExecError::Wasm("exec_instance_not_injected"))?;
     let view = data.memory.clone().view(&store);
     let applyenv = unsafe { data.applyenv_ptr.as_mut() };
 
@@ -423,15 +425,12 @@ fn as_abort_implementation(mut env: FunctionEnvMut<HostEnv>, msg_ptr: i32, filen
         msg, filename, line, column
     );
 
-    crate::consensus::consensus_kv::exec_budget_decr(applyenv, protocol::COST_PER_BYTE_HISTORICAL * full_error_msg.len() as i128);
+    crate::consensus::consensus_kv::exec_budget_decr(applyenv, protocol::COST_PER_BYTE_HISTORICAL * full_error_msg.len() as i128)?;
     set_remaining_points(&mut store, &instance, applyenv.exec_left.max(0) as u64);
 
-    log_line(applyenv, full_error_msg.as_bytes().to_vec());
+    log_line(applyenv, full_error_msg.as_bytes().to_vec())?;
 
-    //TODO: is this OK?
-    panic_any("as_abort");
-    Ok(())
-    //Err(RuntimeError::new("as_abort"))
+    Err(ExecError::Wasm("as_abort").into())
 }
 
 fn as_seed_implementation(mut env: FunctionEnvMut<HostEnv>) -> Result<f64, RuntimeError> {
@@ -441,20 +440,21 @@ fn as_seed_implementation(mut env: FunctionEnvMut<HostEnv>) -> Result<f64, Runti
     Ok(applyenv.caller_env.seedf64)
 }
 
-fn log_line(applyenv: &mut ApplyEnv, line: Vec<u8>) {
+fn log_line(applyenv: &mut ApplyEnv, line: Vec<u8>) -> ExecResult<()> {
     let len = line.len();
     if len > protocol::LOG_MSG_SIZE {
-        panic_any("exec_log_msg_size_exceeded")
+        return Err(ExecError::Wasm("exec_log_msg_size_exceeded"))
     }
     if (applyenv.logs_size.saturating_add(len)) > protocol::LOG_TOTAL_SIZE {
-        panic_any("exec_logs_total_size_exceeded")
+        return Err(ExecError::Wasm("exec_logs_total_size_exceeded"))
     }
     if applyenv.logs.len() > protocol::LOG_TOTAL_ELEMENTS {
-        panic_any("exec_logs_total_elements_exceeded")
+        return Err(ExecError::Wasm("exec_logs_total_elements_exceeded"))
     }
 
     applyenv.logs.push(line);
-    applyenv.logs_size += len
+    applyenv.logs_size += len;
+    Ok(())
 }
 
 pub fn check_module_limits(wasm_bytes: &[u8]) -> Result<(), String> {
@@ -512,17 +512,19 @@ pub fn check_module_limits(wasm_bytes: &[u8]) -> Result<(), String> {
     Ok(())
 }
 
-pub fn validate_contract(env: &mut ApplyEnv, wasm_bytes: &[u8]) {
-    if let Err(e) = check_module_limits(wasm_bytes) {
-        panic_any(e)
+pub fn validate_contract(env: &mut ApplyEnv, wasm_bytes: &[u8]) -> ExecResult<()> {
+    // Limit violations used to surface as String panics, which receipts record as unknown
+    if check_module_limits(wasm_bytes).is_err() {
+        return Err(ExecError::Wasm("unknown"))
     }
 
     let engine = make_engine(env.exec_left.max(0) as u64);
     let mut store = Store::new(engine);
 
-    let module = Module::new(&store, wasm_bytes).unwrap_or_else(|_| panic_any("exec_invalid_module"));
+    let module = Module::new(&store, wasm_bytes).map_err(|_| ExecError::Wasm("exec_invalid_module"))?;
 
-    setup_wasm_instance(env, &module, &mut store, true, &[]);
+    setup_wasm_instance(env, &module, &mut store, true, &[])?;
+    Ok(())
 }
 
 fn cost_function(operator: &Operator) -> u64 {
@@ -581,20 +583,20 @@ fn make_engine(exec_remaining: u64) -> Engine {
         .into()
 }
 
-pub fn setup_wasm_instance(env: &mut ApplyEnv, module: &Module, store: &mut Store, readonly: bool, function_args: &[Vec<u8>]) -> (Instance, Vec<Value>) {
+pub fn setup_wasm_instance(env: &mut ApplyEnv, module: &Module, store: &mut Store, readonly: bool, function_args: &[Vec<u8>]) -> ExecResult<(Instance, Vec<Value>)> {
     // Setup Memory
-    let memory = Memory::new(store, MemoryType::new(Pages(2), Some(Pages(30)), false)).unwrap_or_else(|_| panic_any("exec_memory_alloc"));
+    let memory = Memory::new(store, MemoryType::new(Pages(2), Some(Pages(30)), false)).map_err(|_| ExecError::Wasm("exec_memory_alloc"))?;
 
     let mut wasm_arg_ptrs: Vec<Value> = Vec::new();
     {
         let view = memory.view(store);
-        inject_env_data(&view, env);
+        inject_env_data(&view, env)?;
         let mut current_offset: u64 = 10_000;
         for arg_bytes in function_args {
             // Write the length + bytes
             let len = arg_bytes.len() as i32;
-            view.write(current_offset, &len.to_le_bytes()).unwrap_or_else(|_| panic_any("exec_arg_len_write"));
-            view.write(current_offset + 4, arg_bytes).unwrap_or_else(|_| panic_any("exec_arg_write"));
+            view.write(current_offset, &len.to_le_bytes()).map_err(|_| ExecError::Wasm("exec_arg_len_write"))?;
+            view.write(current_offset + 4, arg_bytes).map_err(|_| ExecError::Wasm("exec_arg_write"))?;
             // Save the POINTER (i32) to pass to the function call later
             wasm_arg_ptrs.push(Value::I32(current_offset as i32));
             // Advance offset
@@ -649,60 +651,64 @@ pub fn setup_wasm_instance(env: &mut ApplyEnv, module: &Module, store: &mut Stor
     };
 
     // Create Instance
-    let instance = Instance::new(store, module, &import_object).unwrap_or_else(|e| {
-        log_line(env, e.to_string().into_bytes());
-        panic_any("exec_instance")
-    });
+    let instance = match Instance::new(store, module, &import_object) {
+        Ok(instance) => instance,
+        Err(e) => {
+            log_line(env, e.to_string().into_bytes())?;
+            return Err(ExecError::Wasm("exec_instance"))
+        }
+    };
     host_env.as_mut(store).instance = Some(instance.clone());
-    (instance, wasm_arg_ptrs)
+    Ok((instance, wasm_arg_ptrs))
 }
 
-fn inject_env_data(view: &MemoryView, env: &ApplyEnv) {
-    let mut w = |offset: u64, data: &[u8]| {
-        view.write(offset, data).unwrap_or_else(|_| panic_any("exec_init_memwrite"))
+fn inject_env_data(view: &MemoryView, env: &ApplyEnv) -> ExecResult<()> {
+    let w = |offset: u64, data: &[u8]| {
+        view.write(offset, data).map_err(|_| ExecError::Wasm("exec_init_memwrite"))
     };
 
     //Reserve first 1024 bytes
     //Reserve first page 65536 bytes
-    w(1_100, &(env.caller_env.seed.len() as u32).to_le_bytes());
-    w(1_104, &env.caller_env.seed);
+    w(1_100, &(env.caller_env.seed.len() as u32).to_le_bytes())?;
+    w(1_104, &env.caller_env.seed)?;
 
     // Entry
-    w(2_000, &env.caller_env.entry_slot.to_le_bytes());
-    w(2_010, &env.caller_env.entry_height.to_le_bytes());
-    w(2_020, &env.caller_env.entry_epoch.to_le_bytes());
+    w(2_000, &env.caller_env.entry_slot.to_le_bytes())?;
+    w(2_010, &env.caller_env.entry_height.to_le_bytes())?;
+    w(2_020, &env.caller_env.entry_epoch.to_le_bytes())?;
     //
-    w(2_100, &(env.caller_env.entry_signer.len() as u32).to_le_bytes());
-    w(2_104, &env.caller_env.entry_signer);
-    w(2_200, &(env.caller_env.entry_prev_hash.len() as u32).to_le_bytes());
-    w(2_204, &env.caller_env.entry_prev_hash);
-    w(2_300, &(env.caller_env.entry_vr.len() as u32).to_le_bytes());
-    w(2_304, &env.caller_env.entry_vr);
-    w(2_400, &(env.caller_env.entry_dr.len() as u32).to_le_bytes());
-    w(2_404, &env.caller_env.entry_dr);
+    w(2_100, &(env.caller_env.entry_signer.len() as u32).to_le_bytes())?;
+    w(2_104, &env.caller_env.entry_signer)?;
+    w(2_200, &(env.caller_env.entry_prev_hash.len() as u32).to_le_bytes())?;
+    w(2_204, &env.caller_env.entry_prev_hash)?;
+    w(2_300, &(env.caller_env.entry_vr.len() as u32).to_le_bytes())?;
+    w(2_304, &env.caller_env.entry_vr)?;
+    w(2_400, &(env.caller_env.entry_dr.len() as u32).to_le_bytes())?;
+    w(2_404, &env.caller_env.entry_dr)?;
 
     // TX
-    w(3_000, &env.caller_env.tx_nonce.to_le_bytes());
+    w(3_000, &env.caller_env.tx_nonce.to_le_bytes())?;
     //
-    w(3_100, &(env.caller_env.tx_signer.len() as u32).to_le_bytes());
-    w(3_104, &env.caller_env.tx_signer);
+    w(3_100, &(env.caller_env.tx_signer.len() as u32).to_le_bytes())?;
+    w(3_104, &env.caller_env.tx_signer)?;
 
     // Accounts
-    w(4_000, &(env.caller_env.account_current.len() as u32).to_le_bytes());
-    w(4_004, &env.caller_env.account_current);
-    w(4_100, &(env.caller_env.account_caller.len() as u32).to_le_bytes());
-    w(4_104, &env.caller_env.account_caller);
-    w(4_200, &(env.caller_env.account_origin.len() as u32).to_le_bytes());
-    w(4_204, &env.caller_env.account_origin);
+    w(4_000, &(env.caller_env.account_current.len() as u32).to_le_bytes())?;
+    w(4_004, &env.caller_env.account_current)?;
+    w(4_100, &(env.caller_env.account_caller.len() as u32).to_le_bytes())?;
+    w(4_104, &env.caller_env.account_caller)?;
+    w(4_200, &(env.caller_env.account_origin.len() as u32).to_le_bytes())?;
+    w(4_204, &env.caller_env.account_origin)?;
 
     // Assets
-    w(5_000, &(env.caller_env.attached_symbol.len() as u32).to_le_bytes());
-    w(5_004, &env.caller_env.attached_symbol);
-    w(5_100, &(env.caller_env.attached_amount.len() as u32).to_le_bytes());
-    w(5_104, &env.caller_env.attached_amount);
+    w(5_000, &(env.caller_env.attached_symbol.len() as u32).to_le_bytes())?;
+    w(5_004, &env.caller_env.attached_symbol)?;
+    w(5_100, &(env.caller_env.attached_amount.len() as u32).to_le_bytes())?;
+    w(5_104, &env.caller_env.attached_amount)?;
+    Ok(())
 }
 
-pub fn call_contract(env: &mut ApplyEnv, wasm_bytes: &[u8], function_name: String, function_args: Vec<Vec<u8>>) -> Vec<u8> {
+pub fn call_contract(env: &mut ApplyEnv, wasm_bytes: &[u8], function_name: String, function_args: Vec<Vec<u8>>) -> ExecResult<Vec<u8>> {
     env.caller_env.call_return_value = Vec::new();
 
     let engine = make_engine(env.exec_left.max(0) as u64);
@@ -717,45 +723,55 @@ pub fn call_contract(env: &mut ApplyEnv, wasm_bytes: &[u8], function_name: Strin
         if let Some(artifact_bytes) = cache.get(&wasm_hash) {
             // FAST PATH: Deserialize from cache
             unsafe { Module::deserialize(&store, artifact_bytes) }
-                .unwrap_or_else(|_| panic_any("exec_deserialize_err"))
+                .map_err(|_| ExecError::Wasm("exec_deserialize_err"))?
         } else {
             // SLOW PATH: Compile from scratch
-            let new_module = Module::new(&store, wasm_bytes).unwrap_or_else(|_| panic_any("exec_invalid_module"));
+            let new_module = Module::new(&store, wasm_bytes).map_err(|_| ExecError::Wasm("exec_invalid_module"))?;
 
             // Serialize and Cache
-            let artifact = new_module.serialize().unwrap_or_else(|_| panic_any("exec_serialize_err"));
+            let artifact = new_module.serialize().map_err(|_| ExecError::Wasm("exec_serialize_err"))?;
             cache.insert(wasm_hash, artifact.to_vec());
 
             new_module
         }
     };
 
-    let (instance, wasm_args) = setup_wasm_instance(env, &module, &mut store, false, &function_args);
+    let (instance, wasm_args) = setup_wasm_instance(env, &module, &mut store, false, &function_args)?;
 
-    let entry_to_call = instance.exports.get_function(&function_name).unwrap_or_else(|e| {
-        log_line(env, e.to_string().into_bytes());
-        panic_any("exec_function_not_found")
-    });
+    let entry_to_call = match instance.exports.get_function(&function_name) {
+        Ok(function) => function,
+        Err(e) => {
+            log_line(env, e.to_string().into_bytes())?;
+            return Err(ExecError::Wasm("exec_function_not_found"))
+        }
+    };
     let start = Instant::now();
     let call_result = entry_to_call.call(&mut store, &wasm_args);
     let duration = start.elapsed();
     //println!("call result {} {:?}", duration.as_millis(), call_result);
 
+    // A host function bailed out, exec_left is already where it stopped
+    if let Err(ref e) = call_result {
+        if let Some(&exec_error) = e.downcast_ref::<ExecError>() {
+            return Err(exec_error)
+        }
+    }
+
     let remaining = match get_remaining_points(&mut store, &instance) {
         MeteringPoints::Remaining(v) => v,
         MeteringPoints::Exhausted => {
             env.exec_left = 0;
-            panic_any("exec_insufficient_exec_budget")
+            return Err(ExecError::Budget("exec_insufficient_exec_budget"))
         },
     };
     env.exec_left = remaining as i128;
 
     match call_result {
-        Ok(_) => env.caller_env.call_return_value.clone(),
-        Err(ref e) if e.message() == "EXIT_IMPORT_RETURN" => env.caller_env.call_return_value.clone(),
+        Ok(_) => Ok(env.caller_env.call_return_value.clone()),
+        Err(ref e) if e.message() == "EXIT_IMPORT_RETURN" => Ok(env.caller_env.call_return_value.clone()),
         Err(err) => {
-            log_line(env, err.message().into_bytes());
-            panic_any("exec_error");
+            log_line(env, err.message().into_bytes())?;
+            Err(ExecError::Wasm("exec_error"))
         }
     }
 }
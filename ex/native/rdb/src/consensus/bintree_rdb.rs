@@ -19,7 +19,7 @@ use std::convert::TryInto;
 // ============================================================================
 
 #[inline]
-fn serialize_key(key: &NodeKey) -> Vec<u8> {
+pub fn serialize_key(key: &NodeKey) -> Vec<u8> {
     let mut v = Vec::with_capacity(34);
     v.extend_from_slice(&key.path);
     v.extend_from_slice(&key.len.to_be_bytes());
@@ -27,7 +27,7 @@ fn serialize_key(key: &NodeKey) -> Vec<u8> {
 }
 
 #[inline]
-fn deserialize_key(data: &[u8]) -> NodeKey {
+pub fn deserialize_key(data: &[u8]) -> NodeKey {
     let mut path = [0u8; 32];
     path.copy_from_slice(&data[0..32]);
     let len = u16::from_be_bytes([data[32], data[33]]);
@@ -251,12 +251,12 @@ impl<'env, 'a> RocksHubt<'env, 'a> {
     // ========================================================================
     fn insert_raw(&mut self, key: NodeKey, val: Hash) {
         let k = serialize_key(&key);
-        kv_put(self.env, &k, &val);
+        kv_put(self.env, &k, &val).expect("contractstate_tree put");
     }
 
     fn remove_raw(&mut self, key: &NodeKey) {
         let k = serialize_key(key);
-        kv_delete(self.env, &k);
+        kv_delete(self.env, &k).expect("contractstate_tree delete");
     }
 
     fn exists_raw(&self, key: &NodeKey) -> bool {
@@ -1,14 +1,17 @@
 use crate::{
-    consensus, BoundColumnFamily, MultiThreaded, Transaction, TransactionDB, TransactionOptions, WriteOptions
+    consensus, BoundColumnFamily, MultiThreaded, Transaction, TransactionDB, TransactionOptions, WriteOptions,
+    SnapshotWithThreadMode
 };
 
 use crate::consensus::bic::protocol;
 use crate::consensus::{bintree, consensus_kv};
 use crate::consensus::consensus_muts;
+use crate::consensus::consensus_parallel;
+use crate::consensus::consensus_trace;
 use crate::model::tx_receipt::TXReceipt;
 use std::clone;
-use std::collections::HashMap;
-use std::panic::panic_any;
+use std::collections::{HashMap, HashSet};
+use crate::consensus::consensus_error::{ExecError, ExecResult};
 
 pub struct CallerEnv {
     pub readonly: bool,
@@ -72,9 +75,12 @@ pub fn make_caller_env(
     }
 }
 
+pub type DbSnapshot<'db> = SnapshotWithThreadMode<'db, TransactionDB<MultiThreaded>>;
+
 pub struct ApplyEnv<'db> {
     pub caller_env: CallerEnv,
     pub db: &'db TransactionDB<MultiThreaded>,
+    pub snapshot: Option<&'db DbSnapshot<'db>>,
     pub cf: std::sync::Arc<BoundColumnFamily<'db>>,
     pub cf_name: Vec<u8>,
     pub cf_contractstate: std::sync::Arc<BoundColumnFamily<'db>>,
@@ -95,6 +101,10 @@ pub struct ApplyEnv<'db> {
     pub testnet: bool,
     pub testnet_peddlebikes: Vec<Vec<u8>>,
     pub readonly: bool,
+    // Set on the worker envs of parallel execution, None everywhere else
+    pub spec: Option<consensus_kv::Speculation<'db>>,
+    // Opt-in call tree of the executed txs, see consensus_trace
+    pub trace: Option<consensus_trace::Trace>,
 }
 
 impl<'db> ApplyEnv<'db> {
@@ -107,8 +117,9 @@ impl<'db> ApplyEnv<'db> {
         Vec<TXReceipt>,
         [u8; 32],
         [u8; 32],
+        Option<consensus_trace::Trace>,
     ) {
-        (self.txn, self.muts_final, self.muts_final_rev, self.receipts, root_receipts, root_contractstate)
+        (self.txn, self.muts_final, self.muts_final_rev, self.receipts, root_receipts, root_contractstate, self.trace)
     }
 }
 
@@ -123,6 +134,7 @@ pub fn make_apply_env<'db>(db: &'db TransactionDB<MultiThreaded>, txn: Transacti
     ApplyEnv {
         caller_env: make_caller_env(entry_signer, entry_prev_hash, entry_slot, entry_prev_slot, entry_height, entry_epoch, entry_vr, entry_vr_b3, entry_dr),
         db: db,
+        snapshot: None,
         cf: cf,
         cf_name: cf_name,
         cf_contractstate: cf_contractstate,
@@ -143,6 +155,8 @@ pub fn make_apply_env<'db>(db: &'db TransactionDB<MultiThreaded>, txn: Transacti
         testnet: testnet,
         testnet_peddlebikes: testnet_peddlebikes,
         readonly: false,
+        spec: None,
+        trace: None,
     }
 }
 
@@ -153,35 +167,119 @@ pub fn set_apply_env_tx<'db>(env: &mut ApplyEnv<'db>, tx_hash: &[u8; 32], tx_sig
     env.caller_env.account_origin = tx_signer.to_vec();
 }
 
+// Fixed size fields of an entry header and its txs, see validate_entry
+pub struct EntryFields {
+    pub signer: [u8; 48],
+    pub prev_hash: [u8; 32],
+    pub vr: [u8; 96],
+    pub vr_b3: [u8; 32],
+    pub dr: [u8; 32],
+    pub txs: Vec<TxFields>,
+}
+
+#[derive(Clone, Copy)]
+pub struct TxFields {
+    pub hash: [u8; 32],
+    pub signer: [u8; 48],
+}
+
+// Field sizes apply_entry and the views need as fixed arrays. The NIFs check them
+// up front and hand the arrays down, a malformed entry from a peer is rejected
+// before any env is built.
+pub fn validate_entry(entry: &crate::model::entry::Entry) -> Result<EntryFields, &'static str> {
+    let header = &entry.header;
+    Ok(EntryFields {
+        signer: header.signer.as_slice().try_into().map_err(|_| "entry_signer_len_wrong")?,
+        prev_hash: header.prev_hash.as_slice().try_into().map_err(|_| "entry_prev_hash_len_wrong")?,
+        vr: header.vr.as_slice().try_into().map_err(|_| "entry_vr_len_wrong")?,
+        vr_b3: *blake3::hash(&header.vr).as_bytes(),
+        dr: header.dr.as_slice().try_into().map_err(|_| "entry_dr_len_wrong")?,
+        txs: entry.txs.iter().map(validate_txu).collect::<Result<_, _>>()?,
+    })
+}
+
+pub fn validate_txu(txu: &crate::model::tx::TXU) -> Result<TxFields, &'static str> {
+    Ok(TxFields {
+        hash: txu.hash.as_slice().try_into().map_err(|_| "tx_hash_len_wrong")?,
+        signer: txu.tx.signer.as_slice().try_into().map_err(|_| "tx_signer_len_wrong")?,
+    })
+}
+
 pub fn apply_entry<'db, 'a>(db: &'db TransactionDB<MultiThreaded>, txn: Transaction<'db, TransactionDB<MultiThreaded>>,
-    entry: crate::model::entry::Entry, pk: &[u8], sk: &[u8],
-    testnet: bool, testnet_peddlebikes: Vec<Vec<u8>>,
-) -> (Transaction<'db, TransactionDB<MultiThreaded>>, Vec<consensus_muts::Mutation>, Vec<consensus_muts::Mutation>, Vec<TXReceipt>, [u8; 32], [u8; 32]) {
+    entry: crate::model::entry::Entry, fields: &EntryFields, pk: &[u8], sk: &[u8],
+    testnet: bool, testnet_peddlebikes: Vec<Vec<u8>>, trace: bool, speculate: bool,
+) -> ExecResult<(Transaction<'db, TransactionDB<MultiThreaded>>, Vec<consensus_muts::Mutation>, Vec<consensus_muts::Mutation>, Vec<TXReceipt>, [u8; 32], [u8; 32], Option<consensus_trace::Trace>)> {
     let cf_h = db.cf_handle("contractstate").unwrap();
     let cf2_h = db.cf_handle("contractstate").unwrap();
     let cf_tree_h = db.cf_handle("contractstate_tree").unwrap();
 
-    let entry_signer = entry.header.signer.as_slice().try_into().unwrap_or_else(|_| panic!("entry_signer_len_wrong"));
-    let entry_prev_hash = entry.header.prev_hash.as_slice().try_into().unwrap_or_else(|_| panic!("entry_prev_hash_len_wrong"));
-    let entry_vr = entry.header.vr.as_slice().try_into().unwrap_or_else(|_| panic!("entry_vr_len_wrong"));
-    let entry_vr_b3_binding = blake3::hash(&entry.header.vr);
-    let entry_vr_b3 = entry_vr_b3_binding.as_bytes().try_into().unwrap_or_else(|_| panic!("entry_vr_len_wrong"));
-    let entry_dr = entry.header.dr.as_slice().try_into().unwrap_or_else(|_| panic!("entry_dr_len_wrong"));
 
 
     let entry_epoch = entry.header.height / 100_000;
     let mut applyenv = make_apply_env(db, txn, cf_h, b"contractstate".to_vec(), cf2_h, cf_tree_h,
-        entry_signer, entry_prev_hash, entry.header.slot, entry.header.prev_slot, entry.header.height,
-        entry_epoch, entry_vr, entry_vr_b3, entry_dr,
+        &fields.signer, &fields.prev_hash, entry.header.slot, entry.header.prev_slot, entry.header.height,
+        entry_epoch, &fields.vr, &fields.vr_b3, &fields.dr,
         testnet, testnet_peddlebikes);
+    if trace {
+        applyenv.trace = Some(consensus_trace::Trace::default());
+    }
+
+    execute_txs(&mut applyenv, &entry.txs, &fields.txs, speculate)?;
+
+    call_exit(&mut applyenv)?;
+
+    let root_receipts = root_receipts(entry.txs.clone(), applyenv.receipts.clone());
+    let root_contractstate = update_and_root_contractstate(&mut applyenv);
+    Ok(applyenv.into_parts(root_receipts, root_contractstate))
+
+    //println!("r{:?} {}", applyenv.caller_env.entry_height, root_receipts(txus.clone(), applyenv.result_log.clone()).iter().map(|b| format!("{:02x}", b)).collect::<String>() );
+    //println!("c{:?} {}", applyenv.caller_env.entry_height, hubt_contractstate_root.iter().map(|b| format!("{:02x}", b)).collect::<String>());
+
+}
+
+// What a tx was charged: its historical cost plus what it used of the exec and
+// storage deposits (the rest is refunded)
+pub struct TxUsage {
+    pub historical: i128,
+    pub exec: i128,
+    pub storage: i128,
+}
+
+impl TxUsage {
+    fn of(applyenv: &ApplyEnv, historical: i128) -> Self {
+        TxUsage {
+            historical,
+            exec: applyenv.exec_max - applyenv.exec_left,
+            storage: applyenv.storage_max - applyenv.storage_left,
+        }
+    }
+}
 
-    call_txs_pre_upfront_cost(&mut applyenv, &entry.txs);
+// The tx part of apply_entry: nonces, upfront costs, then each call with its refunds and receipt.
+// Speculative reads skip the txn and go to the committed db, so speculate must be false
+// when the txn already holds writes from before this entry.
+// An ExecError here is not a failed tx but an entry that cannot be applied.
+fn execute_txs(applyenv: &mut ApplyEnv, txus: &[crate::model::tx::TXU], tx_fields: &[TxFields], speculate: bool) -> ExecResult<Vec<TxUsage>> {
+    call_txs_pre_upfront_cost(applyenv, txus, tx_fields)?;
+
+    // Speculate all txs in parallel against the state after the upfront costs, then walk
+    // them in order: a result whose reads were not written by an earlier tx is committed
+    // as is, anything else executes again below exactly as it would sequentially.
+    // Speculative runs are not traced, a traced env runs everything in order.
+    let base = consensus_parallel::base_state(applyenv);
+    let mut speculated = if speculate && txus.len() >= consensus_parallel::MIN_PARALLEL_TXS && applyenv.trace.is_none() {
+        consensus_parallel::speculate(applyenv.db, &applyenv.caller_env, applyenv.testnet, &applyenv.testnet_peddlebikes, &base, txus, tx_fields)
+    } else {
+        Vec::new()
+    };
+    let mut written: HashSet<Vec<u8>> = HashSet::new();
+    let mut written_upto = applyenv.muts_final.len();
+    let mut usage = Vec::with_capacity(txus.len());
 
-    for (i, txu) in entry.txs.clone().into_iter().enumerate() {
+    for (i, (txu, fields)) in txus.iter().cloned().zip(tx_fields).enumerate() {
         let tx_historical_cost = crate::consensus::bic::protocol::tx_historical_cost(&txu);
 
-        let tx_hash = txu.hash.as_slice().try_into().unwrap_or_else(|_| panic!("tx_hash_len_wrong"));
-        let tx_signer = txu.tx.signer.as_slice().try_into().unwrap_or_else(|_| panic!("tx_signer_len_wrong"));
+        let TxFields { hash: tx_hash, signer: tx_signer } = *fields;
         let tx_nonce = txu.tx.nonce;
         let action = txu.tx.action;
 
@@ -211,31 +309,43 @@ pub fn apply_entry<'db, 'a>(db: &'db TransactionDB<MultiThreaded>, txn: Transact
         applyenv.storage_left = protocol::AMA_1_DOLLAR;
         applyenv.storage_max = protocol::AMA_1_DOLLAR;
 
-        std::panic::set_hook(Box::new(|_| {}));
-        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-            match consensus::bls12_381::validate_public_key(contract.as_slice()) {
-                false => {
-                    //println!("{:?}->{:?} {:?} {:?}", String::from_utf8_lossy(&contract), String::from_utf8_lossy(&function), attached_amount, attached_symbol);
-                    call_bic(&mut applyenv, contract, function, args, attached_symbol, attached_amount);
-                    b"ok".to_vec()
-                }
-                true => {
-                    //println!("{:?}->{:?} {:?} {:?}", bs58::encode(&contract).into_string(), String::from_utf8_lossy(&function), attached_amount, attached_symbol);
-                    let result = call_wasmvm(&mut applyenv, contract, function, args, attached_symbol, attached_amount);
-                    result
-                }
+        for m in &applyenv.muts_final[written_upto..] {
+            written.insert(m.key().to_vec());
+        }
+        written_upto = applyenv.muts_final.len();
+        if let Some(spec) = speculated.get_mut(i).and_then(Option::take) {
+            if spec.is_valid(&written, applyenv.caller_env.call_counter) {
+                commit_speculated(applyenv, spec, tx_hash, tx_historical_cost)?;
+                usage.push(TxUsage::of(applyenv, tx_historical_cost));
+                continue;
+            }
+        }
+
+        consensus_trace::begin_tx(applyenv, i as u64, &tx_hash);
+        // Only an ExecError fails the tx, a panic is a bug and unwinds out of apply_entry
+        let res = match consensus::bls12_381::validate_public_key(contract.as_slice()) {
+            false => {
+                //println!("{:?}->{:?} {:?} {:?}", String::from_utf8_lossy(&contract), String::from_utf8_lossy(&function), attached_amount, attached_symbol);
+                call_bic(&mut *applyenv, contract, function, args, attached_symbol, attached_amount).map(|()| b"ok".to_vec())
+            }
+            true => {
+                //println!("{:?}->{:?} {:?} {:?}", bs58::encode(&contract).into_string(), String::from_utf8_lossy(&function), attached_amount, attached_symbol);
+                call_wasmvm(&mut *applyenv, contract, function, args, attached_symbol, attached_amount)
             }
-        }));
+        };
+        let res = res.map_err(|e| e.reason().as_bytes().to_vec());
 
         applyenv.exec_track = false;
 
         let exec_cost_total = ((tx_historical_cost + (applyenv.exec_max - applyenv.exec_left) + (applyenv.storage_max - applyenv.storage_left)) as u64).to_string();
+        usage.push(TxUsage::of(applyenv, tx_historical_cost));
 
         match res {
             Ok(result) => {
+                consensus_trace::end_tx(applyenv, None);
                 applyenv.muts_final.append(&mut applyenv.muts);
                 applyenv.muts_final_rev.append(&mut applyenv.muts_rev);
-                refund_exec_storage_deposit(&mut applyenv);
+                refund_exec_storage_deposit(applyenv)?;
 
                 //max logs 100
                 //max logs size 1024bytes
@@ -267,58 +377,73 @@ pub fn apply_entry<'db, 'a>(db: &'db TransactionDB<MultiThreaded>, txn: Transact
                 };
                 applyenv.receipts.push(receipt);
             }
-            Err(payload) => {
+            Err(reason) => {
+                consensus_trace::end_tx(applyenv, Some(&reason));
+                // revert leaves set bits in place, keep later speculative reads of them from passing
+                written.extend(applyenv.muts.iter().map(|m| m.key().to_vec()));
                 //TODO: refund storage costs on revert?
-                consensus_kv::revert(&mut applyenv);
-                refund_exec_storage_deposit(&mut applyenv);
-
-                if let Some(&s) = payload.downcast_ref::<&'static str>() {
-                    let receipt = TXReceipt {
-                        txid: tx_hash.into(),
-                        success: false,
-                        result: s.to_string().into(),
-                        exec_used: exec_cost_total.clone().into(),
-                        logs: applyenv.logs.clone(),
-                    };
-                    applyenv.receipts.push(receipt);
-                } else {
-                    let receipt = TXReceipt {
-                        txid: tx_hash.into(),
-                        success: false,
-                        result: b"unknown".into(),
-                        exec_used: exec_cost_total.clone().into(),
-                        logs: applyenv.logs.clone(),
-                    };
-                    applyenv.receipts.push(receipt);
-                }
+                consensus_kv::revert(applyenv);
+                refund_exec_storage_deposit(applyenv)?;
+
+                let receipt = TXReceipt {
+                    txid: tx_hash.into(),
+                    success: false,
+                    result: reason,
+                    exec_used: exec_cost_total.clone().into(),
+                    logs: applyenv.logs.clone(),
+                };
+                applyenv.receipts.push(receipt);
             }
         }
     }
+    Ok(usage)
+}
 
-    call_exit(&mut applyenv);
-
-    let root_receipts = root_receipts(entry.txs.clone(), applyenv.receipts.clone());
-    let root_contractstate = update_and_root_contractstate(&mut applyenv);
-    applyenv.into_parts(root_receipts, root_contractstate)
+// Lands a speculative result on the entry txn the way the sequential path would have:
+// tx writes and muts (only on success, a reverted tx leaves nothing), refund, receipt
+fn commit_speculated(applyenv: &mut ApplyEnv, spec: consensus_parallel::SpecOutcome, tx_hash: [u8; 32], tx_historical_cost: i128) -> ExecResult<()> {
+    applyenv.caller_env.call_counter += spec.call_counter_delta;
+    applyenv.exec_track = false;
+    applyenv.exec_left = spec.exec_left;
+    applyenv.storage_left = spec.storage_left;
+    applyenv.logs = spec.logs;
 
-    //println!("r{:?} {}", applyenv.caller_env.entry_height, root_receipts(txus.clone(), applyenv.result_log.clone()).iter().map(|b| format!("{:02x}", b)).collect::<String>() );
-    //println!("c{:?} {}", applyenv.caller_env.entry_height, hubt_contractstate_root.iter().map(|b| format!("{:02x}", b)).collect::<String>());
+    let exec_cost_total = ((tx_historical_cost + (applyenv.exec_max - applyenv.exec_left) + (applyenv.storage_max - applyenv.storage_left)) as u64).to_string();
 
+    let (success, result) = match spec.result {
+        Ok(result) => {
+            for (key, value) in &spec.writes {
+                match value {
+                    Some(value) => applyenv.txn.put_cf(&applyenv.cf, key, value),
+                    None => applyenv.txn.delete_cf(&applyenv.cf, key),
+                }.map_err(|_| ExecError::Kv("exec_kv_put_failed"))?;
+            }
+            applyenv.muts_final.extend(spec.muts);
+            applyenv.muts_final_rev.extend(spec.muts_rev);
+            (true, result)
+        }
+        Err(reason) => (false, reason),
+    };
+    refund_exec_storage_deposit(applyenv)?;
+
+    let receipt = TXReceipt {
+        txid: tx_hash.into(),
+        success,
+        result: result.into(),
+        exec_used: exec_cost_total.into(),
+        logs: applyenv.logs.clone(),
+    };
+    applyenv.receipts.push(receipt);
+    Ok(())
 }
 
-pub fn contract_view<'db, 'a>(db: &'db TransactionDB<MultiThreaded>, entry: crate::model::entry::Entry, view_pk: Vec<u8>,
-    contract: Vec<u8>, function: Vec<u8>, args: Vec<Vec<u8>>, testnet: bool,
+pub fn contract_view<'db, 'a>(db: &'db TransactionDB<MultiThreaded>, entry: crate::model::entry::Entry, fields: &EntryFields, view_pk: [u8; 48],
+    contract: Vec<u8>, function: Vec<u8>, args: Vec<Vec<u8>>, testnet: bool, snapshot: Option<&'db DbSnapshot<'db>>,
 ) -> (bool, Vec<u8>, Vec<Vec<u8>>) {
     let cf_h = db.cf_handle("contractstate").unwrap();
     let cf2_h = db.cf_handle("contractstate").unwrap();
     let cf_tree_h = db.cf_handle("contractstate_tree").unwrap();
 
-    let entry_signer = entry.header.signer.as_slice().try_into().unwrap_or_else(|_| panic!("entry_signer_len_wrong"));
-    let entry_prev_hash = entry.header.prev_hash.as_slice().try_into().unwrap_or_else(|_| panic!("entry_prev_hash_len_wrong"));
-    let entry_vr = entry.header.vr.as_slice().try_into().unwrap_or_else(|_| panic!("entry_vr_len_wrong"));
-    let entry_vr_b3_binding = blake3::hash(&entry.header.vr);
-    let entry_vr_b3 = entry_vr_b3_binding.as_bytes().try_into().unwrap_or_else(|_| panic!("entry_vr_len_wrong"));
-    let entry_dr = entry.header.dr.as_slice().try_into().unwrap_or_else(|_| panic!("entry_dr_len_wrong"));
 
     let txn_opts = TransactionOptions::default();
     let write_opts = WriteOptions::default();
@@ -326,12 +451,12 @@ pub fn contract_view<'db, 'a>(db: &'db TransactionDB<MultiThreaded>, entry: crat
 
     let entry_epoch = entry.header.height / 100_000;
     let mut applyenv = make_apply_env(db, txn, cf_h, b"contractstate".to_vec(), cf2_h, cf_tree_h,
-        entry_signer, entry_prev_hash, entry.header.slot, entry.header.prev_slot, entry.header.height,
-        entry_epoch, entry_vr, entry_vr_b3, entry_dr,
+        &fields.signer, &fields.prev_hash, entry.header.slot, entry.header.prev_slot, entry.header.height,
+        entry_epoch, &fields.vr, &fields.vr_b3, &fields.dr,
         testnet, Vec::new());
     applyenv.readonly = true;
+    applyenv.snapshot = snapshot;
 
-    let view_pk: [u8; 48] = view_pk.as_slice().try_into().unwrap_or_else(|_| panic!("view_pk_len_wrong"));
     applyenv.caller_env.tx_signer = view_pk;
     applyenv.caller_env.account_current = contract.to_vec();
     applyenv.caller_env.account_origin = view_pk.to_vec();
@@ -339,49 +464,26 @@ pub fn contract_view<'db, 'a>(db: &'db TransactionDB<MultiThreaded>, entry: crat
     applyenv.exec_left = protocol::AMA_10_CENT;
     applyenv.storage_left = protocol::AMA_1_DOLLAR;
 
-    std::panic::set_hook(Box::new(|_| {}));
-    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-        match consensus::bls12_381::validate_public_key(contract.as_slice()) {
-            false => {
-                call_bic(&mut applyenv, contract, function, args, None, None);
-                b"ok".to_vec()
-            }
-            true => {
-                let result = call_wasmvm(&mut applyenv, contract, function, args, None, None);
-                result
-            }
-        }
-    }));
+    let res = match consensus::bls12_381::validate_public_key(contract.as_slice()) {
+        false => call_bic(&mut applyenv, contract, function, args, None, None).map(|()| b"ok".to_vec()),
+        true => call_wasmvm(&mut applyenv, contract, function, args, None, None),
+    };
 
     applyenv.txn.rollback();
 
     match res {
-        Ok(result) => {
-            (true, result.into(), applyenv.logs.clone())
-        }
-        Err(payload) => {
-            if let Some(&s) = payload.downcast_ref::<&'static str>() {
-                (false, s.to_string().into(), applyenv.logs.clone())
-            } else {
-                (false, b"unknown".into(), applyenv.logs.clone())
-            }
-        }
+        Ok(result) => (true, result, applyenv.logs.clone()),
+        Err(e) => (false, e.reason().as_bytes().to_vec(), applyenv.logs.clone()),
     }
 }
 
-pub fn contract_validate<'db, 'a>(db: &'db TransactionDB<MultiThreaded>, entry: crate::model::entry::Entry, wasm_bytes: &[u8],
+pub fn contract_validate<'db, 'a>(db: &'db TransactionDB<MultiThreaded>, entry: crate::model::entry::Entry, fields: &EntryFields, wasm_bytes: &[u8],
     testnet: bool,
 ) -> (Vec<u8>, Vec<Vec<u8>>) {
     let cf_h = db.cf_handle("contractstate").unwrap();
     let cf2_h = db.cf_handle("contractstate").unwrap();
     let cf_tree_h = db.cf_handle("contractstate_tree").unwrap();
 
-    let entry_signer = entry.header.signer.as_slice().try_into().unwrap_or_else(|_| panic!("entry_signer_len_wrong"));
-    let entry_prev_hash = entry.header.prev_hash.as_slice().try_into().unwrap_or_else(|_| panic!("entry_prev_hash_len_wrong"));
-    let entry_vr = entry.header.vr.as_slice().try_into().unwrap_or_else(|_| panic!("entry_vr_len_wrong"));
-    let entry_vr_b3_binding = blake3::hash(&entry.header.vr);
-    let entry_vr_b3 = entry_vr_b3_binding.as_bytes().try_into().unwrap_or_else(|_| panic!("entry_vr_len_wrong"));
-    let entry_dr = entry.header.dr.as_slice().try_into().unwrap_or_else(|_| panic!("entry_dr_len_wrong"));
 
     let txn_opts = TransactionOptions::default();
     let write_opts = WriteOptions::default();
@@ -389,31 +491,57 @@ pub fn contract_validate<'db, 'a>(db: &'db TransactionDB<MultiThreaded>, entry:
 
     let entry_epoch = entry.header.height / 100_000;
     let mut applyenv = make_apply_env(db, txn, cf_h, b"contractstate".to_vec(), cf2_h, cf_tree_h,
-        entry_signer, entry_prev_hash, entry.header.slot, entry.header.prev_slot, entry.header.height,
-        entry_epoch, entry_vr, entry_vr_b3, entry_dr,
+        &fields.signer, &fields.prev_hash, entry.header.slot, entry.header.prev_slot, entry.header.height,
+        entry_epoch, &fields.vr, &fields.vr_b3, &fields.dr,
         testnet, Vec::new());
     applyenv.readonly = true;
 
     applyenv.exec_left = protocol::AMA_10_CENT;
     applyenv.storage_left = protocol::AMA_1_DOLLAR;
 
-    std::panic::set_hook(Box::new(|_| {}));
-    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-        crate::consensus::bic::wasm::validate_contract(&mut applyenv, wasm_bytes)
-    }));
+    let res = crate::consensus::bic::wasm::validate_contract(&mut applyenv, wasm_bytes);
 
     applyenv.txn.rollback();
 
     match res {
-        Ok(result) => (b"ok".to_vec(), applyenv.logs.clone()),
-        Err(payload) => {
-            if let Some(&s) = payload.downcast_ref::<&'static str>() {
-                (s.to_string().into(), applyenv.logs.clone())
-            } else {
-                (b"error".to_vec(), applyenv.logs.clone())
-            }
-        }
+        Ok(()) => (b"ok".to_vec(), applyenv.logs.clone()),
+        // Receipts record the former String panics as unknown, this API always said error
+        Err(e) if e.reason() == "unknown" => (b"error".to_vec(), applyenv.logs.clone()),
+        Err(e) => (e.reason().as_bytes().to_vec(), applyenv.logs.clone()),
+    }
+}
+
+// Dry run of txus on top of current state, in the context of entry's header. Same tx
+// path as apply_entry (nonces, upfront costs, calls, refunds) but no entry exit work or
+// state tree update, and the txn is always rolled back.
+pub fn simulate_txs<'db>(db: &'db TransactionDB<MultiThreaded>, entry: crate::model::entry::Entry, fields: &EntryFields,
+    txus: Vec<crate::model::tx::TXU>, tx_fields: &[TxFields], testnet: bool, trace: bool,
+) -> ExecResult<(Vec<consensus_muts::Mutation>, Vec<TXReceipt>, Vec<TxUsage>, Option<consensus_trace::Trace>)> {
+    let cf_h = db.cf_handle("contractstate").unwrap();
+    let cf2_h = db.cf_handle("contractstate").unwrap();
+    let cf_tree_h = db.cf_handle("contractstate_tree").unwrap();
+
+
+    // Never wait on row locks held by an entry being applied, the simulated write
+    // fails instead (exec_kv_put_failed) and the real entry is not held up
+    let mut txn_opts = TransactionOptions::default();
+    txn_opts.set_lock_timeout(0);
+    let write_opts = WriteOptions::default();
+    let txn = db.transaction_opt(&write_opts, &txn_opts);
+
+    let entry_epoch = entry.header.height / 100_000;
+    let mut applyenv = make_apply_env(db, txn, cf_h, b"contractstate".to_vec(), cf2_h, cf_tree_h,
+        &fields.signer, &fields.prev_hash, entry.header.slot, entry.header.prev_slot, entry.header.height,
+        entry_epoch, &fields.vr, &fields.vr_b3, &fields.dr,
+        testnet, Vec::new());
+    if trace {
+        applyenv.trace = Some(consensus_trace::Trace::default());
     }
+
+    let usage = execute_txs(&mut applyenv, &txus, tx_fields, true);
+
+    applyenv.txn.rollback();
+    Ok((applyenv.muts_final, applyenv.receipts, usage?, applyenv.trace))
 }
 
 fn update_and_root_contractstate(applyenv: &mut ApplyEnv) -> [u8; 32] {
@@ -548,7 +676,7 @@ impl ToTerm for Vec<HashMap<String, String>> {
     }
 }
 
-fn refund_exec_storage_deposit(applyenv: &mut ApplyEnv) {
+fn refund_exec_storage_deposit(applyenv: &mut ApplyEnv) -> ExecResult<()> {
     applyenv.muts = Vec::new();
     applyenv.muts_rev = Vec::new();
 
@@ -557,12 +685,12 @@ fn refund_exec_storage_deposit(applyenv: &mut ApplyEnv) {
         let refund = applyenv.exec_left.max(0);
         if refund > 0 {
             let key = &crate::bcat(&[b"account:", &applyenv.caller_env.account_origin, b":balance:AMA"]);
-            consensus_kv::kv_increment(applyenv, key, refund);
+            consensus_kv::kv_increment(applyenv, key, refund)?;
         }
         // Increment validator / burn
         let cost = applyenv.exec_max - refund;
-        consensus_kv::kv_increment(applyenv, &crate::bcat(&[b"account:", &applyenv.caller_env.entry_signer, b":balance:AMA"]), cost/2);
-        consensus_kv::kv_increment(applyenv, &crate::bcat(&[b"account:", &consensus::bic::coin::BURN_ADDRESS, b":balance:AMA"]), cost/2);
+        consensus_kv::kv_increment(applyenv, &crate::bcat(&[b"account:", &applyenv.caller_env.entry_signer, b":balance:AMA"]), cost/2)?;
+        consensus_kv::kv_increment(applyenv, &crate::bcat(&[b"account:", &consensus::bic::coin::BURN_ADDRESS, b":balance:AMA"]), cost/2)?;
     }
 
     //Refund remainder of the storage deposit
@@ -570,44 +698,45 @@ fn refund_exec_storage_deposit(applyenv: &mut ApplyEnv) {
         let refund = applyenv.storage_left.max(0);
         if refund > 0 {
             let key = &crate::bcat(&[b"account:", &applyenv.caller_env.account_origin, b":balance:AMA"]);
-            consensus_kv::kv_increment(applyenv, key, refund);
+            consensus_kv::kv_increment(applyenv, key, refund)?;
         }
         // Increment validator / burn
         let cost = applyenv.storage_max - refund;
-        consensus_kv::kv_increment(applyenv, &crate::bcat(&[b"account:", &applyenv.caller_env.entry_signer, b":balance:AMA"]), cost/2);
-        consensus_kv::kv_increment(applyenv, &crate::bcat(&[b"account:", &consensus::bic::coin::BURN_ADDRESS, b":balance:AMA"]), cost/2);
+        consensus_kv::kv_increment(applyenv, &crate::bcat(&[b"account:", &applyenv.caller_env.entry_signer, b":balance:AMA"]), cost/2)?;
+        consensus_kv::kv_increment(applyenv, &crate::bcat(&[b"account:", &consensus::bic::coin::BURN_ADDRESS, b":balance:AMA"]), cost/2)?;
     }
     applyenv.muts_final.append(&mut applyenv.muts);
     applyenv.muts_final_rev.append(&mut applyenv.muts_rev);
+    Ok(())
 }
 
-fn call_txs_pre_upfront_cost<'a>(env: &mut ApplyEnv, txus: &[crate::model::tx::TXU]) {
+fn call_txs_pre_upfront_cost<'a>(env: &mut ApplyEnv, txus: &[crate::model::tx::TXU], tx_fields: &[TxFields]) -> ExecResult<()> {
     env.muts = Vec::new();
     env.muts_rev = Vec::new();
-    for txu in txus {
-        let tx_hash = txu.hash.as_slice().try_into().unwrap_or_else(|_| panic!("tx_hash_len_wrong"));
-        let tx_signer = txu.tx.signer.as_slice().try_into().unwrap_or_else(|_| panic!("tx_signer_len_wrong"));
+    for (txu, fields) in txus.iter().zip(tx_fields) {
+        let TxFields { hash: tx_hash, signer: tx_signer } = *fields;
         let tx_nonce = txu.tx.nonce;
 
         set_apply_env_tx(env, &tx_hash, &tx_signer, tx_nonce);
 
         // Update nonce
-        consensus_kv::kv_put(env, &crate::bcat(&[b"account:", &tx_signer, b":attribute:nonce"]), &tx_nonce.to_string().into_bytes());
+        consensus_kv::kv_put(env, &crate::bcat(&[b"account:", &tx_signer, b":attribute:nonce"]), &tx_nonce.to_string().into_bytes())?;
 
         // Deduct tx historical cost
         let tx_historical_cost = crate::consensus::bic::protocol::tx_historical_cost(txu);
-        protocol::pay_cost(env, tx_historical_cost);
+        protocol::pay_cost(env, tx_historical_cost)?;
 
         //lock 0.1 AMA during execution
-        consensus_kv::kv_increment(env, &crate::bcat(&[b"account:", &env.caller_env.account_origin, b":balance:AMA"]), -protocol::AMA_10_CENT);
+        consensus_kv::kv_increment(env, &crate::bcat(&[b"account:", &env.caller_env.account_origin, b":balance:AMA"]), -protocol::AMA_10_CENT)?;
         //lock 1.0 storage AMA during execution
-        consensus_kv::kv_increment(env, &crate::bcat(&[b"account:", &env.caller_env.account_origin, b":balance:AMA"]), -protocol::AMA_1_DOLLAR);
+        consensus_kv::kv_increment(env, &crate::bcat(&[b"account:", &env.caller_env.account_origin, b":balance:AMA"]), -protocol::AMA_1_DOLLAR)?;
     }
     env.muts_final.append(&mut env.muts);
     env.muts_final_rev.append(&mut env.muts_rev);
+    Ok(())
 }
 
-fn call_exit(env: &mut ApplyEnv) {
+fn call_exit(env: &mut ApplyEnv) -> ExecResult<()> {
     //seed RNG for random validator selection
     let vr = env.caller_env.entry_vr.to_vec();
     let seed_hash = blake3::hash(&vr);
@@ -621,10 +750,10 @@ fn call_exit(env: &mut ApplyEnv) {
 
     if env.caller_env.entry_height % 1000 == 0 {
         let digest = blake3::hash(&env.caller_env.entry_vr);
-        consensus_kv::kv_put(env, b"bic:epoch:segment_vr_hash", digest.as_bytes());
+        consensus_kv::kv_put(env, b"bic:epoch:segment_vr_hash", digest.as_bytes())?;
     }
     if env.caller_env.entry_height % 100_000 == 99_999 {
-        consensus::bic::epoch::next(env);
+        consensus::bic::epoch::next(env)?;
     }
     if env.caller_env.entry_height == protocol::forkheight(env) {
         //migrate_db(env);
@@ -632,6 +761,7 @@ fn call_exit(env: &mut ApplyEnv) {
 
     env.muts_final.append(&mut env.muts);
     env.muts_final_rev.append(&mut env.muts_rev);
+    Ok(())
 }
 
 fn unique_mutations(mutations: Vec<consensus_muts::Mutation>, reverse: bool) -> Vec<consensus_muts::Mutation> {
@@ -663,16 +793,16 @@ fn unique_mutations(mutations: Vec<consensus_muts::Mutation>, reverse: bool) ->
     result
 }
 
-fn migrate_db(env: &mut ApplyEnv) {
+fn migrate_db(env: &mut ApplyEnv) -> ExecResult<()> {
     let mut cursor: Vec<u8> = Vec::new();
-    while let Some((next_key_wo_prefix, _val)) = crate::consensus::consensus_kv::kv_get_next(env, b"bic:epoch:trainers:height:", &cursor) {
-        let height = std::str::from_utf8(&next_key_wo_prefix).ok().and_then(|s| s.parse::<u64>().ok()).unwrap_or_else(|| panic_any("invalid_epoch"));
-        let trainers: Vec<vecpak::Term> = consensus::bic::epoch::kv_get_trainers(env, height)
+    while let Some((next_key_wo_prefix, _val)) = crate::consensus::consensus_kv::kv_get_next(env, b"bic:epoch:trainers:height:", &cursor)? {
+        let height = std::str::from_utf8(&next_key_wo_prefix).ok().and_then(|s| s.parse::<u64>().ok()).ok_or(ExecError::Bic("invalid_epoch"))?;
+        let trainers: Vec<vecpak::Term> = consensus::bic::epoch::kv_get_trainers(env, height)?
             .into_iter()
             .map(vecpak::Term::Binary)
             .collect();
         let buf = vecpak::encode(vecpak::Term::List(trainers));
-        crate::consensus::consensus_kv::kv_put(env, &crate::bcat(&[b"bic:epoch:validators:height:", &next_key_wo_prefix]), &buf);
+        crate::consensus::consensus_kv::kv_put(env, &crate::bcat(&[b"bic:epoch:validators:height:", &next_key_wo_prefix]), &buf)?;
         cursor = next_key_wo_prefix;
     }
 
@@ -746,9 +876,17 @@ fn migrate_db(env: &mut ApplyEnv) {
         cursor = next_key_wo_prefix;
     }
     */
+    Ok(())
+}
+
+pub fn call_bic(env: &mut ApplyEnv, contract: Vec<u8>, function: Vec<u8>, args: Vec<Vec<u8>>, attached_symbol: Option<Vec<u8>>, attached_amount: Option<Vec<u8>>) -> ExecResult<()> {
+    let frame = consensus_trace::enter(env, &contract, &function, &args);
+    dispatch_bic(env, contract, function, args, attached_symbol, attached_amount)?;
+    consensus_trace::exit(env, frame, b"ok");
+    Ok(())
 }
 
-pub fn call_bic(env: &mut ApplyEnv, contract: Vec<u8>, function: Vec<u8>, args: Vec<Vec<u8>>, attached_symbol: Option<Vec<u8>>, attached_amount: Option<Vec<u8>>) {
+fn dispatch_bic(env: &mut ApplyEnv, contract: Vec<u8>, function: Vec<u8>, args: Vec<Vec<u8>>, attached_symbol: Option<Vec<u8>>, attached_amount: Option<Vec<u8>>) -> ExecResult<()> {
     if env.testnet {
         match (contract.as_slice(), function.as_slice()) {
             (b"Coin", b"create_and_mint") => return consensus::bic::coin::call_create_and_mint(env, args),
@@ -760,7 +898,7 @@ pub fn call_bic(env: &mut ApplyEnv, contract: Vec<u8>, function: Vec<u8>, args:
             (b"Lockup", b"lock") => return consensus::bic::lockup::call_lock(env, args),
             (b"Lockup", b"unlock") => return consensus::bic::lockup::call_unlock(env, args),
             (b"Contract", b"deploy") => {
-                consensus_kv::exec_budget_decr(env, protocol::COST_PER_DEPLOY);
+                consensus_kv::exec_budget_decr(env, protocol::COST_PER_DEPLOY)?;
                 return consensus::bic::contract::call_deploy(env, args);
             }
             (b"LockupPrime", b"lock") => return consensus::bic::lockup_prime::call_lock(env, args),
@@ -773,7 +911,7 @@ pub fn call_bic(env: &mut ApplyEnv, contract: Vec<u8>, function: Vec<u8>, args:
 
     match (contract.as_slice(), function.as_slice()) {
         (b"Epoch", b"submit_sol") => {
-            consensus_kv::exec_budget_decr(env, protocol::COST_PER_SOL);
+            consensus_kv::exec_budget_decr(env, protocol::COST_PER_SOL)?;
             consensus::bic::epoch::call_submit_sol(env, args)
         },
         (b"Epoch", b"set_emission_address") => consensus::bic::epoch::call_set_emission_address(env, args),
@@ -799,12 +937,19 @@ pub fn call_bic(env: &mut ApplyEnv, contract: Vec<u8>, function: Vec<u8>, args:
         (b"LockupPrime", b"daily_checkin") => consensus::bic::lockup_prime::call_daily_checkin(env, args),
         */
 
-        _ => std::panic::panic_any("invalid_bic_action")
+        _ => Err(ExecError::Bic("invalid_bic_action"))
     }
 }
 
-pub fn call_wasmvm(env: &mut ApplyEnv, contract: Vec<u8>, function: Vec<u8>, args: Vec<Vec<u8>>, attached_symbol: Option<Vec<u8>>, attached_amount: Option<Vec<u8>>) -> Vec<u8> {
-    let function = String::from_utf8(function).unwrap_or_else(|_| panic_any("invalid_function"));
+pub fn call_wasmvm(env: &mut ApplyEnv, contract: Vec<u8>, function: Vec<u8>, args: Vec<Vec<u8>>, attached_symbol: Option<Vec<u8>>, attached_amount: Option<Vec<u8>>) -> ExecResult<Vec<u8>> {
+    let frame = consensus_trace::enter(env, &contract, &function, &args);
+    let result = dispatch_wasmvm(env, contract, function, args, attached_symbol, attached_amount)?;
+    consensus_trace::exit(env, frame, &result);
+    Ok(result)
+}
+
+fn dispatch_wasmvm(env: &mut ApplyEnv, contract: Vec<u8>, function: Vec<u8>, args: Vec<Vec<u8>>, attached_symbol: Option<Vec<u8>>, attached_amount: Option<Vec<u8>>) -> ExecResult<Vec<u8>> {
+    let function = String::from_utf8(function).map_err(|_| ExecError::Bic("invalid_function"))?;
 
     //seed the rng
     let mut hasher = blake3::Hasher::new();
@@ -823,17 +968,17 @@ pub fn call_wasmvm(env: &mut ApplyEnv, contract: Vec<u8>, function: Vec<u8>, arg
     env.caller_env.attached_symbol = Vec::new();
     env.caller_env.attached_amount = Vec::new();
 
-    let bytecode = consensus::bic::contract::bytecode(env, contract.as_slice());
-    if bytecode.is_none() { panic_any("account_has_no_bytecode") }
+    let bytecode = consensus::bic::contract::bytecode(env, contract.as_slice())?;
+    if bytecode.is_none() { return Err(ExecError::Bic("account_has_no_bytecode")) }
 
     match (attached_symbol, attached_amount) {
         (Some(attached_symbol), Some(attached_amount)) => {
-            let amount = std::str::from_utf8(&attached_amount).ok().and_then(|s| s.parse::<i128>().ok()).unwrap_or_else(|| panic_any("invalid_attached_amount"));
-            if amount <= 0 { panic_any("invalid_attached_amount") }
-            if amount > consensus::bic::coin::balance(env, &env.caller_env.account_caller.clone(), &attached_symbol) { panic_any("attached_amount_insufficient_funds") }
+            let amount = std::str::from_utf8(&attached_amount).ok().and_then(|s| s.parse::<i128>().ok()).ok_or(ExecError::Bic("invalid_attached_amount"))?;
+            if amount <= 0 { return Err(ExecError::Bic("invalid_attached_amount")) }
+            if amount > consensus::bic::coin::balance(env, &env.caller_env.account_caller.clone(), &attached_symbol)? { return Err(ExecError::Bic("attached_amount_insufficient_funds")) }
 
-            consensus_kv::kv_increment(env, &crate::bcat(&[b"account:", &contract, b":balance:", &attached_symbol]), amount);
-            consensus_kv::kv_increment(env, &crate::bcat(&[b"account:", &env.caller_env.account_caller, b":balance:", &attached_symbol]), -amount);
+            consensus_kv::kv_increment(env, &crate::bcat(&[b"account:", &contract, b":balance:", &attached_symbol]), amount)?;
+            consensus_kv::kv_increment(env, &crate::bcat(&[b"account:", &env.caller_env.account_caller, b":balance:", &attached_symbol]), -amount)?;
 
             env.caller_env.attached_symbol = attached_symbol;
             env.caller_env.attached_amount = attached_amount;
@@ -842,9 +987,9 @@ pub fn call_wasmvm(env: &mut ApplyEnv, contract: Vec<u8>, function: Vec<u8>, arg
     }
 
     if !env.testnet {
-        std::panic::panic_any("wasm_noop");
+        return Err(ExecError::Wasm("wasm_noop"));
     }
 
-    let error = consensus::bic::wasm::call_contract(env, bytecode.as_deref().unwrap_or_else(|| panic_any("invalid_bytecode")), function, args);
+    let error = consensus::bic::wasm::call_contract(env, bytecode.as_deref().ok_or(ExecError::Bic("invalid_bytecode"))?, function, args);
     error
 }
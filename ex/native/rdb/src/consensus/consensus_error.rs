@@ -0,0 +1,39 @@
+// Why a call failed. The reason string is what ends up in the tx receipt, so it must
+// never change for an existing failure, the variant only says which layer raised it.
+// Anything that is not an ExecError (a panic) is a bug, not a failed tx.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecError {
+    // Exec or storage deposit exhausted or charged a bad amount
+    Budget(&'static str),
+    // Rejected by the kv layer: sizes, integer values, writes in a view, db writes
+    Kv(&'static str),
+    // Rejected by a builtin contract
+    Bic(&'static str),
+    // Rejected by the wasm runtime or a host function
+    Wasm(&'static str),
+}
+
+pub type ExecResult<T> = Result<T, ExecError>;
+
+impl ExecError {
+    pub fn reason(&self) -> &'static str {
+        match *self {
+            ExecError::Budget(reason) | ExecError::Kv(reason) | ExecError::Bic(reason) | ExecError::Wasm(reason) => reason,
+        }
+    }
+}
+
+impl std::fmt::Display for ExecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.reason())
+    }
+}
+
+impl std::error::Error for ExecError {}
+
+// Host functions hand it to wasmer as a user error, call_contract downcasts it back
+impl From<ExecError> for wasmer::RuntimeError {
+    fn from(e: ExecError) -> Self {
+        wasmer::RuntimeError::user(Box::new(e))
+    }
+}
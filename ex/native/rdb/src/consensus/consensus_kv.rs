@@ -1,14 +1,98 @@
-use std::panic::panic_any;
-
-use crate::consensus::{bic::protocol, consensus_apply};
+use crate::consensus::{bic::protocol, consensus_apply, consensus_trace};
+use crate::consensus::consensus_error::{ExecError, ExecResult};
 use consensus_apply::ApplyEnv;
 
 use crate::consensus::consensus_muts;
 use consensus_muts::Mutation;
 
-pub fn exec_budget_decr(env: &mut ApplyEnv, amount: i128) {
+use crate::ReadOptions;
+use std::collections::{HashMap, HashSet};
+
+// Speculative execution of a tx (see consensus_parallel). Writes stay in `writes`
+// instead of the txn. Reads see the tx's own writes, then `base` (what the entry wrote
+// before speculation started), then the committed db. Only contractstate is covered.
+pub struct Speculation<'a> {
+    pub base: &'a HashMap<Vec<u8>, Option<Vec<u8>>>,
+    pub reads: HashSet<Vec<u8>>,
+    pub writes: HashMap<Vec<u8>, Option<Vec<u8>>>,
+    // Took a range read, which the overlay cannot serve; the tx must rerun in order
+    pub ranged: bool,
+}
+
+impl<'a> Speculation<'a> {
+    pub fn new(base: &'a HashMap<Vec<u8>, Option<Vec<u8>>>) -> Self {
+        Speculation { base, reads: HashSet::new(), writes: HashMap::new(), ranged: false }
+    }
+}
+
+// Reads honour the optional snapshot so views can run at a fixed point in time
+fn read_opts(env: &ApplyEnv) -> ReadOptions {
+    let mut ro = ReadOptions::default();
+    if let Some(snap) = env.snapshot {
+        ro.set_snapshot(snap);
+    }
+    ro
+}
+
+fn txn_get(env: &mut ApplyEnv, key: &[u8]) -> Option<Vec<u8>> {
+    if env.spec.is_some() {
+        return state_get(env, key);
+    }
+    match env.snapshot {
+        None => env.txn.get_cf(&env.cf, key).unwrap(),
+        Some(_) => env.txn.get_cf_opt(&env.cf, key, &read_opts(env)).unwrap(),
+    }
+}
+
+// All point reads and writes of the kv_* functions go through these three
+fn state_get(env: &mut ApplyEnv, key: &[u8]) -> Option<Vec<u8>> {
+    match env.spec.as_mut() {
+        None => env.txn.get_cf(&env.cf, key).unwrap(),
+        Some(spec) => {
+            if let Some(value) = spec.writes.get(key) {
+                return value.clone();
+            }
+            spec.reads.insert(key.to_vec());
+            match spec.base.get(key) {
+                Some(value) => value.clone(),
+                None => env.db.get_cf(&env.cf, key).unwrap(),
+            }
+        }
+    }
+}
+
+fn state_put(env: &mut ApplyEnv, key: &[u8], value: &[u8]) -> Result<(), ()> {
+    match env.spec.as_mut() {
+        None => env.txn.put_cf(&env.cf, key, value).map_err(|_| ()),
+        Some(spec) => {
+            spec.writes.insert(key.to_vec(), Some(value.to_vec()));
+            Ok(())
+        }
+    }
+}
+
+fn state_delete(env: &mut ApplyEnv, key: &[u8]) -> Result<(), ()> {
+    match env.spec.as_mut() {
+        None => env.txn.delete_cf(&env.cf, key).map_err(|_| ()),
+        Some(spec) => {
+            spec.writes.insert(key.to_vec(), None);
+            Ok(())
+        }
+    }
+}
+
+// Iterators read the txn directly, a speculative tx gives up and reruns in order
+fn range_read_guard(env: &mut ApplyEnv) -> ExecResult<()> {
+    if let Some(spec) = env.spec.as_mut() {
+        spec.ranged = true;
+        return Err(ExecError::Kv("speculation_ranged_read"));
+    }
+    Ok(())
+}
+
+pub fn exec_budget_decr(env: &mut ApplyEnv, amount: i128) -> ExecResult<()> {
     if amount < 0 {
-         panic_any("exec_invalid_amount_negative");
+         return Err(ExecError::Budget("exec_invalid_amount_negative"));
     }
 
     if env.exec_track {
@@ -16,18 +100,19 @@ pub fn exec_budget_decr(env: &mut ApplyEnv, amount: i128) {
             Some(new_budget) => {
                 if new_budget < 0 {
                     env.exec_left = 0;
-                    panic_any("exec_insufficient_exec_budget");
+                    return Err(ExecError::Budget("exec_insufficient_exec_budget"));
                 }
                 env.exec_left = new_budget;
             },
-            None => panic_any("exec_critical_underflow")
+            None => return Err(ExecError::Budget("exec_critical_underflow"))
         }
     }
+    Ok(())
 }
 
-pub fn storage_budget_decr(env: &mut ApplyEnv, amount: i128) {
+pub fn storage_budget_decr(env: &mut ApplyEnv, amount: i128) -> ExecResult<()> {
     if amount < 0 {
-         panic_any("exec_storage_invalid_amount_negative");
+         return Err(ExecError::Budget("exec_storage_invalid_amount_negative"));
     }
 
     if env.exec_track {
@@ -35,112 +120,120 @@ pub fn storage_budget_decr(env: &mut ApplyEnv, amount: i128) {
             Some(new_budget) => {
                 if new_budget < 0 {
                     env.storage_left = 0;
-                    panic_any("exec_insufficient_storage_budget");
+                    return Err(ExecError::Budget("exec_insufficient_storage_budget"));
                 }
                 env.storage_left = new_budget;
             },
-            None => panic_any("exec_storage_critical_underflow")
+            None => return Err(ExecError::Budget("exec_storage_critical_underflow"))
         }
     }
+    Ok(())
 }
 
-pub fn exec_kv_size(key: &[u8], value: Option<&[u8]>) {
+pub fn exec_kv_size(key: &[u8], value: Option<&[u8]>) -> ExecResult<()> {
     if key.len() > protocol::MAX_DB_KEY_SIZE {
-         panic_any("exec_too_large_key_size");
+         return Err(ExecError::Kv("exec_too_large_key_size"));
     }
     if let Some(v) = value {
         if v.len() > protocol::MAX_DB_VALUE_SIZE {
-             panic_any("exec_too_large_value_size");
+             return Err(ExecError::Kv("exec_too_large_value_size"));
         }
     }
+    Ok(())
 }
 
-pub fn kv_put(env: &mut ApplyEnv, key: &[u8], value: &[u8]) {
+pub fn kv_put(env: &mut ApplyEnv, key: &[u8], value: &[u8]) -> ExecResult<()> {
     if env.readonly {
-        panic!("exec_cannot_write_during_view");
+        return Err(ExecError::Kv("exec_cannot_write_during_view"));
     }
 
-    exec_kv_size(key, Some(value));
-    exec_budget_decr(env, protocol::COST_PER_DB_WRITE_BASE + protocol::cost_db_write_byte(env) * (key.len() + value.len()) as i128);
+    exec_kv_size(key, Some(value))?;
+    exec_budget_decr(env, protocol::COST_PER_DB_WRITE_BASE + protocol::cost_db_write_byte(env) * (key.len() + value.len()) as i128)?;
 
-    let old_value = env.txn.get_cf(&env.cf, key).unwrap();
+    let old_value = state_get(env, key);
     match old_value {
         None => {
-            storage_budget_decr(env, protocol::COST_PER_NEW_LEAF_MERKLE);
-            storage_budget_decr(env, protocol::COST_PER_BYTE_STATE * (key.len() + value.len()) as i128);
+            storage_budget_decr(env, protocol::COST_PER_NEW_LEAF_MERKLE)?;
+            storage_budget_decr(env, protocol::COST_PER_BYTE_STATE * (key.len() + value.len()) as i128)?;
             env.muts_rev.push(Mutation::Delete { op: b"delete".to_vec(), table: env.cf_name.to_vec(), key: key.to_vec() });
 
             env.muts.push(Mutation::Put { op: b"put".to_vec(), table: env.cf_name.to_vec(), key: key.to_vec(), value: value.to_vec() });
-            env.txn.put_cf(&env.cf, key, value).unwrap_or_else(|_| panic_any("exec_kv_put_failed"))
+            consensus_trace::write(env, key, None, Some(value));
+            state_put(env, key, value).map_err(|_| ExecError::Kv("exec_kv_put_failed"))
         },
         Some(old) => {
             //TODO: consider gas refund on delete? gas-token attack?
-            storage_budget_decr(env, protocol::COST_PER_BYTE_STATE * value.len().saturating_sub(old.len()) as i128);
+            storage_budget_decr(env, protocol::COST_PER_BYTE_STATE * value.len().saturating_sub(old.len()) as i128)?;
             env.muts_rev.push(Mutation::Put { op: b"put".to_vec(), table: env.cf_name.to_vec(), key: key.to_vec(), value: old.to_vec() });
 
             env.muts.push(Mutation::Put { op: b"put".to_vec(), table: env.cf_name.to_vec(), key: key.to_vec(), value: value.to_vec() });
-            env.txn.put_cf(&env.cf, key, value).unwrap_or_else(|_| panic_any("exec_kv_put_failed"))
+            consensus_trace::write(env, key, Some(&old), Some(value));
+            state_put(env, key, value).map_err(|_| ExecError::Kv("exec_kv_put_failed"))
         }
     }
 }
 
-pub fn kv_increment(env: &mut ApplyEnv, key: &[u8], value: i128) -> i128 {
+pub fn kv_increment(env: &mut ApplyEnv, key: &[u8], value: i128) -> ExecResult<i128> {
     if env.readonly {
-        panic!("exec_cannot_write_during_view");
+        return Err(ExecError::Kv("exec_cannot_write_during_view"));
     }
 
     let value_str = value.to_string().into_bytes();
-    exec_budget_decr(env, protocol::COST_PER_DB_WRITE_BASE + protocol::cost_db_write_byte(env) * (key.len() + value_str.len()) as i128);
+    exec_budget_decr(env, protocol::COST_PER_DB_WRITE_BASE + protocol::cost_db_write_byte(env) * (key.len() + value_str.len()) as i128)?;
 
-    match env.txn.get_cf(&env.cf, key).unwrap() {
+    match state_get(env, key) {
         None => {
-            exec_kv_size(key, Some(&value_str));
-            storage_budget_decr(env, protocol::COST_PER_NEW_LEAF_MERKLE);
-            storage_budget_decr(env, protocol::COST_PER_BYTE_STATE * (key.len() + value_str.len()) as i128);
+            exec_kv_size(key, Some(&value_str))?;
+            storage_budget_decr(env, protocol::COST_PER_NEW_LEAF_MERKLE)?;
+            storage_budget_decr(env, protocol::COST_PER_BYTE_STATE * (key.len() + value_str.len()) as i128)?;
             env.muts.push(Mutation::Put { op: b"put".to_vec(), table: env.cf_name.to_vec(), key: key.to_vec(), value: value.to_string().into_bytes() });
             env.muts_rev.push(Mutation::Delete { op: b"delete".to_vec(), table: env.cf_name.to_vec(), key: key.to_vec() });
-            env.txn.put_cf(&env.cf, key, value_str).unwrap_or_else(|_| panic_any("exec_kv_increment_failed"));
-            value
+            consensus_trace::write(env, key, None, Some(&value_str));
+            state_put(env, key, &value_str).map_err(|_| ExecError::Kv("exec_kv_increment_failed"))?;
+            Ok(value)
         },
         Some(old) => {
-            let old_int: i128 = atoi::atoi::<i128>(&old).unwrap_or_else(|| panic_any("exec_kv_increment_invalid_integer"));
-            let new_value = old_int.checked_add(value).unwrap_or_else(|| panic_any("exec_kv_increment_integer_overflow"));
+            let old_int: i128 = atoi::atoi::<i128>(&old).ok_or(ExecError::Kv("exec_kv_increment_invalid_integer"))?;
+            let new_value = old_int.checked_add(value).ok_or(ExecError::Kv("exec_kv_increment_integer_overflow"))?;
             let new_value_str = new_value.to_string().into_bytes();
-            exec_kv_size(key, Some(&new_value_str));
-            storage_budget_decr(env, protocol::COST_PER_BYTE_STATE * new_value_str.len().saturating_sub(old.len()) as i128);
+            exec_kv_size(key, Some(&new_value_str))?;
+            storage_budget_decr(env, protocol::COST_PER_BYTE_STATE * new_value_str.len().saturating_sub(old.len()) as i128)?;
             env.muts.push(Mutation::Put { op: b"put".to_vec(), table: env.cf_name.to_vec(), key: key.to_vec(), value: new_value.to_string().into_bytes() });
+            consensus_trace::write(env, key, Some(&old), Some(&new_value_str));
             env.muts_rev.push(Mutation::Put { op: b"put".to_vec(), table: env.cf_name.to_vec(), key: key.to_vec(), value: old });
-            env.txn.put_cf(&env.cf, key, new_value.to_string().into_bytes()).unwrap_or_else(|_| panic_any("kv_put_failed"));
-            new_value
+            state_put(env, key, &new_value_str).map_err(|_| ExecError::Kv("kv_put_failed"))?;
+            Ok(new_value)
         }
     }
 }
 
-pub fn kv_delete(env: &mut ApplyEnv, key: &[u8]) {
+pub fn kv_delete(env: &mut ApplyEnv, key: &[u8]) -> ExecResult<()> {
     if env.readonly {
-        panic!("exec_cannot_write_during_view");
+        return Err(ExecError::Kv("exec_cannot_write_during_view"));
     }
 
-    exec_budget_decr(env, protocol::COST_PER_DB_WRITE_BASE + protocol::cost_db_write_byte(env) * (key.len()) as i128);
+    exec_budget_decr(env, protocol::COST_PER_DB_WRITE_BASE + protocol::cost_db_write_byte(env) * (key.len()) as i128)?;
 
-    match env.txn.get_cf(&env.cf, key).unwrap() {
+    let old = state_get(env, key);
+    consensus_trace::write(env, key, old.as_deref(), None);
+    match old {
         None => (),
         Some(old) => {
             env.muts.push(Mutation::Delete { op: b"delete".to_vec(), table: env.cf_name.to_vec(), key: key.to_vec() });
             env.muts_rev.push(Mutation::Put { op: b"put".to_vec(), table: env.cf_name.to_vec(), key: key.to_vec(), value: old.to_vec() })
         }
     }
-    env.txn.delete_cf(&env.cf, key).unwrap_or_else(|_| panic_any("exec_kv_delete_failed"));
+    state_delete(env, key).map_err(|_| ExecError::Kv("exec_kv_delete_failed"))
 }
 
-pub fn kv_set_bit(env: &mut ApplyEnv, key: &[u8], bit_idx: u64) -> bool {
+pub fn kv_set_bit(env: &mut ApplyEnv, key: &[u8], bit_idx: u64) -> ExecResult<bool> {
     if env.readonly {
-        panic!("exec_cannot_write_during_view");
+        return Err(ExecError::Kv("exec_cannot_write_during_view"));
     }
 
-    exec_budget_decr(env, protocol::COST_PER_DB_WRITE_BASE + protocol::cost_db_write_byte(env) * (key.len()) as i128);
+    exec_budget_decr(env, protocol::COST_PER_DB_WRITE_BASE + protocol::cost_db_write_byte(env) * (key.len()) as i128)?;
 
-    let (mut old, exists) = match env.txn.get_cf(&env.cf, key).unwrap() {
+    let (mut old, exists) = match state_get(env, key) {
         None => (vec![0u8; crate::consensus::bic::sol_bloom::PAGE_SIZE as usize], false),
         Some(value) => (value, true)
     };
@@ -149,8 +242,10 @@ pub fn kv_set_bit(env: &mut ApplyEnv, key: &[u8], bit_idx: u64) -> bool {
     let bit_in_byte = (bit_idx % 8) as u8;
     let mask: u8 = 1u8 << (7 - bit_in_byte);
 
-    if (old[byte_idx] & mask) != 0 {
-        false
+    let was_set = (old[byte_idx] & mask) != 0;
+    consensus_trace::set_bit(env, key, bit_idx, was_set);
+    if was_set {
+        Ok(false)
     } else {
         env.muts.push(Mutation::SetBit { op: b"set_bit".to_vec(), table: env.cf_name.to_vec(), key: key.to_vec(), value: bit_idx, bloomsize: crate::consensus::bic::sol_bloom::PAGE_SIZE});
         match exists {
@@ -158,93 +253,105 @@ pub fn kv_set_bit(env: &mut ApplyEnv, key: &[u8], bit_idx: u64) -> bool {
             false => env.muts_rev.push(Mutation::Delete { op: b"delete".to_vec(), table: env.cf_name.to_vec(), key: key.to_vec()})
         };
         old[byte_idx] |= mask;
-        env.txn.put_cf(&env.cf, key, &old).unwrap_or_else(|_| panic_any("exec_kv_set_bit_failed"));
-        true
+        state_put(env, key, &old).map_err(|_| ExecError::Kv("exec_kv_set_bit_failed"))?;
+        Ok(true)
     }
 }
 
-pub fn kv_exists(env: &mut ApplyEnv, key: &[u8]) -> bool {
-    exec_budget_decr(env, protocol::COST_PER_DB_READ_BASE + protocol::cost_db_read_byte(env) * (key.len()) as i128);
+pub fn kv_exists(env: &mut ApplyEnv, key: &[u8]) -> ExecResult<bool> {
+    exec_budget_decr(env, protocol::COST_PER_DB_READ_BASE + protocol::cost_db_read_byte(env) * (key.len()) as i128)?;
 
-    match env.txn.get_cf(&env.cf, key).unwrap() {
-        None => false,
-        Some(_) => true
-    }
+    let value = txn_get(env, key);
+    consensus_trace::read(env, key, value.as_deref());
+    Ok(value.is_some())
 }
 
-pub fn kv_get(env: &mut ApplyEnv, key: &[u8]) -> Option<Vec<u8>> {
-    exec_budget_decr(env, protocol::COST_PER_DB_READ_BASE + protocol::cost_db_read_byte(env) * (key.len()) as i128);
+pub fn kv_get(env: &mut ApplyEnv, key: &[u8]) -> ExecResult<Option<Vec<u8>>> {
+    exec_budget_decr(env, protocol::COST_PER_DB_READ_BASE + protocol::cost_db_read_byte(env) * (key.len()) as i128)?;
 
-    env.txn.get_cf(&env.cf, key).unwrap()
+    let value = txn_get(env, key);
+    consensus_trace::read(env, key, value.as_deref());
+    Ok(value)
 }
 
-pub fn kv_get_next(env: &mut ApplyEnv, prefix: &[u8], key: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
-    exec_budget_decr(env, protocol::COST_PER_DB_READ_BASE + protocol::cost_db_read_byte(env) * (prefix.len() + key.len()) as i128);
+pub fn kv_get_next(env: &mut ApplyEnv, prefix: &[u8], key: &[u8]) -> ExecResult<Option<(Vec<u8>, Vec<u8>)>> {
+    range_read_guard(env)?;
+    exec_budget_decr(env, protocol::COST_PER_DB_READ_BASE + protocol::cost_db_read_byte(env) * (prefix.len() + key.len()) as i128)?;
 
     let seek = [prefix, key].concat();
 
-    let mut it = env.txn.raw_iterator_cf(&env.cf);
-    it.seek(&seek);
-    let it_valid = it.valid();
-    if !it_valid { return None};
-    if let Some(k) = it.key() {
-        if k == &seek {
-            it.next();
+    let found = {
+        let mut it = env.txn.raw_iterator_cf_opt(&env.cf, read_opts(env));
+        it.seek(&seek);
+        if let Some(k) = it.key() {
+            if k == &seek {
+                it.next();
+            }
         }
-    }
 
-    match it.item() {
-        Some((k, v)) if k.starts_with(prefix) => {
-            let next_key_wo_prefix = k[prefix.len()..].to_vec();
-            Some((next_key_wo_prefix, v.to_vec()))
-        },
-        _ => None
-    }
+        match it.item() {
+            Some((k, v)) if k.starts_with(prefix) => {
+                let next_key_wo_prefix = k[prefix.len()..].to_vec();
+                Some((next_key_wo_prefix, v.to_vec()))
+            },
+            _ => None
+        }
+    };
+    consensus_trace::scan(env, prefix, key, found.as_ref());
+    Ok(found)
 }
 
-pub fn kv_get_prev(env: &mut ApplyEnv, prefix: &[u8], key: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
-    exec_budget_decr(env, protocol::COST_PER_DB_READ_BASE + protocol::cost_db_read_byte(env) * (prefix.len() + key.len()) as i128);
+pub fn kv_get_prev(env: &mut ApplyEnv, prefix: &[u8], key: &[u8]) -> ExecResult<Option<(Vec<u8>, Vec<u8>)>> {
+    range_read_guard(env)?;
+    exec_budget_decr(env, protocol::COST_PER_DB_READ_BASE + protocol::cost_db_read_byte(env) * (prefix.len() + key.len()) as i128)?;
 
     let seek = [prefix, key].concat();
 
-    let mut it = env.txn.raw_iterator_cf(&env.cf);
-    it.seek_for_prev(&seek);
-    let it_valid = it.valid();
-    if !it_valid { return None};
-    if let Some(k) = it.key() {
-        if k == &seek {
-            it.prev();
+    let found = {
+        let mut it = env.txn.raw_iterator_cf_opt(&env.cf, read_opts(env));
+        it.seek_for_prev(&seek);
+        if let Some(k) = it.key() {
+            if k == &seek {
+                it.prev();
+            }
         }
-    }
 
-    match it.item() {
-        Some((k, v)) if k.starts_with(prefix) => {
-            let next_key_wo_prefix = k[prefix.len()..].to_vec();
-            Some((next_key_wo_prefix, v.to_vec()))
-        },
-        _ => None
-    }
+        match it.item() {
+            Some((k, v)) if k.starts_with(prefix) => {
+                let next_key_wo_prefix = k[prefix.len()..].to_vec();
+                Some((next_key_wo_prefix, v.to_vec()))
+            },
+            _ => None
+        }
+    };
+    consensus_trace::scan(env, prefix, key, found.as_ref());
+    Ok(found)
 }
 
-pub fn kv_get_prev_or_first(env: &mut ApplyEnv, prefix: &[u8], key: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
-    exec_budget_decr(env, protocol::COST_PER_DB_READ_BASE + protocol::cost_db_read_byte(env) * (prefix.len() + key.len()) as i128);
+pub fn kv_get_prev_or_first(env: &mut ApplyEnv, prefix: &[u8], key: &[u8]) -> ExecResult<Option<(Vec<u8>, Vec<u8>)>> {
+    range_read_guard(env)?;
+    exec_budget_decr(env, protocol::COST_PER_DB_READ_BASE + protocol::cost_db_read_byte(env) * (prefix.len() + key.len()) as i128)?;
 
     let seek = [prefix, key].concat();
 
-    let mut it = env.txn.raw_iterator_cf(&env.cf);
-    it.seek_for_prev(&seek);
-
-    match it.item() {
-        Some((k, v)) => {
-            if k.starts_with(prefix) {
-                let next_key_wo_prefix = k[prefix.len()..].to_vec();
-                Some((next_key_wo_prefix, v.to_vec()))
-            } else {
-                None
-            }
-        },
-        None => None,
-    }
+    let found = {
+        let mut it = env.txn.raw_iterator_cf_opt(&env.cf, read_opts(env));
+        it.seek_for_prev(&seek);
+
+        match it.item() {
+            Some((k, v)) => {
+                if k.starts_with(prefix) {
+                    let next_key_wo_prefix = k[prefix.len()..].to_vec();
+                    Some((next_key_wo_prefix, v.to_vec()))
+                } else {
+                    None
+                }
+            },
+            None => None,
+        }
+    };
+    consensus_trace::scan(env, prefix, key, found.as_ref());
+    Ok(found)
 }
 
 pub fn contractstate_namespace(key: &[u8]) -> Option<Vec<u8>> {
@@ -280,7 +387,7 @@ pub fn revert(env: &mut ApplyEnv) {
             }
             Mutation::ClearBit { op, table, key, value } => {
                 let bit_idx = value;
-                if let Some(mut old) = kv_get(env, key.as_slice()) {
+                if let Some(mut old) = txn_get(env, key.as_slice()) {
                     let byte_idx   = (bit_idx / 8) as usize;
                     let bit_in     = (bit_idx % 8) as u8;      // 0..=7, MSB-first
                     if byte_idx < old.len() {
@@ -6,7 +6,25 @@ pub enum Mutation {
     ClearBit{ op: Vec<u8>, table: Vec<u8>, key: Vec<u8>, value: u64 },
 }
 
+impl Mutation {
+    pub fn key(&self) -> &[u8] {
+        match self {
+            Mutation::Put { key, .. } | Mutation::Delete { key, .. }
+            | Mutation::SetBit { key, .. } | Mutation::ClearBit { key, .. } => key,
+        }
+    }
+
+    pub fn table(&self) -> &[u8] {
+        match self {
+            Mutation::Put { table, .. } | Mutation::Delete { table, .. }
+            | Mutation::SetBit { table, .. } | Mutation::ClearBit { table, .. } => table,
+        }
+    }
+}
+
 use std::collections::HashMap;
+use vecpak::Term;
+use crate::model::_codec::pl_find_opt;
 
 #[inline]
 fn u64_ascii(n: u64) -> Vec<u8> { n.to_string().into_bytes() }
@@ -49,3 +67,34 @@ pub fn mutations_to_map(muts: Vec<Mutation>) -> Vec<HashMap<Vec<u8>, Vec<u8>>> {
 
     out
 }
+
+// Reads back a mutation list the node stored (vecpak of the maps mutations_to_map
+// produces, ops as binaries, bit indexes as integers)
+pub fn mutations_from_vecpak(bytes: &[u8]) -> Result<Vec<Mutation>, &'static str> {
+    let term = vecpak::decode(bytes)?;
+    let Term::List(items) = term else { return Err("mutations_not_a_list") };
+    items.iter().map(mutation_from_term).collect()
+}
+
+fn mutation_from_term(term: &Term) -> Result<Mutation, &'static str> {
+    let Term::PropList(pairs) = term else { return Err("mutation_not_a_map") };
+    let bytes = |field: &[u8]| match pl_find_opt(pairs, field) {
+        Some(Term::Binary(b)) => Ok(b.clone()),
+        _ => Err("mutation_invalid_field"),
+    };
+    let int = |field: &[u8]| match pl_find_opt(pairs, field) {
+        Some(Term::VarInt(v)) if *v >= 0 => Ok(*v as u64),
+        _ => Err("mutation_invalid_field"),
+    };
+
+    let op = bytes(b"op")?;
+    let table = bytes(b"table")?;
+    let key = bytes(b"key")?;
+    match op.as_slice() {
+        b"put" => Ok(Mutation::Put { value: bytes(b"value")?, op, table, key }),
+        b"delete" => Ok(Mutation::Delete { op, table, key }),
+        b"set_bit" => Ok(Mutation::SetBit { value: int(b"value")?, bloomsize: int(b"bloomsize")?, op, table, key }),
+        b"clear_bit" => Ok(Mutation::ClearBit { value: int(b"value")?, op, table, key }),
+        _ => Err("mutation_unknown_op"),
+    }
+}
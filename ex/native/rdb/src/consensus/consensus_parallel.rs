@@ -0,0 +1,275 @@
+use crate::consensus::{self, consensus_apply, consensus_kv, consensus_muts};
+use consensus_apply::{make_apply_env, ApplyEnv, CallerEnv, TxFields};
+use consensus_kv::Speculation;
+use consensus_muts::Mutation;
+use crate::consensus::bic::protocol;
+use crate::model::tx::TXU;
+use crate::{MultiThreaded, TransactionDB};
+
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+// Below this many txs speculation costs more than it saves
+pub const MIN_PARALLEL_TXS: usize = 4;
+
+// contractstate keys the entry txn wrote before speculation started, None = deleted
+pub type BaseState = HashMap<Vec<u8>, Option<Vec<u8>>>;
+
+// What a tx did when run against the base state. Either committed as is, or thrown
+// away and the tx executed again in order.
+pub struct SpecOutcome {
+    pub result: Result<Vec<u8>, Vec<u8>>,
+    pub muts: Vec<Mutation>,
+    pub muts_rev: Vec<Mutation>,
+    pub logs: Vec<Vec<u8>>,
+    pub exec_left: i128,
+    pub storage_left: i128,
+    // call_counter the tx ran with, assuming every tx before it moved it by one
+    pub call_counter: u32,
+    pub call_counter_delta: u32,
+    pub reads: HashSet<Vec<u8>>,
+    pub writes: HashMap<Vec<u8>, Option<Vec<u8>>>,
+}
+
+impl SpecOutcome {
+    // Every write reads the old value first, so this also catches write/write conflicts.
+    // If none of the keys it read changed since the base state, sequential execution
+    // would have seen the same values and produced the same outcome.
+    // A reverted tx keeps the bits it set (revert skips SetBit), which dropping the
+    // overlay cannot reproduce, so those rerun in order too. Same for a tx whose
+    // call_counter is not the one it would have had in order.
+    pub fn is_valid(&self, written: &HashSet<Vec<u8>>, call_counter: u32) -> bool {
+        if self.call_counter != call_counter {
+            return false;
+        }
+        if self.result.is_err() && self.muts.iter().any(|m| matches!(m, Mutation::SetBit { .. })) {
+            return false;
+        }
+        self.reads.iter().all(|key| !written.contains(key))
+    }
+}
+
+pub fn base_state(env: &ApplyEnv) -> BaseState {
+    let mut base = HashMap::new();
+    for m in &env.muts_final {
+        if m.table() != b"contractstate" || base.contains_key(m.key()) {
+            continue;
+        }
+        let value = env.txn.get_cf(&env.cf_contractstate, m.key()).unwrap();
+        base.insert(m.key().to_vec(), value);
+    }
+    base
+}
+
+// Runs every BIC tx of the entry on the rayon pool, each on its own env reading
+// base + committed db. Slots are None for txs that must run in order: WASM calls
+// (their rng seed depends on call_counter of the txs before) and range reads.
+pub fn speculate(
+    db: &TransactionDB<MultiThreaded>,
+    caller_env: &CallerEnv,
+    testnet: bool,
+    testnet_peddlebikes: &[Vec<u8>],
+    base: &BaseState,
+    txus: &[TXU],
+    tx_fields: &[TxFields],
+) -> Vec<Option<SpecOutcome>> {
+    txus.par_iter().zip(tx_fields).enumerate().map(|(index, (txu, fields))| {
+        if consensus::bls12_381::validate_public_key(&txu.tx.action.contract) {
+            return None;
+        }
+        speculate_tx(db, caller_env, testnet, testnet_peddlebikes, base, index, txu, fields)
+    }).collect()
+}
+
+fn speculate_tx(
+    db: &TransactionDB<MultiThreaded>,
+    caller_env: &CallerEnv,
+    testnet: bool,
+    testnet_peddlebikes: &[Vec<u8>],
+    base: &BaseState,
+    index: usize,
+    txu: &TXU,
+    fields: &TxFields,
+) -> Option<SpecOutcome> {
+    let TxFields { hash: tx_hash, signer: tx_signer } = *fields;
+
+    // Never written to, only there because ApplyEnv carries one
+    let txn = db.transaction();
+    let mut env = make_apply_env(db, txn,
+        db.cf_handle("contractstate")?, b"contractstate".to_vec(),
+        db.cf_handle("contractstate")?, db.cf_handle("contractstate_tree")?,
+        &caller_env.entry_signer, &caller_env.entry_prev_hash,
+        caller_env.entry_slot, caller_env.entry_prev_slot, caller_env.entry_height, caller_env.entry_epoch,
+        &caller_env.entry_vr, &caller_env.entry_vr_b3, &caller_env.entry_dr,
+        testnet, testnet_peddlebikes.to_vec());
+    env.spec = Some(Speculation::new(base));
+
+    // Same setup apply_entry does per tx. A BIC can still end up in call_wasmvm
+    // (testnet Contract.deploy runs init), seeded from call_counter, so the counter
+    // assumed here is recorded and checked before the outcome is committed.
+    let action = &txu.tx.action;
+    env.caller_env.tx_index = index as u64;
+    env.caller_env.tx_hash = tx_hash;
+    env.caller_env.tx_signer = tx_signer;
+    env.caller_env.tx_nonce = txu.tx.nonce;
+    env.caller_env.account_origin = tx_signer.to_vec();
+    env.caller_env.account_caller = tx_signer.to_vec();
+    env.caller_env.call_counter = caller_env.call_counter + index as u32 + 1;
+    env.caller_env.account_current = action.contract.clone();
+    env.exec_track = true;
+    env.exec_left = protocol::AMA_10_CENT;
+    env.exec_max = protocol::AMA_10_CENT;
+    env.storage_left = protocol::AMA_1_DOLLAR;
+    env.storage_max = protocol::AMA_1_DOLLAR;
+    let call_counter = env.caller_env.call_counter;
+
+    // A panic is a bug, rayon hands it on to apply_entry like the sequential path would
+    let res = consensus_apply::call_bic(&mut env, action.contract.clone(), action.function.clone(), action.args.clone(),
+        action.attached_symbol.clone(), action.attached_amount.clone()).map(|()| b"ok".to_vec());
+    let _ = env.txn.rollback();
+
+    let spec = env.spec.take()?;
+    if spec.ranged {
+        return None;
+    }
+    Some(SpecOutcome {
+        result: res.map_err(|e| e.reason().as_bytes().to_vec()),
+        muts: env.muts,
+        muts_rev: env.muts_rev,
+        logs: env.logs,
+        exec_left: env.exec_left,
+        storage_left: env.storage_left,
+        call_counter,
+        call_counter_delta: env.caller_env.call_counter - call_counter,
+        reads: spec.reads,
+        writes: spec.writes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consensus::{bintree::sha256, bls12_381};
+    use crate::model::entry::{Entry, Header};
+    use crate::model::tx::{Action, TX};
+    use crate::model::tx_receipt::TXReceipt;
+    use crate::{Options, TransactionDBOptions};
+
+    // Not on a segment or epoch boundary, call_exit stays out of the way
+    const HEIGHT: u64 = 12_345_678;
+
+    fn pk(seed: u8) -> Vec<u8> {
+        bls12_381::get_public_key(&[seed; 64]).unwrap().to_vec()
+    }
+
+    fn txu(signer: &[u8], nonce: u64, contract: &[u8], function: &[u8], args: Vec<Vec<u8>>) -> TXU {
+        TXU {
+            hash: sha256(&crate::bcat(&[signer, &nonce.to_be_bytes()])).to_vec(),
+            signature: vec![0; 96],
+            tx: TX {
+                signer: signer.to_vec(),
+                nonce,
+                action: Action {
+                    op: b"call".to_vec(),
+                    contract: contract.to_vec(),
+                    function: function.to_vec(),
+                    args,
+                    attached_symbol: None,
+                    attached_amount: None,
+                },
+            },
+        }
+    }
+
+    fn transfer(signer: &[u8], nonce: u64, receiver: &[u8], amount: &[u8]) -> TXU {
+        txu(signer, nonce, b"Coin", b"transfer", vec![receiver.to_vec(), amount.to_vec(), b"AMA".to_vec()])
+    }
+
+    fn entry(txs: Vec<TXU>) -> Entry {
+        Entry {
+            hash: vec![0; 32],
+            signature: vec![0; 96],
+            header: Header {
+                prev_hash: vec![0; 32],
+                height: HEIGHT,
+                slot: HEIGHT,
+                prev_slot: HEIGHT - 1,
+                signer: pk(0),
+                dr: vec![0; 32],
+                vr: vec![7; 96],
+                root_tx: vec![0; 32],
+                root_validator: vec![0; 32],
+            },
+            txs,
+            mask: None,
+            mask_size: None,
+            mask_set_size: None,
+        }
+    }
+
+    type Applied = (Vec<Mutation>, Vec<Mutation>, Vec<TXReceipt>, [u8; 32], [u8; 32]);
+
+    fn apply(entry: &Entry, funded: &[Vec<u8>], speculate: bool) -> Applied {
+        let path = std::env::temp_dir().join(format!("rdb_speculate_{}_{}", std::process::id(), speculate));
+        let _ = std::fs::remove_dir_all(&path);
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+        let db: TransactionDB<MultiThreaded> =
+            TransactionDB::open_cf(&opts, &TransactionDBOptions::default(), &path, ["contractstate", "contractstate_tree"]).unwrap();
+        {
+            let cf = db.cf_handle("contractstate").unwrap();
+            for pk in funded {
+                db.put_cf(&cf, crate::bcat(&[b"account:", pk, b":balance:AMA"]), b"100000000000").unwrap();
+            }
+        }
+
+        let out = {
+            let fields = consensus_apply::validate_entry(entry).unwrap();
+            let (txn, muts, muts_rev, receipts, root_receipts, root_contractstate, _) =
+                consensus_apply::apply_entry(&db, db.transaction(), entry.clone(), &fields, &[], &[], false, Vec::new(), false, speculate).unwrap();
+            drop(txn);
+            (muts, muts_rev, receipts, root_receipts, root_contractstate)
+        };
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+        out
+    }
+
+    fn receipt_fields(receipts: &[TXReceipt]) -> Vec<(Vec<u8>, bool, Vec<u8>, Vec<u8>, Vec<Vec<u8>>)> {
+        receipts.iter().map(|r| (r.txid.clone(), r.success, r.result.clone(), r.exec_used.clone(), r.logs.clone())).collect()
+    }
+
+    #[test]
+    fn speculation_matches_sequential() {
+        let (a, b, c, d, e) = (pk(1), pk(2), pk(3), pk(4), pk(5));
+        let sol = vec![0u8; crate::consensus::bic::sol::SOL_SIZE];
+        let entry = entry(vec![
+            transfer(&a, 1, &b, b"1000000000"),
+            // reads b, written by the tx before
+            transfer(&b, 1, &c, b"2000000000"),
+            // sets solbloom bits, then fails on the epoch
+            txu(&c, 1, b"Epoch", b"submit_sol", vec![sol.clone()]),
+            // reads the bits the failed tx left set
+            txu(&d, 1, b"Epoch", b"submit_sol", vec![sol]),
+            // ranged read of the validator list
+            txu(&a, 2, b"Epoch", b"slash_trainer", vec![pk(9), b"123".to_vec(), vec![0; 96], b"1".to_vec(), vec![0]]),
+            // touches nothing of the others, commits as speculated
+            txu(&d, 2, b"Epoch", b"set_emission_address", vec![pk(6)]),
+            transfer(&e, 1, &a, b"1000000000"),
+        ]);
+        let funded = [a, b, c, d, e];
+
+        let sequential = apply(&entry, &funded, false);
+        let speculated = apply(&entry, &funded, true);
+
+        let success: Vec<bool> = sequential.2.iter().map(|r| r.success).collect();
+        assert_eq!(success, [true, true, false, false, false, true, true]);
+
+        assert_eq!(speculated.0, sequential.0);
+        assert_eq!(speculated.1, sequential.1);
+        assert_eq!(receipt_fields(&speculated.2), receipt_fields(&sequential.2));
+        assert_eq!(speculated.3, sequential.3);
+        assert_eq!(speculated.4, sequential.4);
+    }
+}
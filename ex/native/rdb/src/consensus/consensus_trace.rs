@@ -0,0 +1,151 @@
+use crate::consensus::consensus_apply::ApplyEnv;
+
+// Opt-in call tree of the txs an env executes, for debugging failed txs. Every hook
+// is a no-op while ApplyEnv.trace is None. Only what runs inside a call is recorded,
+// the nonce / deposit / refund bookkeeping around each tx is not.
+#[derive(Default)]
+pub struct Trace {
+    pub txs: Vec<TxTrace>,
+    // Calls entered and not yet returned, innermost last
+    open: Vec<Frame>,
+}
+
+pub struct TxTrace {
+    pub index: u64,
+    pub tx_hash: [u8; 32],
+    pub error: Option<Vec<u8>>,
+    pub calls: Vec<Frame>,
+}
+
+pub enum FrameStatus {
+    Ok(Vec<u8>),
+    // The frame the tx failed in, with the failure reason
+    Failed(Vec<u8>),
+    // Left by a failure in a frame it called
+    Unwound,
+}
+
+pub struct Frame {
+    pub caller: Vec<u8>,
+    pub callee: Vec<u8>,
+    pub function: Vec<u8>,
+    pub args: Vec<Vec<u8>>,
+    // Exec points, including the calls it made
+    pub exec_used: i128,
+    exec_start: i128,
+    pub status: FrameStatus,
+    pub events: Vec<Event>,
+}
+
+pub enum Event {
+    Read { key: Vec<u8>, value: Option<Vec<u8>> },
+    // Range read from prefix ++ from, found key is without the prefix
+    Scan { prefix: Vec<u8>, from: Vec<u8>, found: Option<(Vec<u8>, Vec<u8>)> },
+    // Recorded before the write lands, a write that fails is the last event of its frame
+    Write { key: Vec<u8>, old: Option<Vec<u8>>, new: Option<Vec<u8>> },
+    SetBit { key: Vec<u8>, bit: u64, was_set: bool },
+    Call(Frame),
+}
+
+impl Trace {
+    // Pops the innermost open frame into its parent, or into the tx when it is a root call
+    fn close(&mut self, exec_left: i128, status: FrameStatus) {
+        let Some(mut frame) = self.open.pop() else { return };
+        frame.exec_used = frame.exec_start - exec_left;
+        frame.status = status;
+        match self.open.last_mut() {
+            Some(parent) => parent.events.push(Event::Call(frame)),
+            None => {
+                if let Some(tx) = self.txs.last_mut() {
+                    tx.calls.push(frame);
+                }
+            }
+        }
+    }
+
+    fn record(&mut self, event: Event) {
+        if let Some(frame) = self.open.last_mut() {
+            frame.events.push(event);
+        }
+    }
+}
+
+pub fn begin_tx(env: &mut ApplyEnv, index: u64, tx_hash: &[u8; 32]) {
+    if let Some(trace) = env.trace.as_mut() {
+        trace.open.clear();
+        trace.txs.push(TxTrace { index, tx_hash: *tx_hash, error: None, calls: Vec::new() });
+    }
+}
+
+// error is the panic reason of a failed tx. The innermost frame still open is where it
+// failed, everything above it unwound.
+pub fn end_tx(env: &mut ApplyEnv, error: Option<&[u8]>) {
+    let exec_left = env.exec_left;
+    if let Some(trace) = env.trace.as_mut() {
+        if let Some(error) = error {
+            trace.close(exec_left, FrameStatus::Failed(error.to_vec()));
+            while !trace.open.is_empty() {
+                trace.close(exec_left, FrameStatus::Unwound);
+            }
+            if let Some(tx) = trace.txs.last_mut() {
+                tx.error = Some(error.to_vec());
+            }
+        }
+    }
+}
+
+// Returns the depth to hand back to exit
+pub fn enter(env: &mut ApplyEnv, callee: &[u8], function: &[u8], args: &[Vec<u8>]) -> usize {
+    let exec_left = env.exec_left;
+    let caller = &env.caller_env.account_caller;
+    match env.trace.as_mut() {
+        None => 0,
+        Some(trace) => {
+            trace.open.push(Frame {
+                caller: caller.clone(),
+                callee: callee.to_vec(),
+                function: function.to_vec(),
+                args: args.to_vec(),
+                exec_used: 0,
+                exec_start: exec_left,
+                status: FrameStatus::Unwound,
+                events: Vec::new(),
+            });
+            trace.open.len() - 1
+        }
+    }
+}
+
+pub fn exit(env: &mut ApplyEnv, depth: usize, result: &[u8]) {
+    let exec_left = env.exec_left;
+    if let Some(trace) = env.trace.as_mut() {
+        while trace.open.len() > depth + 1 {
+            trace.close(exec_left, FrameStatus::Unwound);
+        }
+        trace.close(exec_left, FrameStatus::Ok(result.to_vec()));
+    }
+}
+
+pub fn read(env: &mut ApplyEnv, key: &[u8], value: Option<&[u8]>) {
+    if let Some(trace) = env.trace.as_mut() {
+        trace.record(Event::Read { key: key.to_vec(), value: value.map(<[u8]>::to_vec) });
+    }
+}
+
+pub fn scan(env: &mut ApplyEnv, prefix: &[u8], from: &[u8], found: Option<&(Vec<u8>, Vec<u8>)>) {
+    if let Some(trace) = env.trace.as_mut() {
+        trace.record(Event::Scan { prefix: prefix.to_vec(), from: from.to_vec(), found: found.cloned() });
+    }
+}
+
+pub fn write(env: &mut ApplyEnv, key: &[u8], old: Option<&[u8]>, new: Option<&[u8]>) {
+    if let Some(trace) = env.trace.as_mut() {
+        trace.record(Event::Write { key: key.to_vec(), old: old.map(<[u8]>::to_vec), new: new.map(<[u8]>::to_vec) });
+    }
+}
+
+pub fn set_bit(env: &mut ApplyEnv, key: &[u8], bit: u64, was_set: bool) {
+    if let Some(trace) = env.trace.as_mut() {
+        trace.record(Event::SetBit { key: key.to_vec(), bit, was_set });
+    }
+}
@@ -0,0 +1,170 @@
+use crate::consensus::{aggsig, bls12_381, bic::protocol};
+use crate::model::_codec::DecodeError;
+use crate::model::tx::{self, TXU};
+
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+
+pub const TX_MAX_ARGS: usize = 16;
+pub const SYMBOL_MAX_LEN: usize = 32;
+
+// Why a packed txu was refused. Invalid reasons are the same as TX.validate's errors
+// so the txpool can treat both alike.
+#[derive(Debug)]
+pub enum TxError {
+    Decode(DecodeError),
+    Invalid(&'static str),
+}
+
+impl From<DecodeError> for TxError {
+    fn from(e: DecodeError) -> Self {
+        TxError::Decode(e)
+    }
+}
+
+// Stateless checks of TX.validate for one packed txu, done on the canonical bytes and
+// in the same order so both report the same reason. Returns the txu and its historical
+// cost, the balance check needs chain state. tx_size is config :ama, :tx_size.
+pub fn validate_txu_packed(packed: &[u8], tx_size: usize) -> Result<(TXU, i128), TxError> {
+    let txu = crate::model::validate_canonical::<TXU>(packed)?;
+    let tx_bytes = tx::to_bytes_tx(&txu.tx).map_err(TxError::Invalid)?;
+    if tx_bytes.len() >= tx_size { return Err(TxError::Invalid("too_large")) }
+    if Sha256::digest(&tx_bytes).as_slice() != txu.hash.as_slice() { return Err(TxError::Invalid("invalid_hash")) }
+    bls12_381::verify(&txu.tx.signer, &txu.signature, &txu.hash, aggsig::DST_TX)
+        .map_err(|_| TxError::Invalid("invalid_signature"))?;
+
+    // nonce, contract, function and args are typed by the decoder
+    let action = &txu.tx.action;
+    if action.op != b"call" { return Err(TxError::Invalid("op_must_be_call")) }
+    if action.args.len() > TX_MAX_ARGS { return Err(TxError::Invalid("args_length_cannot_exceed_16")) }
+    if let Some(symbol) = &action.attached_symbol {
+        if symbol.is_empty() || symbol.len() > SYMBOL_MAX_LEN { return Err(TxError::Invalid("attached_symbol_wrong_size")) }
+    }
+    match (&action.attached_symbol, &action.attached_amount) {
+        (Some(_), None) => return Err(TxError::Invalid("attached_amount_must_be_included")),
+        (None, Some(_)) => return Err(TxError::Invalid("attached_symbol_must_be_included")),
+        _ => {}
+    }
+
+    let cost = protocol::tx_historical_cost(&txu);
+    Ok((txu, cost))
+}
+
+// Signature checks dominate, so a batch from a tx flood is spread over the rayon pool
+pub fn validate_txus_packed(txus: &[&[u8]], tx_size: usize) -> Vec<Result<(TXU, i128), TxError>> {
+    txus.par_iter().map(|packed| validate_txu_packed(packed, tx_size)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::_codec::EncodeIntoBuf;
+    use crate::model::tx::{Action, TX};
+
+    const SK: [u8; 64] = [7; 64];
+    // config :ama, :tx_size
+    const TX_SIZE: usize = 786_432;
+
+    fn tx_with(f: impl FnOnce(&mut Action)) -> TX {
+        let mut action = Action {
+            op: b"call".to_vec(),
+            contract: b"Coin".to_vec(),
+            function: b"transfer".to_vec(),
+            args: vec![vec![1; 48], b"100".to_vec(), b"AMA".to_vec()],
+            attached_symbol: None,
+            attached_amount: None,
+        };
+        f(&mut action);
+        TX { signer: bls12_381::get_public_key(&SK).unwrap().to_vec(), nonce: 1, action }
+    }
+
+    // Hashed and signed the way TX.build does
+    fn sign(tx: TX) -> TXU {
+        let hash = Sha256::digest(tx::to_bytes_tx(&tx).unwrap()).to_vec();
+        let signature = bls12_381::sign(&SK, &hash, aggsig::DST_TX).unwrap().to_vec();
+        TXU { hash, signature, tx }
+    }
+
+    fn pack(txu: &TXU) -> Vec<u8> {
+        let mut buf = Vec::new();
+        txu.encode_into_buf(&mut buf).unwrap();
+        buf
+    }
+
+    fn reason(txu: &TXU) -> &'static str {
+        match validate_txu_packed(&pack(txu), TX_SIZE) {
+            Ok(_) => "ok",
+            Err(TxError::Invalid(reason)) => reason,
+            Err(TxError::Decode(e)) => panic!("decode: {}", e),
+        }
+    }
+
+    #[test]
+    fn historical_cost() {
+        let txu = sign(tx_with(|_| {}));
+        let (_, cost) = validate_txu_packed(&pack(&txu), TX_SIZE).unwrap();
+        let len = tx::to_bytes_tx(&txu.tx).unwrap().len() as i128;
+        assert_eq!(cost, protocol::AMA_1_CENT.max(protocol::COST_PER_BYTE_HISTORICAL * len));
+        assert_eq!(cost, protocol::AMA_1_CENT);
+
+        // Past about 1.5kB the per byte cost takes over
+        let txu = sign(tx_with(|a| a.args.push(vec![0; 10_000])));
+        let (_, cost) = validate_txu_packed(&pack(&txu), TX_SIZE).unwrap();
+        let len = tx::to_bytes_tx(&txu.tx).unwrap().len() as i128;
+        assert_eq!(cost, protocol::COST_PER_BYTE_HISTORICAL * len);
+    }
+
+    #[test]
+    fn decode_errors() {
+        assert!(matches!(validate_txu_packed(&[0xff, 0xff], TX_SIZE), Err(TxError::Decode(_))));
+
+        let mut txu = sign(tx_with(|_| {}));
+        txu.signature.truncate(95);
+        assert!(matches!(validate_txu_packed(&pack(&txu), TX_SIZE), Err(TxError::Decode(DecodeError::WrongLength { .. }))));
+    }
+
+    #[test]
+    fn too_large() {
+        assert_eq!(reason(&sign(tx_with(|a| a.args = vec![vec![0; TX_SIZE]]))), "too_large");
+    }
+
+    #[test]
+    fn invalid_hash() {
+        let mut txu = sign(tx_with(|_| {}));
+        txu.hash[0] ^= 1;
+        assert_eq!(reason(&txu), "invalid_hash");
+    }
+
+    #[test]
+    fn invalid_signature() {
+        let other = |mut txu: TXU| {
+            txu.signature = bls12_381::sign(&[8; 64], &txu.hash, aggsig::DST_TX).unwrap().to_vec();
+            txu
+        };
+        assert_eq!(reason(&other(sign(tx_with(|_| {})))), "invalid_signature");
+        // Before the action checks, as in TX.validate
+        assert_eq!(reason(&other(sign(tx_with(|a| a.op = b"deploy".to_vec())))), "invalid_signature");
+    }
+
+    #[test]
+    fn action_errors() {
+        assert_eq!(reason(&sign(tx_with(|a| a.op = b"deploy".to_vec()))), "op_must_be_call");
+        assert_eq!(reason(&sign(tx_with(|a| a.args = vec![b"x".to_vec(); 17]))), "args_length_cannot_exceed_16");
+        assert_eq!(reason(&sign(tx_with(|a| a.args = vec![b"x".to_vec(); 16]))), "ok");
+    }
+
+    #[test]
+    fn attachment_errors() {
+        let attach = |symbol: Option<&[u8]>, amount: Option<&[u8]>| sign(tx_with(|a| {
+            a.attached_symbol = symbol.map(<[u8]>::to_vec);
+            a.attached_amount = amount.map(<[u8]>::to_vec);
+        }));
+        assert_eq!(reason(&attach(Some(b"AMA"), Some(b"1"))), "ok");
+        assert_eq!(reason(&attach(Some(b""), Some(b"1"))), "attached_symbol_wrong_size");
+        assert_eq!(reason(&attach(Some(&[b'A'; 33]), Some(b"1"))), "attached_symbol_wrong_size");
+        // Size is checked before the pairing
+        assert_eq!(reason(&attach(Some(&[b'A'; 33]), None)), "attached_symbol_wrong_size");
+        assert_eq!(reason(&attach(Some(b"AMA"), None)), "attached_amount_must_be_included");
+        assert_eq!(reason(&attach(None, Some(b"1"))), "attached_symbol_must_be_included");
+    }
+}
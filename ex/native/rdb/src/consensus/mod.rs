@@ -7,5 +7,9 @@ pub mod bintree_rdb_prove;
 pub mod bls12_381;
 
 pub mod consensus_apply;
+pub mod consensus_error;
 pub mod consensus_kv;
 pub mod consensus_muts;
+pub mod consensus_parallel;
+pub mod consensus_trace;
+pub mod consensus_tx;
@@ -0,0 +1,564 @@
+use rustler::types::map::MapIterator;
+use rustler::{Atom, Encoder, Env, Error, Term};
+use std::collections::HashMap;
+
+use crate::atoms;
+use crate::{Options, TransactionDBOptions, SliceTransform,
+    Cache, LruCacheOptions, BlockBasedOptions, DBCompressionType, BlockBasedIndexType};
+
+const MB: usize = 1024 * 1024;
+const GB: usize = 1024 * MB;
+
+// Per column family tuning, defaults come from DbTuning::cf_defaults
+#[derive(Debug, Clone)]
+pub struct CfTuning {
+    pub write_buffer_size: usize,
+    pub max_write_buffer_number: i32,
+    pub min_write_buffer_number_to_merge: i32,
+    pub target_file_size_base: u64,
+    pub max_compaction_bytes: u64,
+    pub compression_per_level: Vec<DBCompressionType>,
+    pub bloom_bits: f64,
+    pub prefix_extractor: Option<usize>,
+    pub memtable_prefix_bloom_ratio: f64,
+    // Integrated BlobDB, values >= min_blob_size go to blob files instead of the LSM
+    pub enable_blob_files: bool,
+    pub min_blob_size: u64,
+    pub blob_file_size: u64,
+    pub blob_compression: DBCompressionType,
+    pub enable_blob_gc: bool,
+    pub blob_gc_age_cutoff: f64,
+}
+
+impl Default for CfTuning {
+    fn default() -> Self {
+        CfTuning {
+            write_buffer_size: 512 * MB,
+            max_write_buffer_number: 6,
+            min_write_buffer_number_to_merge: 2,
+            target_file_size_base: 8 * GB as u64,
+            max_compaction_bytes: 20 * GB as u64,
+            compression_per_level: vec![
+                DBCompressionType::None,  // L0
+                DBCompressionType::None,  // L1
+                DBCompressionType::Zstd,  // L2
+                DBCompressionType::Zstd,  // L3
+                DBCompressionType::Zstd,  // L4
+                DBCompressionType::Zstd,  // L5
+                DBCompressionType::Zstd,  // L6
+            ],
+            bloom_bits: 10.0,
+            prefix_extractor: None,
+            memtable_prefix_bloom_ratio: 0.1,
+            enable_blob_files: false,
+            min_blob_size: 4096,
+            blob_file_size: 256 * MB as u64,
+            blob_compression: DBCompressionType::Zstd,
+            enable_blob_gc: true,
+            blob_gc_age_cutoff: 0.25,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DbTuning {
+    pub row_cache_size: usize,
+    pub block_cache_size: usize,
+    pub max_open_files: i32,
+    pub parallelism: i32,
+    pub max_background_jobs: i32,
+    pub max_total_wal_size: u64,
+    pub lock_timeout: i64,
+    pub cf_defaults: CfTuning,
+    pub cf: HashMap<String, CfTuning>,
+}
+
+impl Default for DbTuning {
+    fn default() -> Self {
+        DbTuning {
+            row_cache_size: 4 * GB,
+            block_cache_size: 4 * GB,
+            max_open_files: 30000,
+            parallelism: 2,
+            max_background_jobs: 2,
+            max_total_wal_size: 2 * GB as u64,
+            lock_timeout: 3000,
+            cf_defaults: CfTuning::default(),
+            cf: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct DbCaches {
+    pub row_cache: Cache,
+    pub block_cache: Cache,
+}
+
+// Options map as decoded from the Elixir term. Parsing works on this instead of on
+// Terms so the key / value checks below can be unit tested without a BEAM.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Opt {
+    Int(i128),
+    Float(f64),
+    Bool(bool),
+    Atom(String),
+    Str(String),
+    List(Vec<Opt>),
+    Map(Vec<(Opt, Opt)>),
+    Other,
+}
+
+impl Opt {
+    pub fn from_term(term: Term) -> Opt {
+        if let Ok(b) = term.decode::<bool>() { return Opt::Bool(b) }
+        if let Ok(a) = term.atom_to_string() { return Opt::Atom(a) }
+        if let Ok(i) = term.decode::<i64>() { return Opt::Int(i as i128) }
+        if let Ok(i) = term.decode::<u64>() { return Opt::Int(i as i128) }
+        if let Ok(f) = term.decode::<f64>() { return Opt::Float(f) }
+        if let Some(iter) = MapIterator::new(term) {
+            return Opt::Map(iter.map(|(k, v)| (Opt::from_term(k), Opt::from_term(v))).collect())
+        }
+        if let Ok(list) = term.decode::<Vec<Term>>() {
+            return Opt::List(list.into_iter().map(Opt::from_term).collect())
+        }
+        if let Ok(s) = term.decode::<String>() { return Opt::Str(s) }
+        Opt::Other
+    }
+}
+
+// {invalid_option, key}, {unknown_option, key} or {unknown_cf, name}
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptError {
+    Invalid(&'static str),
+    Unknown(String),
+    UnknownCf(String),
+}
+
+impl Encoder for OptError {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        let atom = |name: &str| Atom::from_str(env, name).unwrap_or_else(|_| atoms::key());
+        match self {
+            OptError::Invalid(key) => (atoms::invalid_option(), atom(key)).encode(env),
+            OptError::Unknown(key) => (atoms::unknown_option(), atom(key)).encode(env),
+            OptError::UnknownCf(name) => (atoms::unknown_cf(), name.as_str()).encode(env),
+        }
+    }
+}
+
+impl From<OptError> for Error {
+    fn from(e: OptError) -> Self {
+        Error::Term(Box::new(e))
+    }
+}
+
+trait FromOpt: Sized {
+    fn from_opt(v: &Opt) -> Option<Self>;
+}
+
+macro_rules! from_opt_int {
+    ($($t:ty),*) => {$(
+        impl FromOpt for $t {
+            fn from_opt(v: &Opt) -> Option<Self> {
+                match v { Opt::Int(i) => <$t>::try_from(*i).ok(), _ => None }
+            }
+        }
+    )*};
+}
+from_opt_int!(usize, i32, i64, u64);
+
+impl FromOpt for f64 {
+    fn from_opt(v: &Opt) -> Option<Self> {
+        match v { Opt::Float(f) => Some(*f), Opt::Int(i) => Some(*i as f64), _ => None }
+    }
+}
+
+impl FromOpt for bool {
+    fn from_opt(v: &Opt) -> Option<Self> {
+        match v { Opt::Bool(b) => Some(*b), _ => None }
+    }
+}
+
+fn decode_num<T: FromOpt>(key: &'static str, value: &Opt) -> Result<T, OptError> {
+    T::from_opt(value).ok_or(OptError::Invalid(key))
+}
+
+fn decode_key(k: &Opt) -> Result<&str, OptError> {
+    match k {
+        Opt::Atom(a) => Ok(a.as_str()),
+        _ => Err(OptError::Invalid("key")),
+    }
+}
+
+fn decode_compression(key: &'static str, value: &Opt) -> Result<DBCompressionType, OptError> {
+    match value {
+        Opt::Atom(a) => match a.as_str() {
+            "none" => Ok(DBCompressionType::None),
+            "snappy" => Ok(DBCompressionType::Snappy),
+            "zlib" => Ok(DBCompressionType::Zlib),
+            "bz2" => Ok(DBCompressionType::Bz2),
+            "lz4" => Ok(DBCompressionType::Lz4),
+            "lz4hc" => Ok(DBCompressionType::Lz4hc),
+            "zstd" => Ok(DBCompressionType::Zstd),
+            _ => Err(OptError::Invalid(key)),
+        },
+        _ => Err(OptError::Invalid(key)),
+    }
+}
+
+fn compression_atom(c: &DBCompressionType) -> Atom {
+    match c {
+        DBCompressionType::None => atoms::none(),
+        DBCompressionType::Snappy => atoms::snappy(),
+        DBCompressionType::Zlib => atoms::zlib(),
+        DBCompressionType::Bz2 => atoms::bz2(),
+        DBCompressionType::Lz4 => atoms::lz4(),
+        DBCompressionType::Lz4hc => atoms::lz4hc(),
+        DBCompressionType::Zstd => atoms::zstd(),
+    }
+}
+
+fn map_pairs<'o>(value: &'o Opt, key: &'static str) -> Result<&'o [(Opt, Opt)], OptError> {
+    match value {
+        Opt::Map(pairs) => Ok(pairs.as_slice()),
+        _ => Err(OptError::Invalid(key)),
+    }
+}
+
+impl CfTuning {
+    // Built-in overrides kept from the original hardcoded open path
+    pub fn for_cf(defaults: &CfTuning, name: &str) -> CfTuning {
+        let mut t = defaults.clone();
+        match name {
+            "tx" => t.prefix_extractor = Some(8),
+            "tx_filter" => t.prefix_extractor = Some(16),
+            _ => (),
+        }
+        t
+    }
+
+    // Returns Ok(false) if the key is not a column family option
+    fn set(&mut self, key: &str, value: &Opt) -> Result<bool, OptError> {
+        match key {
+            "write_buffer_size" => self.write_buffer_size = decode_num("write_buffer_size", value)?,
+            "max_write_buffer_number" => self.max_write_buffer_number = decode_num("max_write_buffer_number", value)?,
+            "min_write_buffer_number_to_merge" => self.min_write_buffer_number_to_merge = decode_num("min_write_buffer_number_to_merge", value)?,
+            "target_file_size_base" => self.target_file_size_base = decode_num("target_file_size_base", value)?,
+            "max_compaction_bytes" => self.max_compaction_bytes = decode_num("max_compaction_bytes", value)?,
+            "bloom_bits" => self.bloom_bits = decode_num("bloom_bits", value)?,
+            "memtable_prefix_bloom_ratio" => self.memtable_prefix_bloom_ratio = decode_num("memtable_prefix_bloom_ratio", value)?,
+            "compression_per_level" => {
+                let Opt::List(list) = value else { return Err(OptError::Invalid("compression_per_level")) };
+                self.compression_per_level = list.iter()
+                    .map(|v| decode_compression("compression_per_level", v))
+                    .collect::<Result<Vec<_>, _>>()?;
+            }
+            "enable_blob_files" => self.enable_blob_files = decode_num("enable_blob_files", value)?,
+            "min_blob_size" => self.min_blob_size = decode_num("min_blob_size", value)?,
+            "blob_file_size" => self.blob_file_size = decode_num("blob_file_size", value)?,
+            "blob_compression" => self.blob_compression = decode_compression("blob_compression", value)?,
+            "enable_blob_gc" => self.enable_blob_gc = decode_num("enable_blob_gc", value)?,
+            "blob_gc_age_cutoff" => self.blob_gc_age_cutoff = decode_num("blob_gc_age_cutoff", value)?,
+            "compression" => {
+                let c = decode_compression("compression", value)?;
+                self.compression_per_level = vec![c; self.compression_per_level.len().max(1)];
+            }
+            "prefix_extractor" => {
+                self.prefix_extractor = match value {
+                    Opt::Atom(a) if a == "nil" => None,
+                    _ => Some(decode_num("prefix_extractor", value)?),
+                };
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    pub fn from_term<'a>(defaults: &CfTuning, name: &str, term: Term<'a>) -> Result<CfTuning, Error> {
+        Ok(CfTuning::from_opt(defaults, name, &Opt::from_term(term))?)
+    }
+
+    pub fn from_opt(defaults: &CfTuning, name: &str, opts: &Opt) -> Result<CfTuning, OptError> {
+        let mut t = CfTuning::for_cf(defaults, name);
+        for (k, v) in map_pairs(opts, "cf")? {
+            let key = decode_key(k)?;
+            if !t.set(key, v)? {
+                return Err(OptError::Unknown(key.to_string()));
+            }
+        }
+        Ok(t)
+    }
+
+    pub fn block_based_options(&self, block_cache: &Cache) -> BlockBasedOptions {
+        let mut block_based_options = BlockBasedOptions::default();
+        block_based_options.set_block_cache(block_cache);
+
+        block_based_options.set_bloom_filter(self.bloom_bits, false);
+        block_based_options.set_index_type(BlockBasedIndexType::TwoLevelIndexSearch);
+        block_based_options.set_cache_index_and_filter_blocks(true);
+        block_based_options.set_cache_index_and_filter_blocks_with_high_priority(true);
+        block_based_options.set_pin_top_level_index_and_filter(true);
+        block_based_options.set_partition_filters(true);
+        block_based_options.set_pin_l0_filter_and_index_blocks_in_cache(false);
+        block_based_options
+    }
+
+    pub fn options(&self, caches: &DbCaches, max_total_wal_size: u64) -> Options {
+        let mut cf_opts = Options::default();
+        cf_opts.set_row_cache(&caches.row_cache);
+        cf_opts.set_block_based_table_factory(&self.block_based_options(&caches.block_cache));
+
+        let dict_bytes = 32 * 1024;
+        cf_opts.set_compression_per_level(&self.compression_per_level);
+
+        cf_opts.set_compression_type(DBCompressionType::Zstd);
+        cf_opts.set_compression_options(-14, 2, 0, dict_bytes);
+        cf_opts.set_zstd_max_train_bytes(100 * dict_bytes);
+        /*
+        cf_opts.set_bottommost_compression_type(DBCompressionType::Zstd);
+        cf_opts.set_bottommost_compression_options(-14, 2, 0, dict_bytes, true);
+        cf_opts.set_bottommost_zstd_max_train_bytes(100 * dict_bytes, true);
+        */
+
+        cf_opts.set_max_total_wal_size(max_total_wal_size);
+        cf_opts.set_target_file_size_base(self.target_file_size_base);
+        //cf_opts.set_target_file_size_base(2 * 1024 * 1024 * 1024);
+        //cf_opts.set_target_file_size_multiplier(2);
+        cf_opts.set_max_compaction_bytes(self.max_compaction_bytes);
+
+        // Bigger L0 flushes
+        cf_opts.set_write_buffer_size(self.write_buffer_size);
+        cf_opts.set_max_write_buffer_number(self.max_write_buffer_number);
+        cf_opts.set_min_write_buffer_number_to_merge(self.min_write_buffer_number_to_merge);
+        // L0 thresholds
+        cf_opts.set_level_zero_file_num_compaction_trigger(20);
+        cf_opts.set_level_zero_slowdown_writes_trigger(40);
+        cf_opts.set_level_zero_stop_writes_trigger(100);
+        cf_opts.set_max_subcompactions(1);
+        //cf_opts.set_periodic_compaction_seconds(0);
+
+        //cf_opts.set_level_compaction_dynamic_level_bytes(false);
+
+        if let Some(len) = self.prefix_extractor {
+            cf_opts.set_prefix_extractor(SliceTransform::create_fixed_prefix(len));
+            cf_opts.set_memtable_prefix_bloom_ratio(self.memtable_prefix_bloom_ratio);
+        }
+
+        if self.enable_blob_files {
+            cf_opts.set_enable_blob_files(true);
+            cf_opts.set_min_blob_size(self.min_blob_size);
+            cf_opts.set_blob_file_size(self.blob_file_size);
+            cf_opts.set_blob_compression_type(self.blob_compression);
+            cf_opts.set_enable_blob_gc(self.enable_blob_gc);
+            cf_opts.set_blob_gc_age_cutoff(self.blob_gc_age_cutoff);
+        }
+        cf_opts
+    }
+}
+
+// Same keys from_term accepts, so list_cf output can be fed back into create_cf
+impl Encoder for CfTuning {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        let compression: Vec<Atom> = self.compression_per_level.iter().map(compression_atom).collect();
+        let prefix_extractor = match self.prefix_extractor {
+            Some(len) => len.encode(env),
+            None => atoms::nil().encode(env),
+        };
+        let mut map = Term::map_new(env);
+        map = map.map_put(atoms::write_buffer_size(), self.write_buffer_size).ok().unwrap();
+        map = map.map_put(atoms::max_write_buffer_number(), self.max_write_buffer_number).ok().unwrap();
+        map = map.map_put(atoms::min_write_buffer_number_to_merge(), self.min_write_buffer_number_to_merge).ok().unwrap();
+        map = map.map_put(atoms::target_file_size_base(), self.target_file_size_base).ok().unwrap();
+        map = map.map_put(atoms::max_compaction_bytes(), self.max_compaction_bytes).ok().unwrap();
+        map = map.map_put(atoms::compression_per_level(), compression).ok().unwrap();
+        map = map.map_put(atoms::bloom_bits(), self.bloom_bits).ok().unwrap();
+        map = map.map_put(atoms::prefix_extractor(), prefix_extractor).ok().unwrap();
+        map = map.map_put(atoms::memtable_prefix_bloom_ratio(), self.memtable_prefix_bloom_ratio).ok().unwrap();
+        map = map.map_put(atoms::enable_blob_files(), self.enable_blob_files).ok().unwrap();
+        map = map.map_put(atoms::min_blob_size(), self.min_blob_size).ok().unwrap();
+        map = map.map_put(atoms::blob_file_size(), self.blob_file_size).ok().unwrap();
+        map = map.map_put(atoms::blob_compression(), compression_atom(&self.blob_compression)).ok().unwrap();
+        map = map.map_put(atoms::enable_blob_gc(), self.enable_blob_gc).ok().unwrap();
+        map = map.map_put(atoms::blob_gc_age_cutoff(), self.blob_gc_age_cutoff).ok().unwrap();
+        map
+    }
+}
+
+impl DbTuning {
+    // cf_names are the families being opened, overrides for any other are refused
+    // so a typo in the config does not silently do nothing
+    pub fn from_term<'a>(term: Term<'a>, cf_names: &[String]) -> Result<DbTuning, Error> {
+        Ok(DbTuning::from_opt(&Opt::from_term(term), cf_names)?)
+    }
+
+    pub fn from_opt(opts: &Opt, cf_names: &[String]) -> Result<DbTuning, OptError> {
+        let mut t = DbTuning::default();
+        let mut cf_opt: Option<&Opt> = None;
+
+        for (k, v) in map_pairs(opts, "options")? {
+            let key = decode_key(k)?;
+            match key {
+                "row_cache_size" => t.row_cache_size = decode_num("row_cache_size", v)?,
+                "block_cache_size" => t.block_cache_size = decode_num("block_cache_size", v)?,
+                "max_open_files" => t.max_open_files = decode_num("max_open_files", v)?,
+                "parallelism" => t.parallelism = decode_num("parallelism", v)?,
+                "max_background_jobs" => t.max_background_jobs = decode_num("max_background_jobs", v)?,
+                "max_total_wal_size" => t.max_total_wal_size = decode_num("max_total_wal_size", v)?,
+                "lock_timeout" => t.lock_timeout = decode_num("lock_timeout", v)?,
+                "cf" => cf_opt = Some(v),
+                _ => if !t.cf_defaults.set(key, v)? {
+                    return Err(OptError::Unknown(key.to_string()));
+                }
+            }
+        }
+
+        // Per-CF overrides layer on top of the (possibly overridden) defaults
+        if let Some(cf_opt) = cf_opt {
+            for (k, v) in map_pairs(cf_opt, "cf")? {
+                let name = match k {
+                    Opt::Str(name) => name,
+                    _ => return Err(OptError::Invalid("cf")),
+                };
+                if !cf_names.iter().any(|n| n == name) {
+                    return Err(OptError::UnknownCf(name.clone()));
+                }
+                let cf_t = CfTuning::from_opt(&t.cf_defaults, name, v)?;
+                t.cf.insert(name.clone(), cf_t);
+            }
+        }
+        Ok(t)
+    }
+
+    pub fn cf_tuning(&self, name: &str) -> CfTuning {
+        match self.cf.get(name) {
+            Some(t) => t.clone(),
+            None => CfTuning::for_cf(&self.cf_defaults, name),
+        }
+    }
+
+    pub fn caches(&self) -> DbCaches {
+        let mut lru_opts = LruCacheOptions::default();
+        lru_opts.set_capacity(self.row_cache_size);
+        lru_opts.set_num_shard_bits(8);
+        DbCaches {
+            row_cache: Cache::new_lru_cache_opts(&lru_opts),
+            block_cache: Cache::new_lru_cache(self.block_cache_size),
+        }
+    }
+
+    pub fn db_options(&self) -> Options {
+        let mut db_opts = Options::default();
+        db_opts.create_if_missing(true);
+        db_opts.create_missing_column_families(true);
+        db_opts.set_max_open_files(self.max_open_files);
+        //more threads
+        db_opts.increase_parallelism(self.parallelism);
+        db_opts.set_max_background_jobs(self.max_background_jobs);
+
+        db_opts.set_max_total_wal_size(self.max_total_wal_size);
+        db_opts.set_target_file_size_base(self.cf_defaults.target_file_size_base);
+        //db_opts.set_target_file_size_base(2 * 1024 * 1024 * 1024);
+        //db_opts.set_target_file_size_multiplier(2);
+        db_opts.set_max_compaction_bytes(self.cf_defaults.max_compaction_bytes);
+
+        db_opts.enable_statistics();
+        db_opts.set_statistics_level(rust_rocksdb::statistics::StatsLevel::All);
+        db_opts.set_skip_stats_update_on_db_open(true);
+
+        // Bigger L0 flushes
+        db_opts.set_write_buffer_size(self.cf_defaults.write_buffer_size);
+        db_opts.set_max_write_buffer_number(self.cf_defaults.max_write_buffer_number);
+        db_opts.set_min_write_buffer_number_to_merge(self.cf_defaults.min_write_buffer_number_to_merge);
+        // L0 thresholds
+        db_opts.set_level_zero_file_num_compaction_trigger(8);
+        db_opts.set_level_zero_slowdown_writes_trigger(30);
+        db_opts.set_level_zero_stop_writes_trigger(100);
+        db_opts.set_max_subcompactions(1);
+
+        //db_opts.set_level_compaction_dynamic_level_bytes(false);
+        db_opts
+    }
+
+    pub fn txn_db_options(&self) -> TransactionDBOptions {
+        let mut txn_db_opts = TransactionDBOptions::default();
+        txn_db_opts.set_default_lock_timeout(self.lock_timeout);
+        txn_db_opts.set_txn_lock_timeout(self.lock_timeout);
+        txn_db_opts.set_num_stripes(32);
+        txn_db_opts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn atom(a: &str) -> Opt {
+        Opt::Atom(a.to_string())
+    }
+
+    fn cfs(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn db_options_parse() {
+        let opts = Opt::Map(vec![
+            (atom("max_open_files"), Opt::Int(100)),
+            (atom("bloom_bits"), Opt::Float(12.0)),
+            (atom("compression"), atom("lz4")),
+            (atom("cf"), Opt::Map(vec![
+                (Opt::Str("tx".to_string()), Opt::Map(vec![(atom("prefix_extractor"), atom("nil"))])),
+            ])),
+        ]);
+        let t = DbTuning::from_opt(&opts, &cfs(&["default", "tx"])).unwrap();
+        assert_eq!(t.max_open_files, 100);
+        assert_eq!(t.cf_defaults.bloom_bits, 12.0);
+        assert!(t.cf_defaults.compression_per_level.iter().all(|c| matches!(c, DBCompressionType::Lz4)));
+        // Built-in prefix extractor for tx is overridden, untouched families keep it
+        assert_eq!(t.cf_tuning("tx").prefix_extractor, None);
+        assert_eq!(t.cf_tuning("tx_filter").prefix_extractor, Some(16));
+    }
+
+    #[test]
+    fn db_options_accept_int_for_float() {
+        let opts = Opt::Map(vec![(atom("bloom_bits"), Opt::Int(10))]);
+        assert_eq!(DbTuning::from_opt(&opts, &[]).unwrap().cf_defaults.bloom_bits, 10.0);
+    }
+
+    #[test]
+    fn db_options_reject_unknown_keys() {
+        let opts = Opt::Map(vec![(atom("max_open_filez"), Opt::Int(100))]);
+        assert_eq!(DbTuning::from_opt(&opts, &[]).unwrap_err(), OptError::Unknown("max_open_filez".to_string()));
+
+        let opts = Opt::Map(vec![(atom("cf"), Opt::Map(vec![
+            (Opt::Str("tx".to_string()), Opt::Map(vec![(atom("row_cache_size"), Opt::Int(1))])),
+        ]))]);
+        assert_eq!(DbTuning::from_opt(&opts, &cfs(&["tx"])).unwrap_err(), OptError::Unknown("row_cache_size".to_string()));
+
+        let opts = Opt::Map(vec![(Opt::Str("max_open_files".to_string()), Opt::Int(100))]);
+        assert_eq!(DbTuning::from_opt(&opts, &[]).unwrap_err(), OptError::Invalid("key"));
+    }
+
+    #[test]
+    fn db_options_reject_invalid_values() {
+        let opts = Opt::Map(vec![(atom("max_open_files"), atom("lots"))]);
+        assert_eq!(DbTuning::from_opt(&opts, &[]).unwrap_err(), OptError::Invalid("max_open_files"));
+
+        let opts = Opt::Map(vec![(atom("row_cache_size"), Opt::Int(-1))]);
+        assert_eq!(DbTuning::from_opt(&opts, &[]).unwrap_err(), OptError::Invalid("row_cache_size"));
+
+        let opts = Opt::Map(vec![(atom("compression_per_level"), Opt::List(vec![atom("zstd"), atom("brotli")]))]);
+        assert_eq!(DbTuning::from_opt(&opts, &[]).unwrap_err(), OptError::Invalid("compression_per_level"));
+
+        assert_eq!(DbTuning::from_opt(&Opt::List(vec![]), &[]).unwrap_err(), OptError::Invalid("options"));
+    }
+
+    #[test]
+    fn db_options_reject_unknown_cf() {
+        let opts = Opt::Map(vec![(atom("cf"), Opt::Map(vec![
+            (Opt::Str("contractstat".to_string()), Opt::Map(vec![])),
+        ]))]);
+        assert_eq!(DbTuning::from_opt(&opts, &cfs(&["default", "contractstate"])).unwrap_err(),
+            OptError::UnknownCf("contractstat".to_string()));
+    }
+}
@@ -0,0 +1,107 @@
+use rustler::Error;
+use rust_rocksdb::Range;
+
+use crate::prefix_successor;
+use crate::to_nif_rdb_err;
+use crate::{TransactionDB, MultiThreaded, ReadOptions, AsColumnFamilyRef};
+use rust_rocksdb::ReadTier;
+
+pub struct ApproxUsage {
+    pub sst_size: u64,
+    pub memtable_size: u64,
+}
+
+#[derive(Default)]
+pub struct ExactUsage {
+    pub keys: u64,
+    pub key_bytes: u64,
+    pub value_bytes: u64,
+}
+
+// Upper end of a prefix range. A prefix with no successor (empty or all 0xFF) runs
+// to the end of the keyspace, which for size estimates is just past the last key.
+fn range_end(db: &TransactionDB<MultiThreaded>, cf: &impl AsColumnFamilyRef, prefix: &[u8]) -> Vec<u8> {
+    if let Some(end) = prefix_successor(prefix) {
+        return end
+    }
+    let mut it = db.raw_iterator_cf(cf);
+    it.seek_to_last();
+    match it.key() {
+        Some(last) if last >= prefix => [last, &[0u8][..]].concat(),
+        _ => prefix.to_vec(),
+    }
+}
+
+// Iterator over [prefix, prefix successor) at one snapshot, no block cache pollution
+fn prefix_read_opts(snap: &crate::DbSnapshot, prefix: &[u8]) -> ReadOptions {
+    let mut ro = ReadOptions::default();
+    ro.set_total_order_seek(true);
+    ro.set_fill_cache(false);
+    ro.set_snapshot(snap);
+    ro.set_iterate_lower_bound(prefix.to_vec());
+    if let Some(upper) = prefix_successor(prefix) {
+        ro.set_iterate_upper_bound(upper);
+    }
+    ro
+}
+
+// SST sizes are estimated from file metadata only. The binding's approximate sizes
+// cannot include memtables for a TransactionDB (the C call with include_memtables
+// wants the base rocksdb_t, which rust-rocksdb keeps private), so unflushed bytes per
+// prefix are summed with a memtable only iterator instead. That only touches memory,
+// bounded by the CF's write buffers.
+pub fn approximate_cf(
+    db: &TransactionDB<MultiThreaded>,
+    cf: &impl AsColumnFamilyRef,
+    prefixes: &[Vec<u8>],
+) -> Result<Vec<ApproxUsage>, Error> {
+    let ends: Vec<Vec<u8>> = prefixes.iter().map(|p| range_end(db, cf, p)).collect();
+    let ranges: Vec<Range> = prefixes.iter().zip(ends.iter())
+        .map(|(start, end)| Range::new(start, end))
+        .collect();
+    let sst_sizes = db.get_approximate_sizes_cf(cf, &ranges);
+
+    let snap = db.snapshot();
+    let mut out = Vec::with_capacity(prefixes.len());
+    for (prefix, sst_size) in prefixes.iter().zip(sst_sizes) {
+        let mut ro = prefix_read_opts(&snap, prefix);
+        ro.set_read_tier(ReadTier::Memtable);
+
+        let mut memtable_size = 0u64;
+        let mut it = db.raw_iterator_cf_opt(cf, ro);
+        it.seek_to_first();
+        while let Some((k, v)) = it.item() {
+            memtable_size += (k.len() + v.len()) as u64;
+            it.next();
+        }
+        it.status().map_err(to_nif_rdb_err)?;
+        out.push(ApproxUsage { sst_size, memtable_size });
+    }
+    Ok(out)
+}
+
+// Streams every key under each prefix as of one snapshot. Sizes are the logical
+// key and value lengths, which is what COST_PER_BYTE_STATE charges for.
+pub fn exact_cf(
+    db: &TransactionDB<MultiThreaded>,
+    cf: &impl AsColumnFamilyRef,
+    prefixes: &[Vec<u8>],
+) -> Result<Vec<ExactUsage>, Error> {
+    let snap = db.snapshot();
+    let mut out = Vec::with_capacity(prefixes.len());
+    for prefix in prefixes {
+        let ro = prefix_read_opts(&snap, prefix);
+        let mut usage = ExactUsage::default();
+        let mut it = db.raw_iterator_cf_opt(cf, ro);
+        it.seek_to_first();
+        while let Some((k, v)) = it.item() {
+            usage.keys += 1;
+            usage.key_bytes += k.len() as u64;
+            usage.value_bytes += v.len() as u64;
+            it.next();
+        }
+        it.status().map_err(to_nif_rdb_err)?;
+        out.push(usage);
+    }
+    Ok(out)
+}
@@ -0,0 +1,353 @@
+use rustler::{Encoder, Env, Error, Term};
+
+use crate::atoms;
+use crate::consensus::bintree::{get_bit_be, lcp_be, node_hash, sha256, Hash, Hubt, NodeKey, Op, ZERO_HASH};
+use crate::consensus::bintree_rdb::{deserialize_key, serialize_key};
+use crate::consensus::consensus_kv::contractstate_namespace;
+use crate::{to_bin, to_nif_rdb_err};
+use crate::{DBAccess, DBRawIteratorWithThreadMode, ReadOptions};
+
+// Only the first mismatches are listed, mismatch_count covers all of them
+const MAX_REPORTED: usize = 1000;
+// Pending inserts are applied to a namespace's tree every this many keys
+const REBUILD_CHUNK: usize = 65536;
+// Bits of the path taken by the namespace hash
+const NS_BITS: u16 = 64;
+
+pub struct CfCheck {
+    pub name: String,
+    pub keys: u64,
+    pub status: Result<(), Error>,
+}
+
+#[derive(Clone, Copy)]
+pub enum NodeIssue {
+    // Node the recomputed tree has and contractstate_tree does not
+    Missing,
+    // Node in contractstate_tree the recomputed tree does not have
+    Extra,
+    HashMismatch,
+    // Stored entry that is not a 34 byte key with a 32 byte hash
+    BadEntry,
+}
+
+pub struct NodeMismatch {
+    pub path: Vec<u8>,
+    pub len: Option<u16>,
+    pub issue: NodeIssue,
+}
+
+pub struct TreeCheck {
+    pub leaves: u64,
+    pub computed_root: Hash,
+    pub stored_root: Hash,
+    pub mismatch_count: u64,
+    pub mismatches: Vec<NodeMismatch>,
+}
+
+impl TreeCheck {
+    fn record(&mut self, path: &[u8], len: Option<u16>, issue: NodeIssue) {
+        self.mismatch_count += 1;
+        if self.mismatches.len() < MAX_REPORTED {
+            self.mismatches.push(NodeMismatch { path: path.to_vec(), len, issue });
+        }
+    }
+}
+
+// Full scan options: every block read gets its checksum verified and nothing
+// pollutes the block cache
+pub fn read_opts() -> ReadOptions {
+    let mut ro = ReadOptions::default();
+    ro.set_verify_checksums(true);
+    ro.set_fill_cache(false);
+    ro.set_total_order_seek(true);
+    ro
+}
+
+// The C API has no VerifyChecksum, reading the whole CF with verify_checksums
+// loads and checks every live data block instead. Corruption ends the scan.
+pub fn check_cf<D: DBAccess>(name: &str, mut it: DBRawIteratorWithThreadMode<'_, D>) -> CfCheck {
+    it.seek_to_first();
+    let mut keys = 0u64;
+    while it.valid() {
+        keys += 1;
+        it.next();
+    }
+    CfCheck { name: name.to_string(), keys, status: it.status().map_err(to_nif_rdb_err) }
+}
+
+// Recomputes the contractstate bintree with the same namespacing and leaf/node
+// hashing the applier uses and diffs it node by node against contractstate_tree.
+// Both iterators should read from the same snapshot.
+//
+// Every leaf of a namespace sits under the same 64 bit path prefix and, since the
+// namespace is a prefix of the key, its keys come out of contractstate in one run.
+// So each namespace is rebuilt on its own, compared against the stored nodes under
+// its prefix and dropped, only its subtree root is kept. The nodes above the
+// namespaces are then computed from those roots. Keys without a namespace are not
+// contiguous, they share the all-zero prefix and are checked last.
+pub fn check_tree<D: DBAccess>(
+    mut state: DBRawIteratorWithThreadMode<'_, D>,
+    mut tree: DBRawIteratorWithThreadMode<'_, D>,
+) -> Result<TreeCheck, Error> {
+    let mut check = TreeCheck {
+        leaves: 0,
+        computed_root: ZERO_HASH,
+        stored_root: ZERO_HASH,
+        mismatch_count: 0,
+        mismatches: Vec::new(),
+    };
+    let mut roots: Vec<(NodeKey, Hash)> = Vec::new();
+    let mut group: Option<(Vec<u8>, Hubt, Vec<Op>)> = None;
+    let mut unspaced = (Hubt::new(), Vec::new());
+
+    state.seek_to_first();
+    while let Some((k, v)) = state.item() {
+        match contractstate_namespace(k) {
+            None => push_op(&mut unspaced.0, &mut unspaced.1, Op::Insert(None, k.to_vec(), v.to_vec())),
+            Some(ns) => {
+                if group.as_ref().is_some_and(|(cur, _, _)| *cur != ns) {
+                    let (cur, hubt, ops) = group.take().unwrap();
+                    roots.push(check_subtree(&mut tree, &mut check, &ns_prefix(Some(&cur)), hubt, ops)?);
+                }
+                let (_, hubt, ops) = group.get_or_insert_with(|| (ns.clone(), Hubt::new(), Vec::new()));
+                push_op(hubt, ops, Op::Insert(Some(ns), k.to_vec(), v.to_vec()));
+            }
+        }
+        state.next();
+    }
+    state.status().map_err(to_nif_rdb_err)?;
+    if let Some((cur, hubt, ops)) = group.take() {
+        roots.push(check_subtree(&mut tree, &mut check, &ns_prefix(Some(&cur)), hubt, ops)?);
+    }
+    let (hubt, ops) = unspaced;
+    roots.push(check_subtree(&mut tree, &mut check, &ns_prefix(None), hubt, ops)?);
+    roots.retain(|(_, hash)| *hash != ZERO_HASH);
+    roots.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+    let mut top = Vec::new();
+    if !roots.is_empty() {
+        check.computed_root = combine_roots(&roots, &mut top);
+    }
+    top.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    let mut top = top.into_iter().peekable();
+
+    // Second pass over the whole tree for what the namespace passes do not see:
+    // malformed entries, the nodes above the namespaces and nodes under a prefix
+    // no namespace in contractstate maps to
+    let mut first: Option<(NodeKey, Hash)> = None;
+    let mut last: Option<NodeKey> = None;
+    tree.seek_to_first();
+    while let Some((k, v)) = tree.item() {
+        let hash: Hash = match (k.len(), v.try_into()) {
+            (34, Ok(hash)) => hash,
+            _ => {
+                check.record(k, None, NodeIssue::BadEntry);
+                tree.next();
+                continue;
+            }
+        };
+        let key = deserialize_key(k);
+        first.get_or_insert((key, hash));
+        last = Some(key);
+
+        if key.len < NS_BITS {
+            while let Some((want, _)) = top.next_if(|(want, _)| *want < key) {
+                check.record(&want.path, Some(want.len), NodeIssue::Missing);
+            }
+            match top.next_if(|(want, _)| *want == key) {
+                Some((_, want_hash)) if want_hash != hash => check.record(&key.path, Some(key.len), NodeIssue::HashMismatch),
+                Some(_) => {}
+                None => check.record(&key.path, Some(key.len), NodeIssue::Extra),
+            }
+        } else if roots.binary_search_by(|(root, _)| root.path[..8].cmp(&key.path[..8])).is_err() {
+            check.record(&key.path, Some(key.len), NodeIssue::Extra);
+        }
+        tree.next();
+    }
+    tree.status().map_err(to_nif_rdb_err)?;
+    for (want, _) in top {
+        check.record(&want.path, Some(want.len), NodeIssue::Missing);
+    }
+
+    // Same lookup RocksHubt::root does
+    if let (Some((first_key, first_hash)), Some(last_key)) = (first, last) {
+        if first_key.path == last_key.path {
+            check.stored_root = first_hash;
+        } else {
+            let (path, len) = lcp_be(&first_key.path, &last_key.path);
+            let top = serialize_key(&NodeKey { path, len });
+            tree.seek(&top);
+            if let Some((k, v)) = tree.item() {
+                if k == top.as_slice() {
+                    check.stored_root = v.try_into().unwrap_or(ZERO_HASH);
+                }
+            }
+        }
+    }
+    Ok(check)
+}
+
+fn push_op(hubt: &mut Hubt, ops: &mut Vec<Op>, op: Op) {
+    ops.push(op);
+    if ops.len() >= REBUILD_CHUNK {
+        hubt.batch_update(std::mem::take(ops));
+    }
+}
+
+// First 8 bytes of every path in the namespace, see compute_namespace_path
+fn ns_prefix(ns: Option<&[u8]>) -> [u8; 8] {
+    let mut prefix = [0u8; 8];
+    if let Some(ns) = ns {
+        prefix.copy_from_slice(&sha256(ns)[0..8]);
+    }
+    prefix
+}
+
+// Diffs one namespace's rebuilt subtree against the stored nodes under its prefix
+// and returns the subtree root, ZERO_HASH when the namespace has no leaves.
+// Nodes above the namespaces and malformed entries are left to the full pass.
+fn check_subtree<D: DBAccess>(
+    tree: &mut DBRawIteratorWithThreadMode<'_, D>,
+    check: &mut TreeCheck,
+    prefix: &[u8; 8],
+    mut hubt: Hubt,
+    ops: Vec<Op>,
+) -> Result<(NodeKey, Hash), Error> {
+    hubt.batch_update(ops);
+    check.leaves += hubt.leaves.len() as u64;
+
+    let mut root_key = NodeKey { path: [0u8; 32], len: 256 };
+    root_key.path[..8].copy_from_slice(prefix);
+    if let (Some((first, _)), Some((last, _))) = (hubt.leaves.first_key_value(), hubt.leaves.last_key_value()) {
+        let (path, len) = lcp_be(first, last);
+        root_key = NodeKey { path, len };
+    }
+    let root = (root_key, hubt.root());
+
+    // Stored keys are path ++ len (BE), so byte order matches NodeKey order
+    let mut expected: Vec<(NodeKey, Hash)> = hubt.leaves.iter()
+        .map(|(path, hash)| (NodeKey { path: *path, len: 256 }, *hash))
+        .chain(hubt.internals.iter().map(|(key, hash)| (*key, *hash)))
+        .collect();
+    expected.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    drop(hubt);
+    let mut expected = expected.into_iter().peekable();
+
+    tree.seek(prefix);
+    while let Some((k, v)) = tree.item() {
+        if !k.starts_with(prefix) {
+            break;
+        }
+        let hash: Hash = match (k.len(), v.try_into()) {
+            (34, Ok(hash)) => hash,
+            _ => {
+                tree.next();
+                continue;
+            }
+        };
+        let key = deserialize_key(k);
+        if key.len < NS_BITS {
+            tree.next();
+            continue;
+        }
+
+        while let Some((want, _)) = expected.next_if(|(want, _)| *want < key) {
+            check.record(&want.path, Some(want.len), NodeIssue::Missing);
+        }
+        match expected.next_if(|(want, _)| *want == key) {
+            Some((_, want_hash)) if want_hash != hash => check.record(&key.path, Some(key.len), NodeIssue::HashMismatch),
+            Some(_) => {}
+            None => check.record(&key.path, Some(key.len), NodeIssue::Extra),
+        }
+        tree.next();
+    }
+    tree.status().map_err(to_nif_rdb_err)?;
+    for (want, _) in expected {
+        check.record(&want.path, Some(want.len), NodeIssue::Missing);
+    }
+    Ok(root)
+}
+
+// Builds the nodes joining the namespace subtrees, roots sorted by path with no
+// root inside another. Pushes every joining node to out and returns the top hash.
+fn combine_roots(roots: &[(NodeKey, Hash)], out: &mut Vec<(NodeKey, Hash)>) -> Hash {
+    if let [(_, hash)] = roots {
+        return *hash;
+    }
+    let (path, len) = lcp_be(&roots[0].0.path, &roots[roots.len() - 1].0.path);
+    let split = roots.partition_point(|(key, _)| get_bit_be(&key.path, len) == 0);
+    let left = combine_roots(&roots[..split], out);
+    let right = combine_roots(&roots[split..], out);
+    let hash = node_hash(&path, len, &left, &right);
+    out.push((NodeKey { path, len }, hash));
+    hash
+}
+
+fn status_term<'a>(env: Env<'a>, status: &Result<(), Error>) -> Term<'a> {
+    match status {
+        Ok(()) => atoms::ok().encode(env),
+        Err(Error::Term(t)) => (atoms::error(), t.encode(env)).encode(env),
+        Err(_) => (atoms::error(), atoms::unknown()).encode(env),
+    }
+}
+
+fn issue_atom(issue: NodeIssue) -> rustler::Atom {
+    match issue {
+        NodeIssue::Missing => atoms::missing(),
+        NodeIssue::Extra => atoms::extra(),
+        NodeIssue::HashMismatch => atoms::hash_mismatch(),
+        NodeIssue::BadEntry => atoms::bad_entry(),
+    }
+}
+
+fn tree_term<'a>(env: Env<'a>, tree: &Result<TreeCheck, Error>) -> Term<'a> {
+    let check = match tree {
+        Ok(check) => check,
+        Err(Error::Term(t)) => return (atoms::error(), t.encode(env)).encode(env),
+        Err(_) => return (atoms::error(), atoms::unknown()).encode(env),
+    };
+    let mismatches: Vec<Term<'a>> = check.mismatches.iter().map(|m| {
+        let mut map = Term::map_new(env);
+        map = map.map_put(atoms::path(), to_bin(env, &m.path)).ok().unwrap();
+        map = map.map_put(atoms::len(), m.len).ok().unwrap();
+        map = map.map_put(atoms::issue(), issue_atom(m.issue)).ok().unwrap();
+        map
+    }).collect();
+
+    let mut map = Term::map_new(env);
+    map = map.map_put(atoms::leaves(), check.leaves).ok().unwrap();
+    map = map.map_put(atoms::computed_root(), to_bin(env, &check.computed_root)).ok().unwrap();
+    map = map.map_put(atoms::stored_root(), to_bin(env, &check.stored_root)).ok().unwrap();
+    map = map.map_put(atoms::root_match(), check.computed_root == check.stored_root).ok().unwrap();
+    map = map.map_put(atoms::mismatch_count(), check.mismatch_count).ok().unwrap();
+    map = map.map_put(atoms::mismatches(), mismatches).ok().unwrap();
+    map.encode(env)
+}
+
+// %{healthy, cfs: [%{cf, keys, status}], contractstate: tree report | {:error, _} | nil}
+pub fn report_to_term<'a>(env: Env<'a>, cfs: &[CfCheck], tree: Option<&Result<TreeCheck, Error>>) -> Term<'a> {
+    let cf_terms: Vec<Term<'a>> = cfs.iter().map(|cf| {
+        let mut map = Term::map_new(env);
+        map = map.map_put(atoms::cf(), &cf.name).ok().unwrap();
+        map = map.map_put(atoms::keys(), cf.keys).ok().unwrap();
+        map = map.map_put(atoms::status(), status_term(env, &cf.status)).ok().unwrap();
+        map
+    }).collect();
+
+    let cfs_ok = cfs.iter().all(|cf| cf.status.is_ok());
+    let tree_ok = match tree {
+        Some(Ok(check)) => check.mismatch_count == 0 && check.computed_root == check.stored_root,
+        Some(Err(_)) => false,
+        None => true,
+    };
+
+    let mut map = Term::map_new(env);
+    map = map.map_put(atoms::healthy(), cfs_ok && tree_ok).ok().unwrap();
+    map = map.map_put(atoms::cfs(), cf_terms).ok().unwrap();
+    map = map.map_put(atoms::contractstate(), match tree {
+        Some(tree) => tree_term(env, tree),
+        None => atoms::nil().encode(env),
+    }).ok().unwrap();
+    map.encode(env)
+}
@@ -1,31 +1,128 @@
 pub mod consensus;
 pub mod atoms;
+pub mod backup;
+pub mod db_options;
+pub mod disk_usage;
+pub mod integrity;
+pub mod maintenance;
 pub mod model;
+pub mod replay;
+pub mod sst;
+pub mod stats;
 pub mod tx_filter;
 
 use rustler::types::{Binary, OwnedBinary};
 use rustler::{
     Encoder, Error, Env, Term, NifResult, ResourceArc, Atom,
-    NifTaggedEnum
+    NifTaggedEnum, NifUnitEnum
 };
 
 pub use rust_rocksdb::{TransactionDB, MultiThreaded, TransactionDBOptions, Options,
     Transaction, TransactionOptions, WriteOptions, CompactOptions, BottommostLevelCompaction,
     DBRawIteratorWithThreadMode, BoundColumnFamily, ReadOptions, SliceTransform,
     Cache, LruCacheOptions, BlockBasedOptions, DBCompressionType, BlockBasedIndexType,
-    ColumnFamilyDescriptor, AsColumnFamilyRef};
+    ColumnFamilyDescriptor, AsColumnFamilyRef, WriteBatchWithTransaction,
+    SnapshotWithThreadMode, DBWithThreadMode, DBAccess};
 
+use std::collections::HashMap;
 use std::path::Path;
 use std::ptr::NonNull;
-use std::sync::{Mutex};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, Weak};
 
 use vecpak_ex;
 
 use crate::consensus::bic::protocol;
-use crate::consensus::{bintree, consensus_kv, consensus_muts};
+use crate::consensus::{bintree, consensus_kv, consensus_muts, consensus_trace};
 
 pub struct DbResource {
-    pub db: TransactionDB<MultiThreaded>
+    // None once close_db ran. NIFs hold the read side for the length of the call,
+    // so close waits for them and nothing can use the db after it is dropped
+    inner: RwLock<Option<TransactionDB<MultiThreaded>>>,
+    closed: AtomicBool,
+    // Handles borrowing the db, torn down by close_db before the db itself
+    children: Mutex<DbChildren>,
+    pub opts: Options,
+    pub caches: db_options::DbCaches,
+    pub tuning: db_options::DbTuning,
+    // Options each open CF was created with, for list_cf
+    pub cf_tuning: RwLock<HashMap<String, db_options::CfTuning>>,
+}
+unsafe impl Send for DbResource {}
+unsafe impl Sync for DbResource {}
+
+pub struct DbGuard<'a>(RwLockReadGuard<'a, Option<TransactionDB<MultiThreaded>>>);
+impl Deref for DbGuard<'_> {
+    type Target = TransactionDB<MultiThreaded>;
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref().expect("db guard on closed db")
+    }
+}
+
+#[derive(Default)]
+struct DbChildren {
+    its: Vec<Weak<Mutex<Option<IterInner>>>>,
+    snaps: Vec<Weak<RwLock<Option<DbSnapshot<'static>>>>>,
+    txs: Vec<Weak<Mutex<Option<Tx<'static>>>>>,
+}
+
+fn track<T>(list: &mut Vec<Weak<T>>, item: &Arc<T>) {
+    list.retain(|w| w.strong_count() > 0);
+    list.push(Arc::downgrade(item));
+}
+
+impl DbResource {
+    fn new(db: TransactionDB<MultiThreaded>, opts: Options, caches: db_options::DbCaches,
+        tuning: db_options::DbTuning, cf_tuning: HashMap<String, db_options::CfTuning>) -> Self
+    {
+        DbResource {
+            inner: RwLock::new(Some(db)),
+            closed: AtomicBool::new(false),
+            children: Mutex::new(DbChildren::default()),
+            opts, caches, tuning,
+            cf_tuning: RwLock::new(cf_tuning),
+        }
+    }
+
+    pub fn db(&self) -> Result<DbGuard<'_>, Error> {
+        let guard = self.inner.read().unwrap();
+        if guard.is_none() {
+            return Err(to_nif_err(atoms::db_closed()));
+        }
+        Ok(DbGuard(guard))
+    }
+
+    // Error for a handle whose inner value is gone, either released by the caller or by close_db.
+    // Must not touch `inner`, callers may be holding a child lock that close_db is waiting on
+    fn gone(&self) -> Error {
+        if self.closed.load(Ordering::Acquire) {
+            to_nif_err(atoms::db_closed())
+        } else {
+            to_nif_err(atoms::mutex_closed())
+        }
+    }
+
+    // Callers must hold a DbGuard so registration cannot race close_db
+    fn track_it(&self, it: &Arc<Mutex<Option<IterInner>>>) { track(&mut self.children.lock().unwrap().its, it); }
+    fn track_snap(&self, snap: &Arc<RwLock<Option<DbSnapshot<'static>>>>) { track(&mut self.children.lock().unwrap().snaps, snap); }
+    fn track_tx(&self, tx: &Arc<Mutex<Option<Tx<'static>>>>) { track(&mut self.children.lock().unwrap().txs, tx); }
+
+    // Iterators borrow transactions and snapshots, so they go first. Open transactions are rolled back
+    fn close_children(&self) {
+        let children = std::mem::take(&mut *self.children.lock().unwrap());
+        for it in children.its.iter().filter_map(Weak::upgrade) {
+            drop(it.lock().unwrap().take());
+        }
+        for snap in children.snaps.iter().filter_map(Weak::upgrade) {
+            drop(snap.write().unwrap().take());
+        }
+        for tx in children.txs.iter().filter_map(Weak::upgrade) {
+            if let Some(txn) = tx.lock().unwrap().take() {
+                let _ = txn.rollback();
+            }
+        }
+    }
 }
 
 pub struct CfResource {
@@ -41,10 +138,29 @@ impl AsColumnFamilyRef for CfResource {
     }
 }
 
+// Read-only or secondary instance, never takes write locks on the primary
+pub struct RoDbResource {
+    pub db: DBWithThreadMode<MultiThreaded>,
+    pub secondary: bool,
+    _caches: db_options::DbCaches,
+}
+
+pub struct RoCfResource {
+    db: ResourceArc<RoDbResource>,
+    handle: NonNull<rust_librocksdb_sys::rocksdb_column_family_handle_t>,
+}
+unsafe impl Send for RoCfResource {}
+unsafe impl Sync for RoCfResource {}
+impl AsColumnFamilyRef for RoCfResource {
+    fn inner(&self) -> *mut rust_librocksdb_sys::rocksdb_column_family_handle_t {
+        self.handle.as_ptr()
+    }
+}
+
 type Tx<'a> = Transaction<'a, TransactionDB<MultiThreaded>>;
 pub struct TxResource {
     db: ResourceArc<DbResource>,
-    tx: Mutex<Option<Tx<'static>>>,
+    tx: Arc<Mutex<Option<Tx<'static>>>>,
 }
 unsafe impl Send for TxResource {}
 unsafe impl Sync for TxResource {}
@@ -59,26 +175,77 @@ impl Drop for TxResource {
     }
 }
 
+impl TxResource {
+    // Caller must hold a DbGuard on db
+    fn new(db: ResourceArc<DbResource>, txn: Tx<'static>) -> ResourceArc<Self> {
+        let tx = Arc::new(Mutex::new(Some(txn)));
+        db.track_tx(&tx);
+        ResourceArc::new(TxResource { db, tx })
+    }
+}
+
+type DbSnapshot<'a> = SnapshotWithThreadMode<'a, TransactionDB<MultiThreaded>>;
+pub struct SnapResource {
+    // declared first so the snapshot is released before the db ref is dropped
+    snap: Arc<RwLock<Option<DbSnapshot<'static>>>>,
+    db: ResourceArc<DbResource>,
+}
+
+pub struct WbResource {
+    db: ResourceArc<DbResource>,
+    batch: Mutex<Option<WriteBatchWithTransaction<true>>>,
+}
+
 type DbIter<'a> = DBRawIteratorWithThreadMode<'a, TransactionDB<MultiThreaded>>;
 type TxIter<'a> = DBRawIteratorWithThreadMode<'a, Tx<'a>>;
 enum IterInner { Db(DbIter<'static>), Tx(TxIter<'static>) }
 pub struct ItResource {
+  // declared first so the iterator is destroyed before the tx/db it borrows
+  it: Arc<Mutex<Option<IterInner>>>,
   db: ResourceArc<DbResource>,
   cf: Option<ResourceArc<CfResource>>,
   tx: Option<ResourceArc<TxResource>>,
-  it: Mutex<IterInner>,
 }
 unsafe impl Send for ItResource {} unsafe impl Sync for ItResource {}
 
 impl ItResource {
+  fn wrap(db: &DbResource, it: IterInner, db_arc: ResourceArc<DbResource>,
+      tx: Option<ResourceArc<TxResource>>, cf: Option<ResourceArc<CfResource>>) -> ResourceArc<Self>
+  {
+      let it = Arc::new(Mutex::new(Some(it)));
+      db.track_it(&it);
+      ResourceArc::new(Self { it, db: db_arc, tx, cf })
+  }
+
+  // Iterators capture the snapshot sequence number on creation, releasing the
+  // snapshot afterwards does not affect them
+  pub fn with_snapshot(
+      snap: &SnapResource,
+      cf: Option<ResourceArc<CfResource>>,
+  ) -> Result<ResourceArc<Self>, Error> {
+      if let Some(cf) = &cf {
+          check_snap_cf(snap, cf)?;
+      }
+      let _db = snap.db.db()?;
+      let guard = snap.snap.read().unwrap();
+      let s = guard.as_ref().ok_or_else(|| snap.db.gone())?;
+      let real: DbIter<'_> = match &cf {
+          Some(cf) => s.raw_iterator_cf(&**cf),
+          None     => s.raw_iterator(),
+      };
+      let it = IterInner::Db(unsafe { std::mem::transmute::<DbIter<'_>, DbIter<'static>>(real) });
+      Ok(Self::wrap(&snap.db, it, snap.db.clone(), None, cf))
+  }
+
   pub fn new(
       db: ResourceArc<DbResource>,
       tx: Option<ResourceArc<TxResource>>,
       cf: Option<ResourceArc<CfResource>>,
-  ) -> ResourceArc<Self> {
+  ) -> Result<ResourceArc<Self>, Error> {
+      let db_ref = db.db()?;
       let it = if let Some(txr) = &tx {
-          let guard = txr.tx.lock().expect("tx mutex poisoned");
-          let txn = guard.as_ref().expect("transaction missing (mutex_closed)");
+          let guard = txr.tx.lock().unwrap();
+          let txn = guard.as_ref().ok_or_else(|| db.gone())?;
           let real: TxIter<'_> = match &cf {
               Some(cf) => txn.raw_iterator_cf(&**cf),
               None     => txn.raw_iterator(),
@@ -86,19 +253,25 @@ impl ItResource {
           IterInner::Tx(unsafe { std::mem::transmute::<TxIter<'_>, TxIter<'static>>(real) })
       } else {
           let real: DbIter<'_> = match &cf {
-              Some(cf) => db.db.raw_iterator_cf(&**cf),
-              None     => db.db.raw_iterator(),
+              Some(cf) => db_ref.raw_iterator_cf(&**cf),
+              None     => db_ref.raw_iterator(),
           };
           IterInner::Db(unsafe { std::mem::transmute::<DbIter<'_>, DbIter<'static>>(real) })
       };
 
-      ResourceArc::new(Self { db, tx, cf, it: Mutex::new(it) })
+      let res = Self::wrap(&db, it, db.clone(), tx, cf);
+      drop(db_ref);
+      Ok(res)
   }
 }
 
 macro_rules! with_it { ($s:expr, $it:ident => $body:expr) => {{
   let mut g = $s.it.lock().unwrap();
-  match &mut *g { IterInner::Db($it) => $body, IterInner::Tx($it) => $body }
+  match g.as_mut() {
+      Some(IterInner::Db($it)) => Ok($body),
+      Some(IterInner::Tx($it)) => Ok($body),
+      None => Err($s.db.gone()),
+  }
 }}}
 
 #[inline]
@@ -109,15 +282,15 @@ fn to_bin<'a>(env: Env<'a>, src: &[u8]) -> Binary<'a> {
 }
 
 impl ItResource {
-  pub fn seek(&self, k: &[u8])         { with_it!(self, it => it.seek(k)); }
-  pub fn seek_to_first(&self)          { with_it!(self, it => it.seek_to_first()); }
-  pub fn seek_to_last(&self)           { with_it!(self, it => it.seek_to_last()); }
-  pub fn next(&self)                   { with_it!(self, it => it.next()); }
-  pub fn prev(&self)                   { with_it!(self, it => it.prev()); }
-  pub fn valid(&self) -> bool          { with_it!(self, it => it.valid()) }
-  pub fn key<'a>(&self, env: Env<'a>) -> Option<Binary<'a>> { with_it!(self, it => it.key().map(|k| to_bin(env, k))) }
-  pub fn val<'a>(&self, env: Env<'a>) -> Option<Binary<'a>> { with_it!(self, it => it.value().map(|v| to_bin(env, v))) }
-  pub fn item<'a>(&self, env: Env<'a>) -> Option<(Binary<'a>, Binary<'a>)> {
+  pub fn seek(&self, k: &[u8]) -> Result<(), Error> { with_it!(self, it => it.seek(k)) }
+  pub fn seek_to_first(&self) -> Result<(), Error>  { with_it!(self, it => it.seek_to_first()) }
+  pub fn seek_to_last(&self) -> Result<(), Error>   { with_it!(self, it => it.seek_to_last()) }
+  pub fn next(&self) -> Result<(), Error>           { with_it!(self, it => it.next()) }
+  pub fn prev(&self) -> Result<(), Error>           { with_it!(self, it => it.prev()) }
+  pub fn valid(&self) -> Result<bool, Error>        { with_it!(self, it => it.valid()) }
+  pub fn key<'a>(&self, env: Env<'a>) -> Result<Option<Binary<'a>>, Error> { with_it!(self, it => it.key().map(|k| to_bin(env, k))) }
+  pub fn val<'a>(&self, env: Env<'a>) -> Result<Option<Binary<'a>>, Error> { with_it!(self, it => it.value().map(|v| to_bin(env, v))) }
+  pub fn item<'a>(&self, env: Env<'a>) -> Result<Option<(Binary<'a>, Binary<'a>)>, Error> {
     with_it!(self, it => match (it.key(), it.value()) {
         (Some(k), Some(v)) => Some((to_bin(env, k), to_bin(env, v))),
         _ => None,
@@ -131,11 +304,35 @@ fn on_load(env: Env, _: Term) -> bool {
     let _ = rustler::resource!(CfResource, env);
     let _ = rustler::resource!(TxResource, env);
     let _ = rustler::resource!(ItResource, env);
+    let _ = rustler::resource!(WbResource, env);
+    let _ = rustler::resource!(SnapResource, env);
+    let _ = rustler::resource!(RoDbResource, env);
+    let _ = rustler::resource!(RoCfResource, env);
     true
 }
 
+// {kind, message}, callers can match on the kind (e.g. retry a commit on :busy / :try_again)
 fn to_nif_rdb_err(err: rust_rocksdb::Error) -> Error {
-    Error::Term(Box::new(err.to_string()))
+    use rust_rocksdb::ErrorKind;
+    let kind = match err.kind() {
+        ErrorKind::NotFound => atoms::not_found(),
+        ErrorKind::Corruption => atoms::corruption(),
+        ErrorKind::NotSupported => atoms::not_supported(),
+        ErrorKind::InvalidArgument => atoms::invalid_argument(),
+        ErrorKind::IOError => atoms::io_error(),
+        ErrorKind::MergeInProgress => atoms::merge_in_progress(),
+        ErrorKind::Incomplete => atoms::incomplete(),
+        ErrorKind::ShutdownInProgress => atoms::shutdown_in_progress(),
+        ErrorKind::TimedOut => atoms::timed_out(),
+        ErrorKind::Aborted => atoms::aborted(),
+        ErrorKind::Busy => atoms::busy(),
+        ErrorKind::Expired => atoms::expired(),
+        ErrorKind::TryAgain => atoms::try_again(),
+        ErrorKind::CompactionTooLarge => atoms::compaction_too_large(),
+        ErrorKind::ColumnFamilyDropped => atoms::column_family_dropped(),
+        ErrorKind::Unknown => atoms::unknown(),
+    };
+    Error::Term(Box::new((kind, err.into_string())))
 }
 
 fn to_nif_err(err: Atom) -> Error {
@@ -143,139 +340,32 @@ fn to_nif_err(err: Atom) -> Error {
 }
 
 #[rustler::nif]
-fn open_transaction_db<'a>(env: Env<'a>, path: String, cf_names: Vec<String>) -> NifResult<Term<'a>> {
-    let mut lru_opts = LruCacheOptions::default();
-    lru_opts.set_capacity(4 * 1024 * 1024 * 1024); //4GB
-    lru_opts.set_num_shard_bits(8);
-    let row_cache = Cache::new_lru_cache_opts(&lru_opts);
-
-    let block_cache = Cache::new_lru_cache(4 * 1024 * 1024 * 1024); //4GB
-
-    let mut db_opts = Options::default();
-    db_opts.create_if_missing(true);
-    db_opts.create_missing_column_families(true);
-    db_opts.set_max_open_files(30000);
-    //more threads
-    db_opts.increase_parallelism(2);
-    db_opts.set_max_background_jobs(2);
-
-    db_opts.set_max_total_wal_size(2 * 1024 * 1024 * 1024); // 2GB
-    db_opts.set_target_file_size_base(8 * 1024 * 1024 * 1024);
-    //db_opts.set_target_file_size_base(2 * 1024 * 1024 * 1024);
-    //db_opts.set_target_file_size_multiplier(2);
-    db_opts.set_max_compaction_bytes(20 * 1024 * 1024 * 1024);
-
-    db_opts.enable_statistics();
-    db_opts.set_statistics_level(rust_rocksdb::statistics::StatsLevel::All);
-    db_opts.set_skip_stats_update_on_db_open(true);
-
-    // Bigger L0 flushes
-    db_opts.set_write_buffer_size(512 * 1024 * 1024);
-    db_opts.set_max_write_buffer_number(6);
-    db_opts.set_min_write_buffer_number_to_merge(2);
-    // L0 thresholds
-    db_opts.set_level_zero_file_num_compaction_trigger(8);
-    db_opts.set_level_zero_slowdown_writes_trigger(30);
-    db_opts.set_level_zero_stop_writes_trigger(100);
-    db_opts.set_max_subcompactions(1);
-
-    //db_opts.set_level_compaction_dynamic_level_bytes(false);
-
-    let mut txn_db_opts = TransactionDBOptions::default();
-    txn_db_opts.set_default_lock_timeout(3000);
-    txn_db_opts.set_txn_lock_timeout(3000);
-    txn_db_opts.set_num_stripes(32);
-
-    let mut cf_opts = Options::default();
-    cf_opts.set_row_cache(&row_cache);
-
-    let mut block_based_options = BlockBasedOptions::default();
-    block_based_options.set_block_cache(&block_cache);
-
-    block_based_options.set_bloom_filter(10.0, false);
-    block_based_options.set_index_type(BlockBasedIndexType::TwoLevelIndexSearch);
-    block_based_options.set_cache_index_and_filter_blocks(true);
-    block_based_options.set_cache_index_and_filter_blocks_with_high_priority(true);
-    block_based_options.set_pin_top_level_index_and_filter(true);
-    block_based_options.set_partition_filters(true);
-    block_based_options.set_pin_l0_filter_and_index_blocks_in_cache(false);
-    cf_opts.set_block_based_table_factory(&block_based_options);
-
-    let dict_bytes = 32 * 1024;
-    cf_opts.set_compression_per_level(&[
-        DBCompressionType::None,  // L0
-        DBCompressionType::None,  // L1
-        DBCompressionType::Zstd,  // L2
-        DBCompressionType::Zstd,  // L3
-        DBCompressionType::Zstd,  // L4
-        DBCompressionType::Zstd,  // L5
-        DBCompressionType::Zstd,  // L6
-    ]);
-
-    cf_opts.set_compression_type(DBCompressionType::Zstd);
-    cf_opts.set_compression_options(-14, 2, 0, dict_bytes);
-    cf_opts.set_zstd_max_train_bytes(100 * dict_bytes);
-/*
-    cf_opts.set_bottommost_compression_type(DBCompressionType::Zstd);
-    cf_opts.set_bottommost_compression_options(-14, 2, 0, dict_bytes, true);
-    cf_opts.set_bottommost_zstd_max_train_bytes(100 * dict_bytes, true);
-*/
+fn open_transaction_db<'a>(env: Env<'a>, path: String, cf_names: Vec<String>, opts: Term<'a>) -> NifResult<Term<'a>> {
+    let tuning = db_options::DbTuning::from_term(opts, &cf_names)?;
+    let caches = tuning.caches();
 
-    cf_opts.set_max_total_wal_size(2 * 1024 * 1024 * 1024); // 2GB
-    cf_opts.set_target_file_size_base(8 * 1024 * 1024 * 1024);
-    //cf_opts.set_target_file_size_base(2 * 1024 * 1024 * 1024);
-    //cf_opts.set_target_file_size_multiplier(2);
-    cf_opts.set_max_compaction_bytes(20 * 1024 * 1024 * 1024);
-
-    // Bigger L0 flushes
-    cf_opts.set_write_buffer_size(512 * 1024 * 1024);
-    cf_opts.set_max_write_buffer_number(6);
-    cf_opts.set_min_write_buffer_number_to_merge(2);
-    // L0 thresholds
-    cf_opts.set_level_zero_file_num_compaction_trigger(20);
-    cf_opts.set_level_zero_slowdown_writes_trigger(40);
-    cf_opts.set_level_zero_stop_writes_trigger(100);
-    cf_opts.set_max_subcompactions(1);
-    //cf_opts.set_periodic_compaction_seconds(0);
-
-    //cf_opts.set_level_compaction_dynamic_level_bytes(false);
+    let db_opts = tuning.db_options();
+    let txn_db_opts = tuning.txn_db_options();
 
+    let cf_tuning: HashMap<String, db_options::CfTuning> = cf_names
+        .iter()
+        .map(|name| (name.clone(), tuning.cf_tuning(name)))
+        .collect();
     let cf_descriptors: Vec<_> = cf_names
         .iter()
         .map(|name| {
-            let mut opts = cf_opts.clone();
-
-            if name == "tx" {
-                opts.set_prefix_extractor(SliceTransform::create_fixed_prefix(8));
-                opts.set_memtable_prefix_bloom_ratio(0.1);
-            }
-            if name == "tx_filter" {
-                opts.set_prefix_extractor(SliceTransform::create_fixed_prefix(16));
-                opts.set_memtable_prefix_bloom_ratio(0.1);
-            }
-
+            let opts = cf_tuning[name].options(&caches, tuning.max_total_wal_size);
             ColumnFamilyDescriptor::new(name.as_str(), opts)
         })
         .collect();
 
     match TransactionDB::open_cf_descriptors(&db_opts, &txn_db_opts, Path::new(&path), cf_descriptors) {
         Ok(db) => {
-            let resource = ResourceArc::new(DbResource { db });
+            let resource = ResourceArc::new(DbResource::new(db, db_opts, caches, tuning, cf_tuning));
 
             let mut out = Vec::with_capacity(cf_names.len());
             for name in cf_names {
-                let cf_arc = resource
-                    .db
-                    .cf_handle(&name)
-                    .ok_or_else(|| Error::Term(Box::new(format!("unknown column family: {}", name))))?;
-                let raw = cf_arc.inner();
-                let handle = NonNull::new(raw).ok_or_else(|| Error::Term(Box::new("null CF handle")))?;
-                let cf_res = ResourceArc::new(CfResource {
-                    db: resource.clone(),
-                    _name: name.clone(),
-                    handle,
-                });
-                out.push(cf_res);
+                out.push(cf_resource(&resource, name)?);
             }
 
             Ok((atoms::ok(), resource, out).encode(env))
@@ -284,30 +374,75 @@ fn open_transaction_db<'a>(env: Env<'a>, path: String, cf_names: Vec<String>) ->
     }
 }
 
+fn cf_resource(db: &ResourceArc<DbResource>, name: String) -> Result<ResourceArc<CfResource>, Error> {
+    let db_ref = db.db()?;
+    let cf_arc = db_ref
+        .cf_handle(&name)
+        .ok_or_else(|| Error::Term(Box::new(format!("unknown column family: {}", name))))?;
+    let raw = cf_arc.inner();
+    let handle = NonNull::new(raw).ok_or_else(|| Error::Term(Box::new("null CF handle")))?;
+    Ok(ResourceArc::new(CfResource {
+        db: db.clone(),
+        _name: name,
+        handle,
+    }))
+}
+
 #[rustler::nif(schedule = "DirtyCpu")]
-fn close_db(db: ResourceArc<DbResource>) -> NifResult<Atom> {
-    unsafe {
-        let ptr = &db.db as *const TransactionDB<MultiThreaded> as *mut  TransactionDB<MultiThreaded>;
+fn create_cf<'a>(env: Env<'a>, db: ResourceArc<DbResource>, name: String, opts: Term<'a>) -> NifResult<Term<'a>> {
+    let cf_tuning = db_options::CfTuning::from_term(&db.tuning.cf_defaults, &name, opts)?;
+    let cf_opts = cf_tuning.options(&db.caches, db.tuning.max_total_wal_size);
+    db.db()?.create_cf(name.as_str(), &cf_opts).map_err(to_nif_rdb_err)?;
+    db.cf_tuning.write().unwrap().insert(name.clone(), cf_tuning);
 
-        //(*ptr).cancel_all_background_work(true);
-        let _ = (*ptr).flush_wal(true);
+    Ok((atoms::ok(), cf_resource(&db, name)?).encode(env))
+}
 
-        std::ptr::drop_in_place(ptr);
+// Families on disk, with the options they were opened or created with (nil if not open)
+#[rustler::nif]
+fn list_cf<'a>(env: Env<'a>, db: ResourceArc<DbResource>) -> NifResult<Term<'a>> {
+    let names = TransactionDB::<MultiThreaded>::list_cf(&db.opts, db.db()?.path()).map_err(to_nif_rdb_err)?;
+    let cf_tuning = db.cf_tuning.read().unwrap();
+    let out: Vec<Term<'a>> = names.iter().map(|name| match cf_tuning.get(name) {
+        Some(t) => (name, t).encode(env),
+        None => (name, atoms::nil()).encode(env),
+    }).collect();
+    Ok((atoms::ok(), out).encode(env))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn close_db(db: ResourceArc<DbResource>) -> NifResult<Atom> {
+    // Waits for NIFs running against the db, every later call gets :db_closed
+    let mut guard = db.inner.write().unwrap();
+    if guard.is_none() {
+        return Err(to_nif_err(atoms::db_closed()));
     }
+    db.closed.store(true, Ordering::Release);
+
+    // Iterators, snapshots and txs release through the db, so they go while it is
+    // still in place and only then is it moved out and dropped
+    db.close_children();
+    let inner = guard.take().unwrap();
+    //inner.cancel_all_background_work(true);
+    let _ = inner.flush_wal(true);
+    drop(inner);
     Ok(atoms::ok())
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
 fn drop_cf<'a>(env: Env<'a>, db: ResourceArc<DbResource>, cf_name: String) -> NifResult<Term<'a>> {
-    match db.db.drop_cf(cf_name.as_str()) {
-        Ok(()) => Ok(atoms::ok().encode(env)),
+    match db.db()?.drop_cf(cf_name.as_str()) {
+        Ok(()) => {
+            db.cf_tuning.write().unwrap().remove(&cf_name);
+            Ok(atoms::ok().encode(env))
+        },
         Err(e) => Err(to_nif_rdb_err(e)),
     }
 }
 
 #[rustler::nif]
 fn property_value<'a>(env: Env<'a>, db: ResourceArc<DbResource>, key: String) -> NifResult<Term<'a>> {
-    match db.db.property_value(&key) {
+    match db.db()?.property_value(&key) {
         Ok(Some(value)) => {
             Ok((atoms::ok(), value).encode(env))
         },
@@ -318,7 +453,7 @@ fn property_value<'a>(env: Env<'a>, db: ResourceArc<DbResource>, key: String) ->
 
 #[rustler::nif]
 fn property_value_cf<'a>(env: Env<'a>, cf: ResourceArc<CfResource>, key: String) -> NifResult<Term<'a>> {
-    match cf.db.db.property_value_cf(&*cf, &key) {
+    match cf.db.db()?.property_value_cf(&*cf, &key) {
         Ok(Some(value)) => {
             Ok((atoms::ok(), value).encode(env))
         },
@@ -327,13 +462,71 @@ fn property_value_cf<'a>(env: Env<'a>, cf: ResourceArc<CfResource>, key: String)
     }
 }
 
+#[rustler::nif]
+fn statistics<'a>(env: Env<'a>, db: ResourceArc<DbResource>) -> NifResult<Term<'a>> {
+    Ok((atoms::ok(), stats::db_stats(env, &db)?).encode(env))
+}
+
+#[rustler::nif]
+fn statistics_cf<'a>(env: Env<'a>, cf: ResourceArc<CfResource>) -> NifResult<Term<'a>> {
+    Ok((atoms::ok(), stats::cf_stats(env, &cf)?).encode(env))
+}
+
+#[derive(NifUnitEnum)]
+pub enum UsageMode {
+    Approximate,
+    Exact,
+}
+
+// Per prefix breakdown of a CF. :approximate estimates SST sizes from metadata and
+// sums unflushed bytes from the memtables, :exact streams every key under the
+// prefixes from one snapshot
+#[rustler::nif(schedule = "DirtyCpu")]
+fn disk_usage_cf<'a>(env: Env<'a>, cf: ResourceArc<CfResource>, prefixes: Vec<Binary<'a>>, mode: UsageMode) -> NifResult<Term<'a>> {
+    let prefixes: Vec<Vec<u8>> = prefixes.iter().map(|p| p.as_slice().to_vec()).collect();
+    let db_ref = cf.db.db()?;
+
+    let mut map = Term::map_new(env);
+    let entries: Vec<Term<'a>> = match mode {
+        UsageMode::Approximate => {
+            // Whole CF, per prefix memtable bytes are in the entries
+            let unflushed = db_ref.property_value_cf(&*cf, "rocksdb.cur-size-all-mem-tables")
+                .map_err(to_nif_rdb_err)?
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(0);
+            map = map.map_put(atoms::memtable_size(), unflushed).ok().unwrap();
+            let usage = disk_usage::approximate_cf(&db_ref, &*cf, &prefixes)?;
+            prefixes.iter().zip(usage.iter()).map(|(prefix, u)| {
+                let mut entry = Term::map_new(env);
+                entry = entry.map_put(atoms::prefix(), to_binary2(env, prefix)).ok().unwrap();
+                entry = entry.map_put(atoms::sst_size(), u.sst_size).ok().unwrap();
+                entry = entry.map_put(atoms::memtable_size(), u.memtable_size).ok().unwrap();
+                entry
+            }).collect()
+        }
+        UsageMode::Exact => {
+            let usage = disk_usage::exact_cf(&db_ref, &*cf, &prefixes)?;
+            prefixes.iter().zip(usage.iter()).map(|(prefix, u)| {
+                let mut entry = Term::map_new(env);
+                entry = entry.map_put(atoms::prefix(), to_binary2(env, prefix)).ok().unwrap();
+                entry = entry.map_put(atoms::keys(), u.keys).ok().unwrap();
+                entry = entry.map_put(atoms::key_bytes(), u.key_bytes).ok().unwrap();
+                entry = entry.map_put(atoms::value_bytes(), u.value_bytes).ok().unwrap();
+                entry
+            }).collect()
+        }
+    };
+    map = map.map_put(atoms::prefixes(), entries).ok().unwrap();
+    Ok((atoms::ok(), map).encode(env))
+}
+
 #[rustler::nif(schedule = "DirtyCpu")]
 fn compact_range_cf_all<'a>(env: Env<'a>, cf: ResourceArc<CfResource>) -> NifResult<Term<'a>> {
     let mut copts = CompactOptions::default();
     copts.set_exclusive_manual_compaction(false);
     copts.set_bottommost_level_compaction(BottommostLevelCompaction::ForceOptimized);
 
-    cf.db.db
+    cf.db.db()?
         .compact_range_cf_opt(&*cf, None::<&[u8]>, None::<&[u8]>, &copts);
 
     Ok(atoms::ok().encode(env))
@@ -341,15 +534,43 @@ fn compact_range_cf_all<'a>(env: Env<'a>, cf: ResourceArc<CfResource>) -> NifRes
 
 #[rustler::nif(schedule = "DirtyCpu")]
 fn checkpoint(db: ResourceArc<DbResource>, path: String) -> NifResult<Atom> {
-    db.db
+    db.db()?
         .create_checkpoint(&path)
         .map(|_| atoms::ok())
         .map_err(to_nif_rdb_err)
 }
 
+#[rustler::nif(schedule = "DirtyCpu")]
+fn backup_create<'a>(env: Env<'a>, db: ResourceArc<DbResource>, backup_dir: String) -> NifResult<Term<'a>> {
+    let info = backup::create(&*db.db()?, &backup_dir)?;
+    Ok((atoms::ok(), backup::info_to_term(env, &info)).encode(env))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn backup_list<'a>(env: Env<'a>, backup_dir: String) -> NifResult<Term<'a>> {
+    let infos: Vec<Term<'a>> = backup::list(&backup_dir)?
+        .iter().map(|info| backup::info_to_term(env, info)).collect();
+    Ok((atoms::ok(), infos).encode(env))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn backup_verify(backup_dir: String, backup_id: u32) -> NifResult<Atom> {
+    backup::verify(&backup_dir, backup_id).map(|_| atoms::ok())
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn backup_purge(backup_dir: String, keep: usize) -> NifResult<Atom> {
+    backup::purge(&backup_dir, keep).map(|_| atoms::ok())
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn backup_restore(backup_dir: String, backup_id: u32, db_path: String) -> NifResult<Atom> {
+    backup::restore(&backup_dir, backup_id, &db_path).map(|_| atoms::ok())
+}
+
 #[rustler::nif(schedule = "DirtyCpu")]
 fn flush_wal(db: ResourceArc<DbResource>) -> NifResult<Atom> {
-    db.db
+    db.db()?
         .flush_wal(true)
         .map(|_| atoms::ok())
         .map_err(to_nif_rdb_err)
@@ -357,7 +578,7 @@ fn flush_wal(db: ResourceArc<DbResource>) -> NifResult<Atom> {
 
 #[rustler::nif(schedule = "DirtyCpu")]
 fn flush(db: ResourceArc<DbResource>) -> NifResult<Atom> {
-    db.db
+    db.db()?
         .flush()
         .map(|_| atoms::ok())
         .map_err(to_nif_rdb_err)
@@ -365,7 +586,7 @@ fn flush(db: ResourceArc<DbResource>) -> NifResult<Atom> {
 
 #[rustler::nif(schedule = "DirtyCpu")]
 fn flush_cf(cf: ResourceArc<CfResource>) -> NifResult<Atom> {
-    cf.db.db
+    cf.db.db()?
         .flush_cf(&*cf)
         .map(|_| atoms::ok())
         .map_err(to_nif_rdb_err)
@@ -373,7 +594,7 @@ fn flush_cf(cf: ResourceArc<CfResource>) -> NifResult<Atom> {
 
 #[rustler::nif]
 fn get<'a>(env: Env<'a>, db: ResourceArc<DbResource>, key: Binary) -> NifResult<Term<'a>> {
-    match db.db.get(key.as_slice()) {
+    match db.db()?.get(key.as_slice()) {
         Ok(Some(value)) => {
             let mut ob = OwnedBinary::new(value.len()).ok_or_else(|| Error::Term(Box::new("alloc failed")))?;
             ob.as_mut_slice().copy_from_slice(&value);
@@ -386,7 +607,7 @@ fn get<'a>(env: Env<'a>, db: ResourceArc<DbResource>, key: Binary) -> NifResult<
 
 #[rustler::nif]
 fn get_cf<'a>(env: Env<'a>, cf: ResourceArc<CfResource>, key: Binary) -> NifResult<Term<'a>> {
-    match cf.db.db.get_cf(&*cf, key.as_slice()) {
+    match cf.db.db()?.get_cf(&*cf, key.as_slice()) {
         Ok(Some(value)) => {
             let mut ob = OwnedBinary::new(value.len()).ok_or_else(|| Error::Term(Box::new("alloc failed")))?;
             ob.as_mut_slice().copy_from_slice(&value);
@@ -401,7 +622,7 @@ fn get_cf<'a>(env: Env<'a>, cf: ResourceArc<CfResource>, key: Binary) -> NifResu
 fn exists<'a>(env: Env<'a>, db: ResourceArc<DbResource>, key: Binary) -> NifResult<Term<'a>> {
     let mut ro = ReadOptions::default();
     ro.fill_cache(false);
-    match db.db.get_pinned_opt(key.as_slice(), &ro) {
+    match db.db()?.get_pinned_opt(key.as_slice(), &ro) {
         Ok(Some(_)) => Ok((atoms::ok(), true).encode(env)),
         Ok(None) => Ok((atoms::ok(), false).encode(env)),
         Err(e) => Err(to_nif_rdb_err(e)),
@@ -412,7 +633,7 @@ fn exists<'a>(env: Env<'a>, db: ResourceArc<DbResource>, key: Binary) -> NifResu
 fn exists_cf<'a>(env: Env<'a>, cf: ResourceArc<CfResource>, key: Binary) -> NifResult<Term<'a>> {
     let mut ro = ReadOptions::default();
     ro.fill_cache(false);
-    match cf.db.db.get_pinned_cf_opt(&*cf, key.as_slice(), &ro) {
+    match cf.db.db()?.get_pinned_cf_opt(&*cf, key.as_slice(), &ro) {
         Ok(Some(_)) => Ok((atoms::ok(), true).encode(env)),
         Ok(None) => Ok((atoms::ok(), false).encode(env)),
         Err(e) => Err(to_nif_rdb_err(e)),
@@ -421,7 +642,7 @@ fn exists_cf<'a>(env: Env<'a>, cf: ResourceArc<CfResource>, key: Binary) -> NifR
 
 #[rustler::nif]
 fn put(db: ResourceArc<DbResource>, key: Binary, value: Binary) -> NifResult<Atom> {
-    db.db
+    db.db()?
         .put(key.as_slice(), value.as_slice())
         .map(|_| atoms::ok())
         .map_err(to_nif_rdb_err)
@@ -429,7 +650,7 @@ fn put(db: ResourceArc<DbResource>, key: Binary, value: Binary) -> NifResult<Ato
 
 #[rustler::nif]
 fn put_cf(cf: ResourceArc<CfResource>, key: Binary, value: Binary) -> NifResult<Atom> {
-    cf.db.db
+    cf.db.db()?
         .put_cf(&*cf, key.as_slice(), value.as_slice())
         .map(|_| atoms::ok())
         .map_err(to_nif_rdb_err)
@@ -437,7 +658,7 @@ fn put_cf(cf: ResourceArc<CfResource>, key: Binary, value: Binary) -> NifResult<
 
 #[rustler::nif]
 fn delete(db: ResourceArc<DbResource>, key: Binary) -> NifResult<Atom> {
-    db.db
+    db.db()?
         .delete(key.as_slice())
         .map(|_| atoms::ok())
         .map_err(to_nif_rdb_err)
@@ -445,63 +666,154 @@ fn delete(db: ResourceArc<DbResource>, key: Binary) -> NifResult<Atom> {
 
 #[rustler::nif]
 fn delete_cf(cf: ResourceArc<CfResource>, key: Binary) -> NifResult<Atom> {
-    cf.db.db
+    cf.db.db()?
         .delete_cf(&*cf, key.as_slice())
         .map(|_| atoms::ok())
         .map_err(to_nif_rdb_err)
 }
 
-#[rustler::nif]
+// TransactionDB refuses range tombstones (DeleteRange, and Write of a batch holding one,
+// return NotSupported), so the range goes as point deletes a chunk at a time
+const DELETE_RANGE_CHUNK: usize = 10_000;
+
+fn delete_range(db: &TransactionDB<MultiThreaded>, cf: &impl AsColumnFamilyRef, start_key: &[u8], end_key: &[u8]) -> Result<(), rust_rocksdb::Error> {
+    let mut ro = ReadOptions::default();
+    ro.set_total_order_seek(true);
+    ro.set_fill_cache(false);
+    ro.set_iterate_upper_bound(end_key.to_vec());
+    let mut it = db.raw_iterator_cf_opt(cf, ro);
+    it.seek(start_key);
+
+    let mut batch = WriteBatchWithTransaction::<true>::default();
+    while let Some(key) = it.key() {
+        batch.delete_cf(cf, key);
+        if batch.len() >= DELETE_RANGE_CHUNK {
+            db.write(std::mem::take(&mut batch))?;
+        }
+        it.next();
+    }
+    it.status()?;
+    if !batch.is_empty() {
+        db.write(batch)?;
+    }
+    Ok(())
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
 fn delete_range_cf(cf: ResourceArc<CfResource>, start_key: Binary, end_key: Binary, compact: bool) -> NifResult<Atom> {
-    cf.db.db
-        .delete_range_cf(&*cf, start_key.as_slice(), end_key.as_slice())
-        .map(|_| atoms::ok())
-        .map_err(to_nif_rdb_err);
+    let db_ref = cf.db.db()?;
+    delete_range(&db_ref, &*cf, start_key.as_slice(), end_key.as_slice()).map_err(to_nif_rdb_err)?;
     if compact {
-        cf.db.db.compact_range_cf(&*cf, Option::<&[u8]>::None, Option::<&[u8]>::None);
+        db_ref.compact_range_cf(&*cf, Option::<&[u8]>::None, Option::<&[u8]>::None);
     }
     Ok(atoms::ok())
 }
 
+// Async maintenance, returns {:ok, ref} and later sends {:rdb_done, ref, result} to the caller
+#[rustler::nif]
+fn compact_range_cf_all_async<'a>(env: Env<'a>, cf: ResourceArc<CfResource>) -> Term<'a> {
+    maintenance::spawn(env, move |reply| {
+        let mut copts = CompactOptions::default();
+        copts.set_exclusive_manual_compaction(false);
+        copts.set_bottommost_level_compaction(BottommostLevelCompaction::ForceOptimized);
+
+        reply.progress(atoms::compacting());
+        cf.db.db()?.compact_range_cf_opt(&*cf, None::<&[u8]>, None::<&[u8]>, &copts);
+        Ok(atoms::ok())
+    })
+}
+
+#[rustler::nif]
+fn checkpoint_async<'a>(env: Env<'a>, db: ResourceArc<DbResource>, path: String) -> Term<'a> {
+    maintenance::spawn(env, move |reply| {
+        reply.progress(atoms::checkpointing());
+        db.db()?.create_checkpoint(&path).map_err(to_nif_rdb_err)?;
+        Ok(atoms::ok())
+    })
+}
+
+#[rustler::nif]
+fn flush_async<'a>(env: Env<'a>, db: ResourceArc<DbResource>) -> Term<'a> {
+    maintenance::spawn(env, move |reply| {
+        reply.progress(atoms::flushing());
+        db.db()?.flush().map_err(to_nif_rdb_err)?;
+        Ok(atoms::ok())
+    })
+}
+
+#[rustler::nif]
+fn delete_range_cf_async<'a>(env: Env<'a>, cf: ResourceArc<CfResource>, start_key: Binary, end_key: Binary, compact: bool) -> Term<'a> {
+    let start_key = start_key.as_slice().to_vec();
+    let end_key = end_key.as_slice().to_vec();
+    maintenance::spawn(env, move |reply| {
+        let db_ref = cf.db.db()?;
+        reply.progress(atoms::deleting());
+        delete_range(&db_ref, &*cf, &start_key, &end_key).map_err(to_nif_rdb_err)?;
+        if compact {
+            reply.progress(atoms::compacting());
+            db_ref.compact_range_cf(&*cf, Some(&start_key), Some(&end_key));
+        }
+        Ok(atoms::ok())
+    })
+}
+
 #[rustler::nif]
 fn iterator<'a>(env: Env<'a>, db: ResourceArc<DbResource>) -> NifResult<Term<'a>> {
-    let res = ItResource::new(db.clone(), None, None);
+    let res = ItResource::new(db.clone(), None, None)?;
     Ok((atoms::ok(), res).encode(env))
 }
 
 #[rustler::nif]
 fn iterator_cf<'a>(env: Env<'a>, cf: ResourceArc<CfResource>) -> NifResult<Term<'a>> {
-    let res = ItResource::new(cf.db.clone(), None, Some(cf.clone()));
+    let res = ItResource::new(cf.db.clone(), None, Some(cf.clone()))?;
     Ok((atoms::ok(), res).encode(env))
 }
 
 // Transaction
+#[derive(NifTaggedEnum)]
+pub enum TxOption {
+    LockTimeout(i64),
+    DeadlockDetect(bool),
+    SetSnapshot(bool),
+    Sync(bool),
+    DisableWal(bool),
+}
+
 #[rustler::nif]
-fn transaction<'a>(env: Env<'a>, db: ResourceArc<DbResource>) -> NifResult<Term<'a>> {
-    let wopts = WriteOptions::default();
-    let topts = TransactionOptions::default();
+fn transaction<'a>(env: Env<'a>, db: ResourceArc<DbResource>, opts: Vec<TxOption>) -> NifResult<Term<'a>> {
+    let mut wopts = WriteOptions::default();
+    let mut topts = TransactionOptions::default();
+    for opt in opts {
+        match opt {
+            TxOption::LockTimeout(ms) => topts.set_lock_timeout(ms),
+            TxOption::DeadlockDetect(v) => topts.set_deadlock_detect(v),
+            TxOption::SetSnapshot(v) => topts.set_snapshot(v),
+            TxOption::Sync(v) => wopts.set_sync(v),
+            TxOption::DisableWal(v) => wopts.disable_wal(v),
+        }
+    }
 
-    let tx_local: Tx<'_> = db.db.transaction_opt(&wopts, &topts);
+    let db_ref = db.db()?;
+    let tx_local: Tx<'_> = db_ref.transaction_opt(&wopts, &topts);
     let tx_static: Tx<'static> = unsafe { std::mem::transmute::<Tx<'_>, Tx<'static>>(tx_local) };
 
-    Ok((atoms::ok(), ResourceArc::new(TxResource {
-        db: db,
-        tx: Mutex::new(Some(tx_static)),
-    })).encode(env))
+    Ok((atoms::ok(), TxResource::new(db.clone(), tx_static)).encode(env))
 }
 
 #[rustler::nif]
 fn transaction_commit(tx: ResourceArc<TxResource>) -> NifResult<Atom> {
+    let _db = tx.db.db()?;
     let mut guard = tx.tx.lock().unwrap();
-    let txn = guard.take().ok_or_else(|| to_nif_err(atoms::mutex_closed()))?;
+    let txn = guard.take().ok_or_else(|| tx.db.gone())?;
     drop(guard); // don’t hold the lock while committing
     txn.commit().map(|_| atoms::ok()).map_err(to_nif_rdb_err)
 }
 
 #[rustler::nif]
 fn transaction_rollback(tx: ResourceArc<TxResource>) -> NifResult<Atom> {
+    let _db = tx.db.db()?;
     let mut guard = tx.tx.lock().unwrap();
-    let txn = guard.take().ok_or_else(|| to_nif_err(atoms::mutex_closed()))?;
+    let txn = guard.take().ok_or_else(|| tx.db.gone())?;
     drop(guard);
     txn.rollback().map(|_| atoms::ok()).map_err(to_nif_rdb_err)
 }
@@ -509,7 +821,7 @@ fn transaction_rollback(tx: ResourceArc<TxResource>) -> NifResult<Atom> {
 #[rustler::nif]
 fn transaction_set_savepoint(tx: ResourceArc<TxResource>) -> NifResult<Atom> {
     let guard = tx.tx.lock().unwrap();
-    let txn = guard.as_ref().ok_or_else(|| to_nif_err(atoms::mutex_closed()))?;
+    let txn = guard.as_ref().ok_or_else(|| tx.db.gone())?;
     txn.set_savepoint();
     Ok(atoms::ok())
 }
@@ -517,7 +829,7 @@ fn transaction_set_savepoint(tx: ResourceArc<TxResource>) -> NifResult<Atom> {
 #[rustler::nif]
 fn transaction_rollback_to_savepoint(tx: ResourceArc<TxResource>) -> NifResult<Atom> {
     let guard = tx.tx.lock().unwrap();
-    let txn = guard.as_ref().ok_or_else(|| to_nif_err(atoms::mutex_closed()))?;
+    let txn = guard.as_ref().ok_or_else(|| tx.db.gone())?;
     txn.rollback_to_savepoint()
         .map(|_| atoms::ok())
         .map_err(to_nif_rdb_err)
@@ -526,7 +838,7 @@ fn transaction_rollback_to_savepoint(tx: ResourceArc<TxResource>) -> NifResult<A
 #[rustler::nif]
 fn transaction_get<'a>(env: Env<'a>, tx: ResourceArc<TxResource>, key: Binary) -> NifResult<Term<'a>> {
     let guard = tx.tx.lock().unwrap();
-    let txn = guard.as_ref().ok_or_else(|| to_nif_err(atoms::mutex_closed()))?;
+    let txn = guard.as_ref().ok_or_else(|| tx.db.gone())?;
     match txn.get(key.as_slice()) {
         Ok(Some(value)) => {
             let mut ob = OwnedBinary::new(value.len()).ok_or_else(|| Error::Term(Box::new("alloc failed")))?;
@@ -538,10 +850,24 @@ fn transaction_get<'a>(env: Env<'a>, tx: ResourceArc<TxResource>, key: Binary) -
     }
 }
 
+// Locks the key until commit/rollback, concurrent writers to it block or time out with :timed_out / :busy
+#[rustler::nif]
+fn transaction_get_for_update_cf<'a>(env: Env<'a>, tx: ResourceArc<TxResource>, cf: ResourceArc<CfResource>,
+    key: Binary, exclusive: bool) -> NifResult<Term<'a>>
+{
+    let guard = tx.tx.lock().unwrap();
+    let txn = guard.as_ref().ok_or_else(|| tx.db.gone())?;
+    match txn.get_pinned_for_update_cf(&*cf, key.as_slice(), exclusive) {
+        Ok(Some(value)) => Ok((atoms::ok(), to_bin(env, &value)).encode(env)),
+        Ok(None) => Ok((atoms::ok(), atoms::nil()).encode(env)),
+        Err(e) => Err(to_nif_rdb_err(e)),
+    }
+}
+
 #[rustler::nif]
 fn transaction_get_cf<'a>(env: Env<'a>, tx: ResourceArc<TxResource>, cf: ResourceArc<CfResource>, key: Binary) -> NifResult<Term<'a>> {
     let guard = tx.tx.lock().unwrap();
-    let txn = guard.as_ref().ok_or_else(|| to_nif_err(atoms::mutex_closed()))?;
+    let txn = guard.as_ref().ok_or_else(|| tx.db.gone())?;
     match txn.get_cf(&*cf, key.as_slice()) {
         Ok(Some(value)) => {
             let mut ob = OwnedBinary::new(value.len()).ok_or_else(|| Error::Term(Box::new("alloc failed")))?;
@@ -556,7 +882,7 @@ fn transaction_get_cf<'a>(env: Env<'a>, tx: ResourceArc<TxResource>, cf: Resourc
 #[rustler::nif]
 fn transaction_exists<'a>(env: Env<'a>, tx: ResourceArc<TxResource>, key: Binary) -> NifResult<Term<'a>> {
     let guard = tx.tx.lock().unwrap();
-    let txn = guard.as_ref().ok_or_else(|| to_nif_err(atoms::mutex_closed()))?;
+    let txn = guard.as_ref().ok_or_else(|| tx.db.gone())?;
     let mut ro = ReadOptions::default();
     ro.fill_cache(false);
     let rustlol = match txn.get_pinned_opt(key.as_slice(), &ro) {
@@ -570,7 +896,7 @@ fn transaction_exists<'a>(env: Env<'a>, tx: ResourceArc<TxResource>, key: Binary
 #[rustler::nif]
 fn transaction_exists_cf<'a>(env: Env<'a>, tx: ResourceArc<TxResource>, cf: ResourceArc<CfResource>, key: Binary) -> NifResult<Term<'a>> {
     let guard = tx.tx.lock().unwrap();
-    let txn = guard.as_ref().ok_or_else(|| to_nif_err(atoms::mutex_closed()))?;
+    let txn = guard.as_ref().ok_or_else(|| tx.db.gone())?;
     let mut ro = ReadOptions::default();
     ro.fill_cache(false);
     let rustlol = match txn.get_pinned_cf_opt(&*cf, key.as_slice(), &ro) {
@@ -584,7 +910,7 @@ fn transaction_exists_cf<'a>(env: Env<'a>, tx: ResourceArc<TxResource>, cf: Reso
 #[rustler::nif]
 fn transaction_put(tx: ResourceArc<TxResource>, key: Binary, val: Binary) -> NifResult<Atom> {
     let guard = tx.tx.lock().unwrap();
-    let txn = guard.as_ref().ok_or_else(|| to_nif_err(atoms::mutex_closed()))?;
+    let txn = guard.as_ref().ok_or_else(|| tx.db.gone())?;
     txn.put(key.as_slice(), val.as_slice())
         .map(|_| atoms::ok())
         .map_err(to_nif_rdb_err)
@@ -593,7 +919,7 @@ fn transaction_put(tx: ResourceArc<TxResource>, key: Binary, val: Binary) -> Nif
 #[rustler::nif]
 fn transaction_put_cf(tx: ResourceArc<TxResource>, cf: ResourceArc<CfResource>, key: Binary, val: Binary) -> NifResult<Atom> {
     let guard = tx.tx.lock().unwrap();
-    let txn = guard.as_ref().ok_or_else(|| to_nif_err(atoms::mutex_closed()))?;
+    let txn = guard.as_ref().ok_or_else(|| tx.db.gone())?;
     txn.put_cf(&*cf, key.as_slice(), val.as_slice())
         .map(|_| atoms::ok())
         .map_err(to_nif_rdb_err)
@@ -602,7 +928,7 @@ fn transaction_put_cf(tx: ResourceArc<TxResource>, cf: ResourceArc<CfResource>,
 #[rustler::nif]
 fn transaction_delete(tx: ResourceArc<TxResource>, key: Binary) -> NifResult<Atom> {
     let guard = tx.tx.lock().unwrap();
-    let txn = guard.as_ref().ok_or_else(|| to_nif_err(atoms::mutex_closed()))?;
+    let txn = guard.as_ref().ok_or_else(|| tx.db.gone())?;
     txn.delete(key.as_slice())
         .map(|_| atoms::ok())
         .map_err(to_nif_rdb_err)
@@ -611,7 +937,7 @@ fn transaction_delete(tx: ResourceArc<TxResource>, key: Binary) -> NifResult<Ato
 #[rustler::nif]
 fn transaction_delete_cf(tx: ResourceArc<TxResource>, cf: ResourceArc<CfResource>, key: Binary) -> NifResult<Atom> {
     let guard = tx.tx.lock().unwrap();
-    let txn = guard.as_ref().ok_or_else(|| to_nif_err(atoms::mutex_closed()))?;
+    let txn = guard.as_ref().ok_or_else(|| tx.db.gone())?;
     txn.delete_cf(&*cf, key.as_slice())
         .map(|_| atoms::ok())
         .map_err(to_nif_rdb_err)
@@ -619,16 +945,471 @@ fn transaction_delete_cf(tx: ResourceArc<TxResource>, cf: ResourceArc<CfResource
 
 #[rustler::nif]
 fn transaction_iterator<'a>(env: Env<'a>, tx: ResourceArc<TxResource>) -> NifResult<Term<'a>> {
-    let res = ItResource::new(tx.db.clone(), Some(tx.clone()), None);
+    let res = ItResource::new(tx.db.clone(), Some(tx.clone()), None)?;
     Ok((atoms::ok(), res).encode(env))
 }
 
 #[rustler::nif]
 fn transaction_iterator_cf<'a>(env: Env<'a>, tx: ResourceArc<TxResource>, cf: ResourceArc<CfResource>) -> NifResult<Term<'a>> {
-    let res = ItResource::new(cf.db.clone(), Some(tx.clone()), Some(cf.clone()));
+    let res = ItResource::new(cf.db.clone(), Some(tx.clone()), Some(cf.clone()))?;
     Ok((atoms::ok(), res).encode(env))
 }
 
+#[rustler::nif(schedule = "DirtyCpu")]
+fn multi_get_cf<'a>(env: Env<'a>, cf: ResourceArc<CfResource>, keys: Vec<Binary>) -> NifResult<Term<'a>> {
+    let results = cf.db.db()?.multi_get_cf(keys.iter().map(|k| (&*cf, k.as_slice())));
+    let mut out = Vec::with_capacity(results.len());
+    for r in results {
+        match r {
+            Ok(Some(value)) => out.push(to_bin(env, &value).encode(env)),
+            Ok(None) => out.push(atoms::nil().encode(env)),
+            Err(e) => return Err(to_nif_rdb_err(e)),
+        }
+    }
+    Ok((atoms::ok(), out).encode(env))
+}
+
+#[derive(NifTaggedEnum)]
+pub enum ScanBound<'a> {
+    End(Binary<'a>),
+    Prefix(Binary<'a>),
+}
+
+#[derive(NifUnitEnum)]
+pub enum ScanDirection {
+    Forward,
+    Reverse,
+}
+
+// Smallest key greater than every key starting with prefix, None if prefix is all 0xFF
+fn prefix_successor(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut v = prefix.to_vec();
+    while let Some(last) = v.pop() {
+        if last != 0xFF {
+            v.push(last + 1);
+            return Some(v);
+        }
+    }
+    None
+}
+
+// Returns up to limit pairs in [start, end) or [max(start, prefix), prefix successor).
+// Forward prefix scans use prefix_same_as_start, so on a CF with a prefix extractor
+// the prefix must be at least as long as the extractor; use {:end, key} otherwise.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn scan_cf<'a>(env: Env<'a>, cf: ResourceArc<CfResource>, start: Binary<'a>, bound: ScanBound<'a>,
+    limit: u32, direction: ScanDirection) -> NifResult<Term<'a>>
+{
+    let (ro, lower) = scan_read_opts(start, bound, &direction);
+    let db_ref = cf.db.db()?;
+    let it = db_ref.raw_iterator_cf_opt(&*cf, ro);
+    scan_collect(env, it, &lower, limit, &direction)
+}
+
+fn scan_read_opts(start: Binary, bound: ScanBound, direction: &ScanDirection) -> (ReadOptions, Vec<u8>) {
+    let mut ro = ReadOptions::default();
+    let lower = match bound {
+        ScanBound::End(end) => {
+            ro.set_total_order_seek(true);
+            ro.set_iterate_upper_bound(end.as_slice());
+            start.as_slice().to_vec()
+        }
+        ScanBound::Prefix(prefix) => {
+            match direction {
+                ScanDirection::Forward => ro.set_prefix_same_as_start(true),
+                ScanDirection::Reverse => ro.set_total_order_seek(true),
+            }
+            if let Some(upper) = prefix_successor(prefix.as_slice()) {
+                ro.set_iterate_upper_bound(upper);
+            }
+            start.as_slice().max(prefix.as_slice()).to_vec()
+        }
+    };
+    ro.set_iterate_lower_bound(lower.clone());
+    (ro, lower)
+}
+
+fn scan_collect<'a, D: DBAccess>(env: Env<'a>, mut it: DBRawIteratorWithThreadMode<'_, D>, lower: &[u8],
+    limit: u32, direction: &ScanDirection) -> NifResult<Term<'a>>
+{
+    match direction {
+        ScanDirection::Forward => it.seek(lower),
+        ScanDirection::Reverse => it.seek_to_last(),
+    }
+
+    let mut out = Vec::with_capacity((limit as usize).min(1024));
+    while out.len() < limit as usize {
+        match it.item() {
+            Some((k, v)) => out.push((to_bin(env, k), to_bin(env, v))),
+            None => break,
+        }
+        match direction {
+            ScanDirection::Forward => it.next(),
+            ScanDirection::Reverse => it.prev(),
+        }
+    }
+    it.status().map_err(to_nif_rdb_err)?;
+
+    Ok((atoms::ok(), out).encode(env))
+}
+
+// SST export / ingest
+#[rustler::nif(schedule = "DirtyCpu")]
+fn sst_export_cf<'a>(env: Env<'a>, cf: ResourceArc<CfResource>, dir: String,
+    start: Option<Binary<'a>>, end: Option<Binary<'a>>, max_file_size: u64) -> NifResult<Term<'a>>
+{
+    let files = sst::export_cf(&*cf.db.db()?, &*cf, &dir,
+        start.as_ref().map(|b| b.as_slice()), end.as_ref().map(|b| b.as_slice()), max_file_size)?;
+
+    let total_keys: u64 = files.iter().map(|f| f.keys).sum();
+    let files_list: Vec<Term<'a>> = files.iter().map(|f| {
+        let mut map = Term::map_new(env);
+        map = map.map_put(atoms::path(), &f.path).ok().unwrap();
+        map = map.map_put(atoms::keys(), f.keys).ok().unwrap();
+        map = map.map_put(atoms::size(), f.size).ok().unwrap();
+        map = map.map_put(atoms::checksum(), to_binary2(env, &f.checksum)).ok().unwrap();
+        map = map.map_put(atoms::first_key(), to_binary2(env, &f.first_key)).ok().unwrap();
+        map = map.map_put(atoms::last_key(), to_binary2(env, &f.last_key)).ok().unwrap();
+        map
+    }).collect();
+
+    let mut manifest = Term::map_new(env);
+    manifest = manifest.map_put(atoms::cf(), &cf._name).ok().unwrap();
+    manifest = manifest.map_put(atoms::keys(), total_keys).ok().unwrap();
+    manifest = manifest.map_put(atoms::files(), files_list).ok().unwrap();
+    Ok((atoms::ok(), manifest).encode(env))
+}
+
+// files are plain paths or the file maps of an export manifest, whose checksums get verified
+#[rustler::nif(schedule = "DirtyCpu")]
+fn sst_ingest_cf<'a>(cf: ResourceArc<CfResource>, files: Vec<Term<'a>>, move_files: bool) -> NifResult<Atom> {
+    let mut entries = Vec::with_capacity(files.len());
+    for file in files {
+        if file.is_map() {
+            let path: String = file.map_get(atoms::path())?.decode()?;
+            let checksum = match file.map_get(atoms::checksum()) {
+                Ok(term) => Some(term.decode::<Binary>()?.as_slice().to_vec()),
+                Err(_) => None,
+            };
+            entries.push((path, checksum));
+        } else {
+            entries.push((file.decode::<String>()?, None));
+        }
+    }
+    sst::ingest_cf(&*cf.db.db()?, &*cf, &entries, move_files).map(|_| atoms::ok())
+}
+
+// Read-only / secondary instances
+fn open_ro<'a>(env: Env<'a>, path: String, secondary_path: Option<String>, cf_names: Vec<String>, opts: Term<'a>) -> NifResult<Term<'a>> {
+    let tuning = db_options::DbTuning::from_term(opts, &cf_names)?;
+    let caches = tuning.caches();
+
+    let mut db_opts = tuning.db_options();
+    let cf_descriptors: Vec<_> = cf_names
+        .iter()
+        .map(|name| {
+            let opts = tuning.cf_tuning(name).options(&caches, tuning.max_total_wal_size);
+            ColumnFamilyDescriptor::new(name.as_str(), opts)
+        })
+        .collect();
+
+    let db = match &secondary_path {
+        Some(secondary_path) => {
+            // Secondaries must keep every table file open to follow the primary
+            db_opts.set_max_open_files(-1);
+            DBWithThreadMode::<MultiThreaded>::open_cf_descriptors_as_secondary(
+                &db_opts, Path::new(&path), Path::new(secondary_path), cf_descriptors)
+        }
+        None => DBWithThreadMode::<MultiThreaded>::open_cf_descriptors_read_only(
+            &db_opts, Path::new(&path), cf_descriptors, false),
+    }.map_err(to_nif_rdb_err)?;
+
+    let resource = ResourceArc::new(RoDbResource { db, secondary: secondary_path.is_some(), _caches: caches });
+    let mut out = Vec::with_capacity(cf_names.len());
+    for name in cf_names {
+        let cf_arc = resource
+            .db
+            .cf_handle(&name)
+            .ok_or_else(|| Error::Term(Box::new(format!("unknown column family: {}", name))))?;
+        let handle = NonNull::new(cf_arc.inner()).ok_or_else(|| Error::Term(Box::new("null CF handle")))?;
+        out.push(ResourceArc::new(RoCfResource { db: resource.clone(), handle }));
+    }
+
+    Ok((atoms::ok(), resource, out).encode(env))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn open_read_only<'a>(env: Env<'a>, path: String, cf_names: Vec<String>, opts: Term<'a>) -> NifResult<Term<'a>> {
+    open_ro(env, path, None, cf_names, opts)
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn open_secondary<'a>(env: Env<'a>, path: String, secondary_path: String, cf_names: Vec<String>, opts: Term<'a>) -> NifResult<Term<'a>> {
+    open_ro(env, path, Some(secondary_path), cf_names, opts)
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn try_catch_up_with_primary(db: ResourceArc<RoDbResource>) -> NifResult<Atom> {
+    if !db.secondary {
+        return Err(to_nif_err(atoms::not_secondary()));
+    }
+    db.db
+        .try_catch_up_with_primary()
+        .map(|_| atoms::ok())
+        .map_err(to_nif_rdb_err)
+}
+
+#[rustler::nif]
+fn ro_get_cf<'a>(env: Env<'a>, cf: ResourceArc<RoCfResource>, key: Binary) -> NifResult<Term<'a>> {
+    match cf.db.db.get_pinned_cf(&*cf, key.as_slice()) {
+        Ok(Some(value)) => Ok((atoms::ok(), to_bin(env, &value)).encode(env)),
+        Ok(None) => Ok((atoms::ok(), atoms::nil()).encode(env)),
+        Err(e) => Err(to_nif_rdb_err(e)),
+    }
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn ro_multi_get_cf<'a>(env: Env<'a>, cf: ResourceArc<RoCfResource>, keys: Vec<Binary>) -> NifResult<Term<'a>> {
+    let results = cf.db.db.multi_get_cf(keys.iter().map(|k| (&*cf, k.as_slice())));
+    let mut out = Vec::with_capacity(results.len());
+    for r in results {
+        match r {
+            Ok(Some(value)) => out.push(to_bin(env, &value).encode(env)),
+            Ok(None) => out.push(atoms::nil().encode(env)),
+            Err(e) => return Err(to_nif_rdb_err(e)),
+        }
+    }
+    Ok((atoms::ok(), out).encode(env))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn ro_scan_cf<'a>(env: Env<'a>, cf: ResourceArc<RoCfResource>, start: Binary<'a>, bound: ScanBound<'a>,
+    limit: u32, direction: ScanDirection) -> NifResult<Term<'a>>
+{
+    let (ro, lower) = scan_read_opts(start, bound, &direction);
+    let it = cf.db.db.raw_iterator_cf_opt(&*cf, ro);
+    scan_collect(env, it, &lower, limit, &direction)
+}
+
+#[rustler::nif]
+fn ro_property_value_cf<'a>(env: Env<'a>, cf: ResourceArc<RoCfResource>, key: String) -> NifResult<Term<'a>> {
+    match cf.db.db.property_value_cf(&*cf, &key) {
+        Ok(Some(value)) => Ok((atoms::ok(), value).encode(env)),
+        Ok(None) => Ok((atoms::ok(), atoms::nil()).encode(env)),
+        Err(e) => Err(to_nif_rdb_err(e)),
+    }
+}
+
+// Integrity verification. Reads every CF in full with checksum verification, then
+// rebuilds the contractstate bintree and diffs it against contractstate_tree.
+// Takes as long as a full DB scan, meant for a quiet node or a read-only instance
+#[rustler::nif(schedule = "DirtyCpu")]
+fn verify_integrity<'a>(env: Env<'a>, db: ResourceArc<DbResource>) -> NifResult<Term<'a>> {
+    let db_ref = db.db()?;
+    let snap = db_ref.snapshot();
+    let read_opts = || {
+        let mut ro = integrity::read_opts();
+        ro.set_snapshot(&snap);
+        ro
+    };
+
+    let names = TransactionDB::<MultiThreaded>::list_cf(&db.opts, db_ref.path()).map_err(to_nif_rdb_err)?;
+    let mut cfs = Vec::with_capacity(names.len());
+    for name in &names {
+        if let Some(cf) = db_ref.cf_handle(name) {
+            cfs.push(integrity::check_cf(name, db_ref.raw_iterator_cf_opt(&cf, read_opts())));
+        }
+    }
+
+    let tree = match (db_ref.cf_handle("contractstate"), db_ref.cf_handle("contractstate_tree")) {
+        (Some(state), Some(tree)) => Some(integrity::check_tree(
+            db_ref.raw_iterator_cf_opt(&state, read_opts()),
+            db_ref.raw_iterator_cf_opt(&tree, read_opts()),
+        )),
+        _ => None,
+    };
+    Ok((atoms::ok(), integrity::report_to_term(env, &cfs, tree.as_ref())).encode(env))
+}
+
+// Same report for a read-only or secondary instance, so the check can run against
+// a stopped node's DB without opening it for writes
+#[rustler::nif(schedule = "DirtyCpu")]
+fn ro_verify_integrity<'a>(env: Env<'a>, db: ResourceArc<RoDbResource>) -> NifResult<Term<'a>> {
+    let names = DBWithThreadMode::<MultiThreaded>::list_cf(&Options::default(), db.db.path()).map_err(to_nif_rdb_err)?;
+    let mut cfs = Vec::with_capacity(names.len());
+    for name in &names {
+        if let Some(cf) = db.db.cf_handle(name) {
+            cfs.push(integrity::check_cf(name, db.db.raw_iterator_cf_opt(&cf, integrity::read_opts())));
+        }
+    }
+
+    let tree = match (db.db.cf_handle("contractstate"), db.db.cf_handle("contractstate_tree")) {
+        (Some(state), Some(tree)) => Some(integrity::check_tree(
+            db.db.raw_iterator_cf_opt(&state, integrity::read_opts()),
+            db.db.raw_iterator_cf_opt(&tree, integrity::read_opts()),
+        )),
+        _ => None,
+    };
+    Ok((atoms::ok(), integrity::report_to_term(env, &cfs, tree.as_ref())).encode(env))
+}
+
+// Snapshot
+#[rustler::nif]
+fn snapshot<'a>(env: Env<'a>, db: ResourceArc<DbResource>) -> NifResult<Term<'a>> {
+    let db_ref = db.db()?;
+    let snap_local: DbSnapshot<'_> = db_ref.snapshot();
+    let snap_static: DbSnapshot<'static> = unsafe { std::mem::transmute::<DbSnapshot<'_>, DbSnapshot<'static>>(snap_local) };
+
+    let snap = Arc::new(RwLock::new(Some(snap_static)));
+    db.track_snap(&snap);
+    Ok((atoms::ok(), ResourceArc::new(SnapResource { snap, db: db.clone() })).encode(env))
+}
+
+// A snapshot only reads the DB it was taken on
+fn check_snap_cf(snap: &SnapResource, cf: &CfResource) -> Result<(), Error> {
+    if std::ptr::eq::<DbResource>(&*snap.db, &*cf.db) { Ok(()) } else { Err(to_nif_err(atoms::cf_wrong_db())) }
+}
+
+#[rustler::nif]
+fn snapshot_release(snap: ResourceArc<SnapResource>) -> NifResult<Atom> {
+    let mut guard = snap.snap.write().unwrap();
+    guard.take().ok_or_else(|| snap.db.gone())?;
+    Ok(atoms::ok())
+}
+
+#[rustler::nif]
+fn snapshot_get<'a>(env: Env<'a>, snap: ResourceArc<SnapResource>, key: Binary) -> NifResult<Term<'a>> {
+    let guard = snap.snap.read().unwrap();
+    let s = guard.as_ref().ok_or_else(|| snap.db.gone())?;
+    match s.get(key.as_slice()) {
+        Ok(Some(value)) => Ok((atoms::ok(), to_bin(env, &value)).encode(env)),
+        Ok(None) => Ok((atoms::ok(), atoms::nil()).encode(env)),
+        Err(e) => Err(to_nif_rdb_err(e)),
+    }
+}
+
+#[rustler::nif]
+fn snapshot_get_cf<'a>(env: Env<'a>, snap: ResourceArc<SnapResource>, cf: ResourceArc<CfResource>, key: Binary) -> NifResult<Term<'a>> {
+    check_snap_cf(&snap, &cf)?;
+    let guard = snap.snap.read().unwrap();
+    let s = guard.as_ref().ok_or_else(|| snap.db.gone())?;
+    match s.get_cf(&*cf, key.as_slice()) {
+        Ok(Some(value)) => Ok((atoms::ok(), to_bin(env, &value)).encode(env)),
+        Ok(None) => Ok((atoms::ok(), atoms::nil()).encode(env)),
+        Err(e) => Err(to_nif_rdb_err(e)),
+    }
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn snapshot_multi_get_cf<'a>(env: Env<'a>, snap: ResourceArc<SnapResource>, cf: ResourceArc<CfResource>, keys: Vec<Binary>) -> NifResult<Term<'a>> {
+    check_snap_cf(&snap, &cf)?;
+    let guard = snap.snap.read().unwrap();
+    let s = guard.as_ref().ok_or_else(|| snap.db.gone())?;
+    let results = s.multi_get_cf(keys.iter().map(|k| (&*cf, k.as_slice())));
+    let mut out = Vec::with_capacity(results.len());
+    for r in results {
+        match r {
+            Ok(Some(value)) => out.push(to_bin(env, &value).encode(env)),
+            Ok(None) => out.push(atoms::nil().encode(env)),
+            Err(e) => return Err(to_nif_rdb_err(e)),
+        }
+    }
+    Ok((atoms::ok(), out).encode(env))
+}
+
+#[rustler::nif]
+fn snapshot_iterator<'a>(env: Env<'a>, snap: ResourceArc<SnapResource>) -> NifResult<Term<'a>> {
+    let res = ItResource::with_snapshot(&snap, None)?;
+    Ok((atoms::ok(), res).encode(env))
+}
+
+#[rustler::nif]
+fn snapshot_iterator_cf<'a>(env: Env<'a>, snap: ResourceArc<SnapResource>, cf: ResourceArc<CfResource>) -> NifResult<Term<'a>> {
+    let res = ItResource::with_snapshot(&snap, Some(cf.clone()))?;
+    Ok((atoms::ok(), res).encode(env))
+}
+
+// WriteBatch. No delete_range: TransactionDB::Write refuses batches holding a range deletion
+#[derive(NifUnitEnum)]
+pub enum WriteFlag {
+    Sync,
+    DisableWal,
+}
+
+// A batch only writes to the DB it was created on
+fn check_batch_cf(wb: &WbResource, cf: &CfResource) -> Result<(), Error> {
+    if std::ptr::eq::<DbResource>(&*wb.db, &*cf.db) { Ok(()) } else { Err(to_nif_err(atoms::cf_wrong_db())) }
+}
+
+#[rustler::nif]
+fn write_batch<'a>(env: Env<'a>, db: ResourceArc<DbResource>) -> NifResult<Term<'a>> {
+    Ok((atoms::ok(), ResourceArc::new(WbResource {
+        db: db,
+        batch: Mutex::new(Some(WriteBatchWithTransaction::<true>::default())),
+    })).encode(env))
+}
+
+#[rustler::nif]
+fn write_batch_put(wb: ResourceArc<WbResource>, key: Binary, val: Binary) -> NifResult<Atom> {
+    let mut guard = wb.batch.lock().unwrap();
+    let batch = guard.as_mut().ok_or_else(|| to_nif_err(atoms::mutex_closed()))?;
+    batch.put(key.as_slice(), val.as_slice());
+    Ok(atoms::ok())
+}
+
+#[rustler::nif]
+fn write_batch_put_cf(wb: ResourceArc<WbResource>, cf: ResourceArc<CfResource>, key: Binary, val: Binary) -> NifResult<Atom> {
+    check_batch_cf(&wb, &cf)?;
+    let mut guard = wb.batch.lock().unwrap();
+    let batch = guard.as_mut().ok_or_else(|| to_nif_err(atoms::mutex_closed()))?;
+    batch.put_cf(&*cf, key.as_slice(), val.as_slice());
+    Ok(atoms::ok())
+}
+
+#[rustler::nif]
+fn write_batch_delete(wb: ResourceArc<WbResource>, key: Binary) -> NifResult<Atom> {
+    let mut guard = wb.batch.lock().unwrap();
+    let batch = guard.as_mut().ok_or_else(|| to_nif_err(atoms::mutex_closed()))?;
+    batch.delete(key.as_slice());
+    Ok(atoms::ok())
+}
+
+#[rustler::nif]
+fn write_batch_delete_cf(wb: ResourceArc<WbResource>, cf: ResourceArc<CfResource>, key: Binary) -> NifResult<Atom> {
+    check_batch_cf(&wb, &cf)?;
+    let mut guard = wb.batch.lock().unwrap();
+    let batch = guard.as_mut().ok_or_else(|| to_nif_err(atoms::mutex_closed()))?;
+    batch.delete_cf(&*cf, key.as_slice());
+    Ok(atoms::ok())
+}
+
+#[rustler::nif]
+fn write_batch_count(wb: ResourceArc<WbResource>) -> NifResult<usize> {
+    let guard = wb.batch.lock().unwrap();
+    let batch = guard.as_ref().ok_or_else(|| to_nif_err(atoms::mutex_closed()))?;
+    Ok(batch.len())
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn write_batch_write(wb: ResourceArc<WbResource>, flags: Vec<WriteFlag>) -> NifResult<Atom> {
+    let mut guard = wb.batch.lock().unwrap();
+    let batch = guard.take().ok_or_else(|| to_nif_err(atoms::mutex_closed()))?;
+    drop(guard);
+
+    let mut wopts = WriteOptions::default();
+    for flag in flags {
+        match flag {
+            WriteFlag::Sync => wopts.set_sync(true),
+            WriteFlag::DisableWal => wopts.disable_wal(true),
+        }
+    }
+
+    wb.db.db()?
+        .write_opt(batch, &wopts)
+        .map(|_| atoms::ok())
+        .map_err(to_nif_rdb_err)
+}
+
 //Iterator Generic
 #[derive(NifTaggedEnum)]
 pub enum IterMove<'a> {
@@ -649,24 +1430,26 @@ fn iterator_move<'a>(env: Env<'a>, res: ResourceArc<ItResource>, action: Term<'a
     let action = parse_iter_move(action)?;
 
     match action {
-        IterMove::First => with_it!(res, it => it.seek_to_first()),
-        IterMove::Last => with_it!(res, it => it.seek_to_last()),
-        IterMove::Next => with_it!(res, it => it.next()),
-        IterMove::Prev => with_it!(res, it => it.prev()),
-        IterMove::Seek(ref key) => with_it!(res, it => it.seek(key.as_slice())),
-        IterMove::SeekForPrev(ref key) => with_it!(res, it => it.seek_for_prev(key.as_slice())),
+        IterMove::First => with_it!(res, it => it.seek_to_first())?,
+        IterMove::Last => with_it!(res, it => it.seek_to_last())?,
+        IterMove::Next => with_it!(res, it => it.next())?,
+        IterMove::Prev => with_it!(res, it => it.prev())?,
+        IterMove::Seek(ref key) => with_it!(res, it => it.seek(key.as_slice()))?,
+        IterMove::SeekForPrev(ref key) => with_it!(res, it => it.seek_for_prev(key.as_slice()))?,
     }
 
     let mut g = res.it.lock().unwrap();
-    let is_valid = match &*g {
-        IterInner::Db(it) => it.valid(),
-        IterInner::Tx(it) => it.valid(),
+    let is_valid = match g.as_ref() {
+        Some(IterInner::Db(it)) => it.valid(),
+        Some(IterInner::Tx(it)) => it.valid(),
+        None => return Err(res.db.gone()),
     };
     if !is_valid { return Ok((atoms::error(), atoms::invalid_iterator()).encode(env)); }
 
-    let (k_opt, v_opt) = match &mut *g {
-        IterInner::Db(it) => (it.key(), it.value()),
-        IterInner::Tx(it) => (it.key(), it.value()),
+    let (k_opt, v_opt) = match g.as_mut() {
+        Some(IterInner::Db(it)) => (it.key(), it.value()),
+        Some(IterInner::Tx(it)) => (it.key(), it.value()),
+        None => return Err(res.db.gone()),
     };
     match (k_opt, v_opt) {
         (Some(k), Some(v)) => {
@@ -700,97 +1483,270 @@ pub fn fixed<const N: usize>(t: Term<'_>) -> Result<[u8; N], Error> {
     Ok(a)
 }
 
+// {:error, reason} for an entry or tx the consensus code would refuse to run, or
+// one that does not decode (reason is then the model::_codec::DecodeError message)
+fn invalid_input(reason: impl std::fmt::Display) -> Error {
+    Error::Term(Box::new(reason.to_string()))
+}
+
+// Last line of defence for the NIFs fed entries and txs from peers, a panic below
+// comes back as {:error, {:panic, message}} instead of raising in the caller
+fn catch_nif<'a>(f: impl FnOnce() -> NifResult<Term<'a>>) -> NifResult<Term<'a>> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        Err(Error::Term(Box::new((atoms::panic(), panic_message(&*payload)))))
+    })
+}
+
+pub(crate) fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    match payload.downcast_ref::<&'static str>() {
+        Some(s) => s.to_string(),
+        None => payload.downcast_ref::<String>().cloned().unwrap_or_default(),
+    }
+}
+
+// The node re-encodes entries it already accepted, canonical checks belong on the
+// net and tx validation paths. Fields apply needs are checked by validate_entry
+fn decode_entry(entry_vecpak: &[u8]) -> Result<(crate::model::entry::Entry, consensus::consensus_apply::EntryFields), Error> {
+    let entry = crate::model::entry::from_bytes_lenient(entry_vecpak).map_err(invalid_input)?;
+    let fields = consensus::consensus_apply::validate_entry(&entry).map_err(invalid_input)?;
+    Ok((entry, fields))
+}
+
 #[rustler::nif(schedule = "DirtyCpu")]
 fn apply_entry<'a>(env: Env<'a>, db: ResourceArc<DbResource>, entry_vecpak: Binary, pk: Binary, sk: Binary,
-    testnet: bool, testnet_peddlebikes: Vec<Binary>) -> Result<Term<'a>, Error>
+    testnet: bool, testnet_peddlebikes: Vec<Binary>, speculate: bool, trace: bool) -> Result<Term<'a>, Error>
 {
-    let entry = crate::model::entry::from_bytes(entry_vecpak.as_slice()).map_err(|_| Error::BadArg)?;
+    catch_nif(|| {
+        let (entry, fields) = decode_entry(entry_vecpak.as_slice())?;
+
+        let txn_opts = TransactionOptions::default();
+        let write_opts = WriteOptions::default();
+        let db_ref = db.db()?;
+        let txn = db_ref.transaction_opt(&write_opts, &txn_opts);
+
+        let (txn, muts, muts_rev, receipts, root_receipts, root_contractstate, tx_trace) =
+            consensus::consensus_apply::apply_entry(&db_ref, txn, entry, &fields, pk.as_slice(), sk.as_slice(),
+                testnet, testnet_peddlebikes.iter().map(|bin| bin.as_slice().to_vec()).collect(), trace, speculate
+            ).map_err(|e| invalid_input(e.reason()))?;
+
+        let tx_static: Tx<'static> = unsafe { std::mem::transmute::<Tx<'_>, Tx<'static>>(txn) };
+        let term_txn = TxResource::new(db.clone(), tx_static).encode(env);
+        drop(db_ref);
+
+        let mut ob1 = OwnedBinary::new(root_receipts.len()).ok_or_else(|| Error::Term(Box::new("alloc failed")))?;
+        ob1.as_mut_slice().copy_from_slice(&root_receipts);
+        let mut ob2 = OwnedBinary::new(root_contractstate.len()).ok_or_else(|| Error::Term(Box::new("alloc failed")))?;
+        ob2.as_mut_slice().copy_from_slice(&root_contractstate);
+
+        let receipts_list: Vec<Term<'a>> = receipts.iter().map(|r| receipt_to_term(env, r)).collect();
+
+        // Trace is nil unless asked for, so the shape does not depend on the flag
+        let trace_term = match tx_trace {
+            Some(tx_trace) => trace_to_term(env, &tx_trace),
+            None => atoms::nil().encode(env),
+        };
+        Ok((term_txn, consensus_muts::mutations_to_map(muts), consensus_muts::mutations_to_map(muts_rev), receipts_list,
+            Binary::from_owned(ob1, env).encode(env), Binary::from_owned(ob2, env).encode(env), trace_term).encode(env))
+    })
+}
 
-    let txn_opts = TransactionOptions::default();
-    let write_opts = WriteOptions::default();
-    let txn = db.db.transaction_opt(&write_opts, &txn_opts);
+fn receipt_to_term<'a>(env: Env<'a>, r: &crate::model::tx_receipt::TXReceipt) -> Term<'a> {
+    let mut map = Term::map_new(env);
+    map = map.map_put(atoms::success(), r.success).ok().unwrap();
+    map = map.map_put(atoms::txid(), to_binary2(env, &r.txid)).ok().unwrap();
+    map = map.map_put(atoms::result(), to_binary2(env, &r.result)).ok().unwrap();
+    map = map.map_put(atoms::exec_used(), to_binary2(env, &r.exec_used)).ok().unwrap();
+    let logs_list: Vec<Binary> = r.logs.iter().map(|log| {
+        to_binary2(env, log)
+    }).collect();
+    map = map.map_put(atoms::logs(), logs_list).ok().unwrap();
+    map
+}
 
-    let (txn, muts, muts_rev, receipts, root_receipts, root_contractstate) =
-        consensus::consensus_apply::apply_entry(&db.db, txn, entry, pk.as_slice(), sk.as_slice(),
-            testnet, testnet_peddlebikes.iter().map(|bin| bin.as_slice().to_vec()).collect()
-        );
+fn opt_bin<'a>(env: Env<'a>, data: Option<&Vec<u8>>) -> Term<'a> {
+    match data {
+        Some(data) => to_binary2(env, data).encode(env),
+        None => atoms::nil().encode(env),
+    }
+}
 
-    let tx_static: Tx<'static> = unsafe { std::mem::transmute::<Tx<'_>, Tx<'static>>(txn) };
-    let term_txn = ResourceArc::new(TxResource {
-        db: db,
-        tx: Mutex::new(Some(tx_static)),
-    }).encode(env);
+fn frame_to_term<'a>(env: Env<'a>, frame: &consensus_trace::Frame) -> Term<'a> {
+    use consensus_trace::{Event, FrameStatus};
 
-    let mut ob1 = OwnedBinary::new(root_receipts.len()).ok_or_else(|| Error::Term(Box::new("alloc failed"))).unwrap();
-    ob1.as_mut_slice().copy_from_slice(&root_receipts);
-    let mut ob2 = OwnedBinary::new(root_contractstate.len()).ok_or_else(|| Error::Term(Box::new("alloc failed"))).unwrap();
-    ob2.as_mut_slice().copy_from_slice(&root_contractstate);
+    let events: Vec<Term<'a>> = frame.events.iter().map(|event| {
+        let mut map = Term::map_new(env);
+        match event {
+            Event::Read { key, value } => {
+                map = map.map_put(atoms::op(), atoms::read()).ok().unwrap();
+                map = map.map_put(atoms::key(), to_binary2(env, key)).ok().unwrap();
+                map = map.map_put(atoms::value(), opt_bin(env, value.as_ref())).ok().unwrap();
+            }
+            Event::Scan { prefix, from, found } => {
+                let found = match found {
+                    Some((k, v)) => (to_binary2(env, k), to_binary2(env, v)).encode(env),
+                    None => atoms::nil().encode(env),
+                };
+                map = map.map_put(atoms::op(), atoms::scan()).ok().unwrap();
+                map = map.map_put(atoms::prefix(), to_binary2(env, prefix)).ok().unwrap();
+                map = map.map_put(atoms::from(), to_binary2(env, from)).ok().unwrap();
+                map = map.map_put(atoms::found(), found).ok().unwrap();
+            }
+            Event::Write { key, old, new } => {
+                map = map.map_put(atoms::op(), atoms::write()).ok().unwrap();
+                map = map.map_put(atoms::key(), to_binary2(env, key)).ok().unwrap();
+                map = map.map_put(atoms::old(), opt_bin(env, old.as_ref())).ok().unwrap();
+                map = map.map_put(atoms::new(), opt_bin(env, new.as_ref())).ok().unwrap();
+            }
+            Event::SetBit { key, bit, was_set } => {
+                map = map.map_put(atoms::op(), atoms::set_bit()).ok().unwrap();
+                map = map.map_put(atoms::key(), to_binary2(env, key)).ok().unwrap();
+                map = map.map_put(atoms::bit(), *bit).ok().unwrap();
+                map = map.map_put(atoms::was_set(), *was_set).ok().unwrap();
+            }
+            Event::Call(frame) => {
+                map = frame_to_term(env, frame);
+                map = map.map_put(atoms::op(), atoms::call()).ok().unwrap();
+            }
+        }
+        map
+    }).collect();
+
+    let (status, result) = match &frame.status {
+        FrameStatus::Ok(result) => (atoms::ok(), Some(result)),
+        FrameStatus::Failed(reason) => (atoms::failed(), Some(reason)),
+        FrameStatus::Unwound => (atoms::unwound(), None),
+    };
+    let args: Vec<Binary> = frame.args.iter().map(|arg| to_binary2(env, arg)).collect();
 
-    let mut receipts_list = Vec::new();
-    for r in receipts {
+    let mut map = Term::map_new(env);
+    map = map.map_put(atoms::caller(), to_binary2(env, &frame.caller)).ok().unwrap();
+    map = map.map_put(atoms::callee(), to_binary2(env, &frame.callee)).ok().unwrap();
+    map = map.map_put(atoms::function(), to_binary2(env, &frame.function)).ok().unwrap();
+    map = map.map_put(atoms::args(), args).ok().unwrap();
+    map = map.map_put(atoms::exec_used(), frame.exec_used as i64).ok().unwrap();
+    map = map.map_put(atoms::status(), status).ok().unwrap();
+    map = map.map_put(atoms::result(), opt_bin(env, result)).ok().unwrap();
+    map = map.map_put(atoms::events(), events).ok().unwrap();
+    map
+}
+
+// [%{index, tx_hash, error, calls: [frame]}], a frame being
+// %{caller, callee, function, args, exec_used, status: :ok | :failed | :unwound, result, events}
+// and events the kv ops and nested calls (op: :call) in execution order
+fn trace_to_term<'a>(env: Env<'a>, trace: &consensus_trace::Trace) -> Term<'a> {
+    let txs: Vec<Term<'a>> = trace.txs.iter().map(|tx| {
+        let calls: Vec<Term<'a>> = tx.calls.iter().map(|frame| frame_to_term(env, frame)).collect();
         let mut map = Term::map_new(env);
-        map = map.map_put(atoms::success(), r.success).ok().unwrap();
-        map = map.map_put(atoms::txid(), to_binary2(env, &r.txid)).ok().unwrap();
-        map = map.map_put(atoms::result(), to_binary2(env, &r.result)).ok().unwrap();
-        map = map.map_put(atoms::exec_used(), to_binary2(env, &r.exec_used)).ok().unwrap();
-        let logs_list: Vec<Binary> = r.logs.iter().map(|log| {
-            to_binary2(env, log)
+        map = map.map_put(atoms::index(), tx.index).ok().unwrap();
+        map = map.map_put(atoms::tx_hash(), to_binary2(env, &tx.tx_hash)).ok().unwrap();
+        map = map.map_put(atoms::error(), opt_bin(env, tx.error.as_ref())).ok().unwrap();
+        map = map.map_put(atoms::calls(), calls).ok().unwrap();
+        map
+    }).collect();
+    txs.encode(env)
+}
+
+// Dry run of txus (vecpak TXUs) against current state with entry_vecpak as the entry
+// context. Never commits; receipts carry what each tx was charged on top of exec_used
+#[rustler::nif(schedule = "DirtyCpu")]
+fn simulate_txs<'a>(env: Env<'a>, db: ResourceArc<DbResource>, entry_vecpak: Binary, txus: Vec<Binary>,
+    testnet: bool, trace: bool) -> Result<Term<'a>, Error>
+{
+    catch_nif(|| {
+        let (entry, fields) = decode_entry(entry_vecpak.as_slice())?;
+        let mut txus_decoded = Vec::with_capacity(txus.len());
+        let mut tx_fields = Vec::with_capacity(txus.len());
+        for bin in &txus {
+            let txu = crate::model::validate_canonical::<crate::model::tx::TXU>(bin.as_slice()).map_err(invalid_input)?;
+            tx_fields.push(consensus::consensus_apply::validate_txu(&txu).map_err(invalid_input)?);
+            txus_decoded.push(txu);
+        }
+
+        let (muts, receipts, usage, tx_trace) = consensus::consensus_apply::simulate_txs(&*db.db()?, entry, &fields, txus_decoded, &tx_fields, testnet, trace)
+            .map_err(|e| invalid_input(e.reason()))?;
+
+        let receipts_list: Vec<Term<'a>> = receipts.iter().zip(usage.iter()).map(|(r, u)| {
+            let mut map = receipt_to_term(env, r);
+            map = map.map_put(atoms::historical_cost(), u.historical as u64).ok().unwrap();
+            map = map.map_put(atoms::exec_cost(), u.exec as u64).ok().unwrap();
+            map = map.map_put(atoms::storage_cost(), u.storage as u64).ok().unwrap();
+            map
         }).collect();
-        map = map.map_put(atoms::logs(), logs_list).ok().unwrap();
-        receipts_list.push(map);
-    }
 
-    Ok((term_txn, consensus_muts::mutations_to_map(muts), consensus_muts::mutations_to_map(muts_rev), receipts_list,
-        Binary::from_owned(ob1, env).encode(env), Binary::from_owned(ob2, env).encode(env)).encode(env))
+        let mut map = Term::map_new(env);
+        map = map.map_put(atoms::receipts(), receipts_list).ok().unwrap();
+        map = map.map_put(atoms::muts(), consensus_muts::mutations_to_map(muts)).ok().unwrap();
+        if let Some(tx_trace) = tx_trace {
+            map = map.map_put(atoms::trace(), trace_to_term(env, &tx_trace)).ok().unwrap();
+        }
+        Ok((atoms::ok(), map).encode(env))
+    })
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
 fn contract_view<'a>(env: Env<'a>, db: ResourceArc<DbResource>, entry_vecpak: Binary, view_pk: Binary,
-    contract: Binary, function: Binary, fargs: Vec<Binary>, testnet: bool) -> Result<Term<'a>, Error>
+    contract: Binary, function: Binary, fargs: Vec<Binary>, testnet: bool,
+    snapshot: Option<ResourceArc<SnapResource>>) -> Result<Term<'a>, Error>
 {
-    let entry = crate::model::entry::from_bytes(entry_vecpak.as_slice()).map_err(|_| Error::BadArg)?;
+    catch_nif(|| {
+        let (entry, fields) = decode_entry(entry_vecpak.as_slice())?;
+        let view_pk: [u8; 48] = view_pk.as_slice().try_into().map_err(|_| invalid_input("view_pk_len_wrong"))?;
 
-    let (success, result, logs) = consensus::consensus_apply::contract_view(
-        &db.db, entry, view_pk.as_slice().to_vec(),
-        contract.as_slice().to_vec(), function.as_slice().to_vec(), fargs.iter().map(|bin| bin.as_slice().to_vec()).collect(),
-        testnet
-    );
+        if snapshot.as_ref().is_some_and(|s| !std::ptr::eq::<DbResource>(&*s.db, &*db)) {
+            return Err(to_nif_err(atoms::snapshot_wrong_db()));
+        }
+        // db guard before the snapshot lock, close_db takes them in that order
+        let db_ref = db.db()?;
+        let snap_guard = snapshot.as_ref().map(|s| s.snap.read().unwrap());
+        let snap = match &snap_guard {
+            Some(g) => Some(g.as_ref().ok_or_else(|| db.gone())?),
+            None => None,
+        };
+
+        let (success, result, logs) = consensus::consensus_apply::contract_view(
+            &db_ref, entry, &fields, view_pk,
+            contract.as_slice().to_vec(), function.as_slice().to_vec(), fargs.iter().map(|bin| bin.as_slice().to_vec()).collect(),
+            testnet, snap
+        );
 
-    let mut ob_result = OwnedBinary::new(result.len()).ok_or_else(|| Error::Term(Box::new("alloc failed"))).unwrap();
-    ob_result.as_mut_slice().copy_from_slice(&result);
+        let mut ob_result = OwnedBinary::new(result.len()).ok_or_else(|| Error::Term(Box::new("alloc failed")))?;
+        ob_result.as_mut_slice().copy_from_slice(&result);
 
-    let mut logs_list = Vec::new();
-    for l in logs {
-        let mut ob_log = OwnedBinary::new(l.len()).ok_or_else(|| Error::Term(Box::new("alloc failed"))).unwrap();
-        ob_log.as_mut_slice().copy_from_slice(&l);
-        logs_list.push(Binary::from_owned(ob_log, env))
-    };
+        let mut logs_list = Vec::new();
+        for l in logs {
+            let mut ob_log = OwnedBinary::new(l.len()).ok_or_else(|| Error::Term(Box::new("alloc failed")))?;
+            ob_log.as_mut_slice().copy_from_slice(&l);
+            logs_list.push(Binary::from_owned(ob_log, env))
+        };
 
-    Ok((success, Binary::from_owned(ob_result, env), logs_list).encode(env))
+        Ok((success, Binary::from_owned(ob_result, env), logs_list).encode(env))
+    })
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
 fn contract_validate<'a>(env: Env<'a>, db: ResourceArc<DbResource>, entry_vecpak: Binary, wasmbytes: Binary,
     testnet: bool) -> Result<Term<'a>, Error>
 {
-    let entry = crate::model::entry::from_bytes(entry_vecpak.as_slice()).map_err(|_| Error::BadArg)?;
+    catch_nif(|| {
+        let (entry, fields) = decode_entry(entry_vecpak.as_slice())?;
 
-    let (result, logs) = consensus::consensus_apply::contract_validate(
-        &db.db, entry, wasmbytes.as_slice(),
-        testnet
-    );
+        let (result, logs) = consensus::consensus_apply::contract_validate(
+            &*db.db()?, entry, &fields, wasmbytes.as_slice(),
+            testnet
+        );
 
-    let mut ob_result = OwnedBinary::new(result.len()).ok_or_else(|| Error::Term(Box::new("alloc failed"))).unwrap();
-    ob_result.as_mut_slice().copy_from_slice(&result);
+        let mut ob_result = OwnedBinary::new(result.len()).ok_or_else(|| Error::Term(Box::new("alloc failed")))?;
+        ob_result.as_mut_slice().copy_from_slice(&result);
 
-    let mut logs_list = Vec::new();
-    for l in logs {
-        let mut ob_log = OwnedBinary::new(l.len()).ok_or_else(|| Error::Term(Box::new("alloc failed"))).unwrap();
-        ob_log.as_mut_slice().copy_from_slice(&l);
-        logs_list.push(Binary::from_owned(ob_log, env))
-    };
+        let mut logs_list = Vec::new();
+        for l in logs {
+            let mut ob_log = OwnedBinary::new(l.len()).ok_or_else(|| Error::Term(Box::new("alloc failed")))?;
+            ob_log.as_mut_slice().copy_from_slice(&l);
+            logs_list.push(Binary::from_owned(ob_log, env))
+        };
 
-    Ok((Binary::from_owned(ob_result, env), logs_list).encode(env))
+        Ok((Binary::from_owned(ob_result, env), logs_list).encode(env))
+    })
 }
 
 #[rustler::nif]
@@ -810,6 +1766,48 @@ fn vecpak_decode<'a>(env: Env<'a>, bin: Binary) -> Result<Term<'a>, Error> {
     Ok(term.encode(env))
 }
 
+// :ok if bin is the canonical vecpak encoding of kind (:entry, :txu, :attestation
+// or :tx_receipt), {:error, reason} otherwise
+#[rustler::nif]
+fn validate_canonical(kind: Atom, bin: Binary) -> NifResult<Atom> {
+    use crate::model::{attestation::Attestation, entry::Entry, tx::TXU, tx_receipt::TXReceipt};
+    let bytes = bin.as_slice();
+    let res = if kind == atoms::entry() {
+        crate::model::validate_canonical::<Entry>(bytes).map(|_| ())
+    } else if kind == atoms::txu() {
+        crate::model::validate_canonical::<TXU>(bytes).map(|_| ())
+    } else if kind == atoms::attestation() {
+        crate::model::validate_canonical::<Attestation>(bytes).map(|_| ())
+    } else if kind == atoms::tx_receipt() {
+        crate::model::validate_canonical::<TXReceipt>(bytes).map(|_| ())
+    } else {
+        return Err(Error::BadArg);
+    };
+    res.map_err(invalid_input)?;
+    Ok(atoms::ok())
+}
+
+// Stateless tx validation for the txpool, one entry per packed txu in input order:
+// {:ok, historical_cost} or {:error, reason}. reason is an atom named like the
+// TX.validate errors, :tx_not_canonical for txus that do not decode as TX.validate_packed
+#[rustler::nif(schedule = "DirtyCpu")]
+fn validate_txus<'a>(env: Env<'a>, txus: Vec<Binary<'a>>, tx_size: usize) -> Result<Term<'a>, Error> {
+    catch_nif(|| {
+        let packed: Vec<&[u8]> = txus.iter().map(|b| b.as_slice()).collect();
+        let results = consensus::consensus_tx::validate_txus_packed(&packed, tx_size);
+
+        let list: Vec<Term<'a>> = results.into_iter().map(|r| match r {
+            Ok((_, cost)) => (atoms::ok(), cost as u64).encode(env),
+            Err(consensus::consensus_tx::TxError::Invalid(reason)) => {
+                let reason = Atom::from_str(env, reason).unwrap_or_else(|_| atoms::unknown());
+                (atoms::error(), reason).encode(env)
+            }
+            Err(consensus::consensus_tx::TxError::Decode(_)) => (atoms::error(), atoms::tx_not_canonical()).encode(env),
+        }).collect();
+        Ok(list.encode(env))
+    })
+}
+
 #[rustler::nif]
 fn freivalds(tensor: Binary, vr_b3: Binary) -> bool {
     crate::consensus::bic::sol_freivalds::freivalds(tensor.as_slice(), vr_b3.as_slice())
@@ -946,9 +1944,10 @@ fn bintree_root_verify<'a>(env: Env<'a>, proof_ex: Term<'a>, ns: Option<Binary<'
 
 //rocksdb proof
 #[rustler::nif]
-fn bintree_contractstate_root_prove<'a>(env: Env<'a>, db: ResourceArc<DbResource>, ns: Option<Binary<'a>>, key: Binary<'a>) -> Term<'a> {
-    let cf_handle = db.db.cf_handle("contractstate_tree").unwrap();
-    let mut iter = db.db.raw_iterator_cf(&cf_handle);
+fn bintree_contractstate_root_prove<'a>(env: Env<'a>, db: ResourceArc<DbResource>, ns: Option<Binary<'a>>, key: Binary<'a>) -> NifResult<Term<'a>> {
+    let db_ref = db.db()?;
+    let cf_handle = db_ref.cf_handle("contractstate_tree").unwrap();
+    let mut iter = db_ref.raw_iterator_cf(&cf_handle);
 
     //let namespace_data = consensus_kv::contractstate_namespace(&key);
     //let namespace = namespace_data.as_deref();
@@ -981,7 +1980,7 @@ fn bintree_contractstate_root_prove<'a>(env: Env<'a>, db: ResourceArc<DbResource
     proof_map = proof_map.map_put(atoms::hash(), hash_term).ok().unwrap();
     proof_map = proof_map.map_put(atoms::nodes(), nodes_list.encode(env)).ok().unwrap();
 
-    (proof_map).encode(env)
+    Ok((proof_map).encode(env))
 }
 /*
 #[rustler::nif(schedule = "DirtyCpu")]
@@ -1076,7 +2075,49 @@ fn build_tx_hashfilters<'a>(env: Env<'a>, txus: Vec<Term<'a>>) -> NifResult<Vec<
 #[rustler::nif]
 fn query_tx_hashfilter<'a>(env: Env<'a>, db: ResourceArc<DbResource>, signer: Binary<'a>, arg0: Binary<'a>, contract: Binary<'a>, function: Binary<'a>,
     limit: u32, sort: bool, cursor: Option<Binary<'a>>) -> NifResult<(Option<Binary<'a>>, Vec<Binary<'a>>)> {
-    tx_filter::query_tx_hashfilter(env, &db.db, &signer, &arg0, &contract, &function, limit as usize, sort, cursor.map(|b| b.as_slice()))
+    tx_filter::query_tx_hashfilter(env, &*db.db()?, &signer, &arg0, &contract, &function, limit as usize, sort, cursor.map(|b| b.as_slice()))
 }
 
 rustler::init!("Elixir.RDB", load = on_load);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delete_range_on_transaction_db() {
+        let path = std::env::temp_dir().join(format!("rdb_delete_range_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+        // Prefix seeks would stop at the first prefix without total order
+        let mut cf_opts = Options::default();
+        cf_opts.set_prefix_extractor(SliceTransform::create_fixed_prefix(1));
+        let db: TransactionDB<MultiThreaded> = TransactionDB::open_cf_descriptors(&opts, &TransactionDBOptions::default(), &path,
+            vec![ColumnFamilyDescriptor::new("tx_filter", cf_opts)]).unwrap();
+        let cf = db.cf_handle("tx_filter").unwrap();
+
+        // The plain range delete is what this works around
+        assert!(db.delete_range_cf(&cf, b"a", b"b").is_err());
+
+        let key = |p: u8, i: u32| crate::bcat(&[&[p], &i.to_be_bytes()]);
+        for p in [b'a', b'b', b'c', b'd'] {
+            for i in 0..(DELETE_RANGE_CHUNK as u32 + 5) {
+                db.put_cf(&cf, key(p, i), b"v").unwrap();
+            }
+        }
+        delete_range(&db, &cf, &key(b'b', 3), &key(b'd', 0)).unwrap();
+
+        let mut ro = ReadOptions::default();
+        ro.set_total_order_seek(true);
+        let left: Vec<Vec<u8>> = db.iterator_cf_opt(&cf, ro, rust_rocksdb::IteratorMode::Start).map(|kv| kv.unwrap().0.to_vec()).collect();
+        let count = |p: u8| left.iter().filter(|k| k[0] == p).count();
+        assert_eq!((count(b'a'), count(b'b'), count(b'c'), count(b'd')), (DELETE_RANGE_CHUNK + 5, 3, 0, DELETE_RANGE_CHUNK + 5));
+        assert!(left.contains(&key(b'b', 2)) && !left.contains(&key(b'b', 3)));
+
+        drop(cf);
+        drop(db);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+}
@@ -0,0 +1,84 @@
+use rustler::env::{OwnedEnv, SavedTerm};
+use rustler::{Atom, Encoder, Env, Error, LocalPid, Term};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Mutex, OnceLock};
+
+use crate::atoms;
+
+// Long running maintenance (compaction, checkpoints, flushes, range deletes) runs here
+// instead of on a dirty scheduler. Jobs run one at a time in submission order.
+type Job = Box<dyn FnOnce() + Send + 'static>;
+static WORKER: OnceLock<Mutex<Sender<Job>>> = OnceLock::new();
+
+fn worker() -> &'static Mutex<Sender<Job>> {
+    WORKER.get_or_init(|| {
+        let (tx, rx) = channel::<Job>();
+        std::thread::Builder::new()
+            .name("rdb_maintenance".into())
+            .spawn(move || {
+                for job in rx {
+                    job();
+                }
+            })
+            .expect("failed to spawn rdb maintenance thread");
+        Mutex::new(tx)
+    })
+}
+
+// Messages back to the caller: {:rdb_progress, ref, stage} while running, then {:rdb_done, ref, result}
+pub struct Reply {
+    pid: LocalPid,
+    owned: OwnedEnv,
+    reference: SavedTerm,
+}
+
+impl Reply {
+    pub fn progress(&self, stage: Atom) {
+        let pid = self.pid;
+        self.owned.run(|env| {
+            let reference = self.reference.load(env);
+            let mut msg_env = OwnedEnv::new();
+            let _ = msg_env.send_and_clear(&pid, |menv| {
+                (atoms::rdb_progress(), reference.in_env(menv), stage).encode(menv)
+            });
+        });
+    }
+
+    fn done(mut self, result: Result<Atom, Error>) {
+        let pid = self.pid;
+        let reference = self.reference;
+        let _ = self.owned.send_and_clear(&pid, |env| {
+            let result = match result {
+                Ok(a) => a.encode(env),
+                Err(Error::Term(t)) => (atoms::error(), t.encode(env)).encode(env),
+                Err(_) => (atoms::error(), atoms::badarg()).encode(env),
+            };
+            (atoms::rdb_done(), reference.load(env), result).encode(env)
+        });
+    }
+}
+
+// Queues job and returns {:ok, ref} right away. A job that panics reports
+// {:error, {:panic, message}} and the worker moves on to the next one.
+pub fn spawn<'a, F>(env: Env<'a>, job: F) -> Term<'a>
+where
+    F: FnOnce(&Reply) -> Result<Atom, Error> + Send + 'static,
+{
+    let reference = env.make_ref().encode(env);
+    let owned = OwnedEnv::new();
+    let reply = Reply { pid: env.pid(), reference: owned.save(reference), owned };
+
+    let task: Job = Box::new(move || {
+        reply.progress(atoms::started());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| job(&reply)))
+            .unwrap_or_else(|payload| {
+                Err(Error::Term(Box::new((atoms::panic(), crate::panic_message(&*payload)))))
+            });
+        reply.done(result);
+    });
+    let sent = worker().lock().unwrap().send(task);
+    if sent.is_err() {
+        return (atoms::error(), atoms::worker_down()).encode(env);
+    }
+    (atoms::ok(), reference).encode(env)
+}
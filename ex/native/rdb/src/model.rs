@@ -3,3 +3,82 @@ pub mod attestation;
 pub mod entry;
 pub mod tx;
 pub mod tx_receipt;
+
+use _codec::{DecodeError, EncodeIntoBuf, EncodeToTerm};
+use vecpak::Term;
+
+// Hashes and signatures are over the exact vecpak bytes, so only the canonical
+// encoding of a model is accepted: decode, encode back and the bytes must match.
+// Catches out of order or duplicate keys and non minimal varints.
+pub fn validate_canonical<T>(data: &[u8]) -> Result<T, DecodeError>
+where
+    T: for<'a> TryFrom<&'a Term, Error = DecodeError> + EncodeToTerm,
+{
+    let term = vecpak::decode(data)?;
+    let v = T::try_from(&term)?;
+    let mut buf = Vec::with_capacity(data.len());
+    v.encode_into_buf(&mut buf)?;
+    if buf != data {
+        return Err(DecodeError::NonCanonical);
+    }
+    Ok(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tx::{Action, TX, TXU};
+
+    fn txu() -> TXU {
+        TXU {
+            hash: vec![1; 32],
+            signature: vec![2; 96],
+            tx: TX {
+                signer: vec![3; 48],
+                nonce: 300,
+                action: Action {
+                    op: b"call".to_vec(),
+                    contract: b"Coin".to_vec(),
+                    function: b"transfer".to_vec(),
+                    args: vec![vec![4; 48], b"100".to_vec(), b"AMA".to_vec()],
+                    attached_symbol: None,
+                    attached_amount: None,
+                },
+            },
+        }
+    }
+
+    fn canonical() -> Vec<u8> {
+        let mut buf = Vec::new();
+        txu().encode_into_buf(&mut buf).unwrap();
+        buf
+    }
+
+    fn position(haystack: &[u8], needle: &[u8]) -> usize {
+        haystack.windows(needle.len()).position(|w| w == needle).unwrap()
+    }
+
+    #[test]
+    fn validate_canonical_accepts_canonical() {
+        let txu = validate_canonical::<TXU>(&canonical()).unwrap();
+        assert_eq!(txu.tx.nonce, 300);
+    }
+
+    #[test]
+    fn validate_canonical_rejects_reordered_keys() {
+        // The top level pairs of the canonical bytes, each encoded as key then value,
+        // put back together with the first two swapped
+        let canonical = canonical();
+        let Term::PropList(pairs) = txu().to_term().unwrap() else { unreachable!() };
+        let mut encoded: Vec<Vec<u8>> = pairs.into_iter()
+            .map(|(k, v)| [vecpak::encode(k), vecpak::encode(v)].concat())
+            .collect();
+        encoded.sort_by_key(|pair| position(&canonical, pair));
+        let header = &canonical[..canonical.len() - encoded.iter().map(Vec::len).sum::<usize>()];
+        assert_eq!([header, encoded.concat().as_slice()].concat(), canonical);
+
+        encoded.swap(0, 1);
+        let reordered = [header, encoded.concat().as_slice()].concat();
+        assert_eq!(validate_canonical::<TXU>(&reordered).unwrap_err(), DecodeError::NonCanonical);
+    }
+}
@@ -14,74 +14,157 @@ impl<T: EncodeToTerm> EncodeIntoBuf for T {
     }
 }
 
-pub trait DecodeFromTerm: Sized {
-    fn from_term(t: &Term) -> Self;
+// Models are decoded with TryFrom<&Term>, everything that comes off the wire or out
+// of the db goes through these so a malformed term is an error, never a panic
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DecodeError {
+    #[error("vecpak: {0}")]
+    Vecpak(&'static str),
+    #[error("{0}: expected proplist")]
+    NotAPropList(&'static str),
+    #[error("{model}.{field}: missing field")]
+    MissingField { model: &'static str, field: &'static str },
+    #[error("{model}.{field}: wrong type, expected {expected}")]
+    WrongType { model: &'static str, field: &'static str, expected: &'static str },
+    #[error("{model}.{field}: wrong length {got}, expected {expected}")]
+    WrongLength { model: &'static str, field: &'static str, expected: usize, got: usize },
+    #[error("{model}.{field}: out of range")]
+    OutOfRange { model: &'static str, field: &'static str },
+    #[error("{model}: unexpected field {field}")]
+    UnexpectedField { model: &'static str, field: String },
+    #[error("{model}.{field}: duplicate field")]
+    DuplicateField { model: &'static str, field: &'static str },
+    #[error("non-canonical encoding")]
+    NonCanonical,
 }
 
-#[inline]
-pub fn pl_find<'a>(pairs: &'a [(Term, Term)], key: &[u8]) -> &'a Term {
-    &pairs
-        .iter()
-        .find(|(k, _)| matches!(k, Term::Binary(b) if b.as_slice() == key))
-        .unwrap()
-        .1
-}
-
-#[inline]
-pub fn pl_get_varint(pairs: &[(Term, Term)], key: &[u8]) -> i128 {
-    match pl_find(pairs, key) {
-        Term::VarInt(x) => *x,
-        _ => unreachable!(),
+impl From<&'static str> for DecodeError {
+    fn from(e: &'static str) -> Self {
+        DecodeError::Vecpak(e)
     }
 }
 
-#[inline]
-pub fn pl_get_varint_opt(pairs: &[(Term, Term)], key: &[u8]) -> Option<i128> {
-    match pl_find_opt(pairs, key) {
-        Some(Term::VarInt(x)) => Some(*x),
-        _ => None,
-    }
+// Strict is for bytes from peers and clients. Lenient is how models were decoded before
+// and is kept for what the node re-encodes itself for apply_entry: unknown and
+// duplicate keys are skipped (first one wins), optional fields of the wrong type read
+// as absent, lengths are not checked and u64s wrap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Strict,
+    Lenient,
 }
 
-#[inline]
-pub fn pl_get_u64(pairs: &[(Term, Term)], key: &[u8]) -> u64 {
-    pl_get_varint(pairs, key) as u64
+// Proplist of a model being decoded. Keys are checked against the model's field list
+// up front, the getters then only have to care about type and length
+pub struct Fields<'a> {
+    model: &'static str,
+    pairs: &'a [(Term, Term)],
+    mode: Mode,
 }
 
-#[inline]
-pub fn pl_get_bool<'a>(pairs: &'a [(Term, Term)], key: &[u8]) -> bool {
-    match pl_find(pairs, key) {
-        Term::Bool(v) => *v,
-        _ => unreachable!(),
+impl<'a> Fields<'a> {
+    pub fn new(model: &'static str, t: &'a Term, known: &[&'static str]) -> Result<Self, DecodeError> {
+        Self::with_mode(model, t, known, Mode::Strict)
     }
-}
-#[inline]
-pub fn pl_get_bytes<'a>(pairs: &'a [(Term, Term)], key: &[u8]) -> &'a [u8] {
-    match pl_find(pairs, key) {
-        Term::Binary(v) => v.as_slice(),
-        _ => unreachable!(),
+
+    pub fn with_mode(model: &'static str, t: &'a Term, known: &[&'static str], mode: Mode) -> Result<Self, DecodeError> {
+        let Term::PropList(pairs) = t else { return Err(DecodeError::NotAPropList(model)) };
+        if mode == Mode::Lenient {
+            return Ok(Fields { model, pairs, mode });
+        }
+        for (i, (k, _)) in pairs.iter().enumerate() {
+            let Term::Binary(key) = k else {
+                return Err(DecodeError::UnexpectedField { model, field: "<non-binary key>".to_string() });
+            };
+            let Some(&field) = known.iter().find(|f| f.as_bytes() == key.as_slice()) else {
+                return Err(DecodeError::UnexpectedField { model, field: String::from_utf8_lossy(key).into_owned() });
+            };
+            if pairs[..i].iter().any(|(k2, _)| matches!(k2, Term::Binary(b) if b == key)) {
+                return Err(DecodeError::DuplicateField { model, field });
+            }
+        }
+        Ok(Fields { model, pairs, mode })
     }
-}
-#[inline]
-pub fn pl_get_list<'a>(pairs: &'a [(Term, Term)], key: &[u8]) -> &'a [Term] {
-    match pl_find(pairs, key) {
-        Term::List(v) => v.as_slice(),
-        _ => unreachable!(),
+
+    pub fn find_opt(&self, field: &'static str) -> Option<&'a Term> {
+        pl_find_opt(self.pairs, field.as_bytes())
     }
-}
 
-#[inline]
-pub fn pl_get_list_of_bytes(pairs: &[(Term, Term)], key: &[u8]) -> Vec<Vec<u8>> {
-    match pl_find(pairs, key) {
-        Term::List(list) => {
-            list.iter().map(|item| {
-                match item {
-                    Term::Binary(b) => b.clone(),
-                    _ => unreachable!(),
-                }
-            }).collect()
-        },
-        _ => unreachable!(),
+    pub fn find(&self, field: &'static str) -> Result<&'a Term, DecodeError> {
+        self.find_opt(field).ok_or(DecodeError::MissingField { model: self.model, field })
+    }
+
+    fn wrong_type(&self, field: &'static str, expected: &'static str) -> DecodeError {
+        DecodeError::WrongType { model: self.model, field, expected }
+    }
+
+    pub fn varint(&self, field: &'static str) -> Result<i128, DecodeError> {
+        match self.find(field)? {
+            Term::VarInt(x) => Ok(*x),
+            _ => Err(self.wrong_type(field, "varint")),
+        }
+    }
+
+    pub fn varint_opt(&self, field: &'static str) -> Result<Option<i128>, DecodeError> {
+        match self.find_opt(field) {
+            None => Ok(None),
+            Some(Term::VarInt(x)) => Ok(Some(*x)),
+            Some(_) if self.mode == Mode::Lenient => Ok(None),
+            Some(_) => Err(self.wrong_type(field, "varint")),
+        }
+    }
+
+    pub fn u64(&self, field: &'static str) -> Result<u64, DecodeError> {
+        let x = self.varint(field)?;
+        match self.mode {
+            Mode::Strict => u64::try_from(x).map_err(|_| DecodeError::OutOfRange { model: self.model, field }),
+            Mode::Lenient => Ok(x as u64),
+        }
+    }
+
+    pub fn bool(&self, field: &'static str) -> Result<bool, DecodeError> {
+        match self.find(field)? {
+            Term::Bool(v) => Ok(*v),
+            _ => Err(self.wrong_type(field, "bool")),
+        }
+    }
+
+    pub fn bytes(&self, field: &'static str) -> Result<&'a [u8], DecodeError> {
+        match self.find(field)? {
+            Term::Binary(v) => Ok(v.as_slice()),
+            _ => Err(self.wrong_type(field, "binary")),
+        }
+    }
+
+    pub fn bytes_opt(&self, field: &'static str) -> Result<Option<&'a [u8]>, DecodeError> {
+        match self.find_opt(field) {
+            None => Ok(None),
+            Some(Term::Binary(v)) => Ok(Some(v.as_slice())),
+            Some(_) if self.mode == Mode::Lenient => Ok(None),
+            Some(_) => Err(self.wrong_type(field, "binary")),
+        }
+    }
+
+    pub fn bytes_len(&self, field: &'static str, len: usize) -> Result<Vec<u8>, DecodeError> {
+        let v = self.bytes(field)?;
+        if v.len() != len && self.mode == Mode::Strict {
+            return Err(DecodeError::WrongLength { model: self.model, field, expected: len, got: v.len() });
+        }
+        Ok(v.to_vec())
+    }
+
+    pub fn list(&self, field: &'static str) -> Result<&'a [Term], DecodeError> {
+        match self.find(field)? {
+            Term::List(v) => Ok(v.as_slice()),
+            _ => Err(self.wrong_type(field, "list")),
+        }
+    }
+
+    pub fn list_of_bytes(&self, field: &'static str) -> Result<Vec<Vec<u8>>, DecodeError> {
+        self.list(field)?.iter().map(|item| match item {
+            Term::Binary(b) => Ok(b.clone()),
+            _ => Err(self.wrong_type(field, "list of binary")),
+        }).collect()
     }
 }
 
@@ -92,10 +175,140 @@ pub fn pl_find_opt<'a>(pairs: &'a [(Term, Term)], key: &[u8]) -> Option<&'a Term
         .map(|(_, v)| v)
 }
 
-#[inline]
-pub fn pl_get_bytes_opt<'a>(pairs: &'a [(Term, Term)], key: &[u8]) -> Option<&'a [u8]> {
-    match pl_find_opt(pairs, key) {
-        Some(Term::Binary(v)) => Some(v.as_slice()),
-        _ => None,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::entry::{Entry, Header};
+    use crate::model::tx::{self, Action, TX, TXU};
+
+    fn bin(b: &[u8]) -> Term {
+        Term::Binary(b.to_vec())
+    }
+
+    fn props(pairs: Vec<(&str, Term)>) -> Term {
+        Term::PropList(pairs.into_iter().map(|(k, v)| (bin(k.as_bytes()), v)).collect())
+    }
+
+    fn tx_term(signer: Term, nonce: Term) -> Term {
+        let action = props(vec![
+            ("op", bin(b"call")),
+            ("contract", bin(b"Coin")),
+            ("function", bin(b"transfer")),
+            ("args", Term::List(vec![bin(b"a"), bin(b"1")])),
+        ]);
+        props(vec![("signer", signer), ("nonce", nonce), ("action", action)])
+    }
+
+    fn txu() -> TXU {
+        TXU {
+            hash: vec![1; 32],
+            signature: vec![2; 96],
+            tx: TX {
+                signer: vec![3; 48],
+                nonce: 7,
+                action: Action {
+                    op: b"call".to_vec(),
+                    contract: b"Coin".to_vec(),
+                    function: b"transfer".to_vec(),
+                    args: vec![vec![4; 48], b"100".to_vec(), b"AMA".to_vec()],
+                    attached_symbol: Some(b"AMA".to_vec()),
+                    attached_amount: Some(b"5".to_vec()),
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut buf = Vec::new();
+        txu().encode_into_buf(&mut buf).unwrap();
+        let back = tx::from_bytes(&buf).unwrap();
+        assert_eq!(back.hash, vec![1; 32]);
+        assert_eq!(back.tx.nonce, 7);
+        assert_eq!(back.tx.action.args, txu().tx.action.args);
+        assert_eq!(back.tx.action.attached_amount, Some(b"5".to_vec()));
+
+        let mut again = Vec::new();
+        back.encode_into_buf(&mut again).unwrap();
+        assert_eq!(again, buf);
+    }
+
+    #[test]
+    fn malformed_bytes() {
+        let mut buf = Vec::new();
+        txu().encode_into_buf(&mut buf).unwrap();
+        assert!(matches!(tx::from_bytes(&buf[..buf.len() - 1]), Err(DecodeError::Vecpak(_))));
+        assert!(matches!(tx::from_bytes(&[0xff, 0xff, 0xff]), Err(DecodeError::Vecpak(_))));
+        assert!(tx::from_bytes(&[]).is_err());
+    }
+
+    #[test]
+    fn missing_field() {
+        let t = props(vec![("signer", bin(&[0; 48])), ("nonce", Term::VarInt(1))]);
+        assert_eq!(TX::try_from(&t).unwrap_err(), DecodeError::MissingField { model: "tx", field: "action" });
+    }
+
+    #[test]
+    fn wrong_type() {
+        let t = tx_term(bin(&[0; 48]), bin(b"1"));
+        assert_eq!(TX::try_from(&t).unwrap_err(), DecodeError::WrongType { model: "tx", field: "nonce", expected: "varint" });
+
+        let t = props(vec![
+            ("op", bin(b"call")),
+            ("contract", bin(b"Coin")),
+            ("function", bin(b"transfer")),
+            ("args", Term::List(vec![Term::VarInt(1)])),
+        ]);
+        assert_eq!(Action::try_from(&t).unwrap_err(), DecodeError::WrongType { model: "action", field: "args", expected: "list of binary" });
+
+        assert_eq!(TX::try_from(&Term::List(vec![])).unwrap_err(), DecodeError::NotAPropList("tx"));
+    }
+
+    #[test]
+    fn wrong_length() {
+        let t = tx_term(bin(&[0; 47]), Term::VarInt(1));
+        assert_eq!(TX::try_from(&t).unwrap_err(), DecodeError::WrongLength { model: "tx", field: "signer", expected: 48, got: 47 });
+    }
+
+    #[test]
+    fn out_of_range() {
+        let t = tx_term(bin(&[0; 48]), Term::VarInt(-1));
+        assert_eq!(TX::try_from(&t).unwrap_err(), DecodeError::OutOfRange { model: "tx", field: "nonce" });
+    }
+
+    #[test]
+    fn unexpected_and_duplicate_fields() {
+        let with = |key: &[u8]| {
+            let Term::PropList(mut pairs) = tx_term(bin(&[0; 48]), Term::VarInt(1)) else { unreachable!() };
+            pairs.push((bin(key), Term::VarInt(2)));
+            Term::PropList(pairs)
+        };
+        assert_eq!(TX::try_from(&with(b"extra")).unwrap_err(), DecodeError::UnexpectedField { model: "tx", field: "extra".to_string() });
+        assert_eq!(TX::try_from(&with(b"nonce")).unwrap_err(), DecodeError::DuplicateField { model: "tx", field: "nonce" });
+    }
+
+    #[test]
+    fn lenient() {
+        let header = Header {
+            prev_hash: vec![0; 32], height: 1, slot: 1, prev_slot: 0, signer: vec![0; 48],
+            dr: vec![0; 32], vr: vec![0; 96], root_tx: vec![0; 32], root_validator: vec![0; 32],
+        };
+        let entry = Entry { hash: vec![0; 32], signature: vec![0; 96], header, txs: vec![txu()], mask: None, mask_size: None, mask_set_size: None };
+        let Term::PropList(mut pairs) = entry.to_term().unwrap() else { unreachable!() };
+        // What a bitstring mask or a key the node added would look like
+        pairs.push((bin(b"mask"), Term::List(vec![])));
+        pairs.push((bin(b"extra"), Term::VarInt(1)));
+        let t = Term::PropList(pairs);
+
+        assert!(Entry::try_from(&t).is_err());
+        let back = Entry::decode(&t, Mode::Lenient).unwrap();
+        assert_eq!(back.mask, None);
+        assert_eq!(back.txs[0].tx.action.attached_symbol, Some(b"AMA".to_vec()));
+
+        // Lengths are left to validate_entry, a missing field is still an error
+        let t = tx_term(bin(&[0; 47]), Term::VarInt(1));
+        assert_eq!(TX::decode(&t, Mode::Lenient).unwrap().signer.len(), 47);
+        let t = props(vec![("signer", bin(&[0; 48])), ("nonce", Term::VarInt(1))]);
+        assert_eq!(TX::decode(&t, Mode::Lenient).unwrap_err(), DecodeError::MissingField { model: "tx", field: "action" });
     }
 }
@@ -1,5 +1,4 @@
-use crate::model::_codec as codec;
-use crate::model::_codec::{EncodeToTerm, DecodeFromTerm};
+use crate::model::_codec::{EncodeToTerm, DecodeError, Fields};
 use vecpak::{Term};
 
 #[derive(Debug, Clone)]
@@ -19,15 +18,22 @@ impl EncodeToTerm for Attestation {
         ]))
     }
 }
-impl DecodeFromTerm for Attestation {
-    fn from_term(t: &Term) -> Self {
-        let Term::PropList(pairs) = t else { unreachable!() };
+impl TryFrom<&Term> for Attestation {
+    type Error = DecodeError;
 
-        let entry_hash  = codec::pl_get_bytes(pairs,   b"entry_hash").to_vec();
-        let mutations_hash  = codec::pl_get_bytes(pairs,   b"mutations_hash").to_vec();
-        let signer  = codec::pl_get_bytes(pairs,   b"signer").to_vec();
-        let signature  = codec::pl_get_bytes(pairs,   b"signature").to_vec();
+    fn try_from(t: &Term) -> Result<Self, DecodeError> {
+        let f = Fields::new("attestation", t, &["entry_hash", "mutations_hash", "signer", "signature"])?;
 
-        Attestation { entry_hash, mutations_hash, signer, signature }
+        let entry_hash      = f.bytes_len("entry_hash", 32)?;
+        let mutations_hash  = f.bytes_len("mutations_hash", 32)?;
+        let signer          = f.bytes_len("signer", 48)?;
+        let signature       = f.bytes_len("signature", 96)?;
+
+        Ok(Attestation { entry_hash, mutations_hash, signer, signature })
     }
 }
+
+pub fn from_bytes(data: &[u8]) -> Result<Attestation, DecodeError> {
+    let term = vecpak::decode(data)?;
+    Attestation::try_from(&term)
+}
@@ -1,5 +1,4 @@
-use crate::model::_codec as codec;
-use crate::model::_codec::{EncodeToTerm, DecodeFromTerm};
+use crate::model::_codec::{EncodeToTerm, DecodeError, Fields, Mode};
 use crate::model::tx::{TXU};
 use vecpak::{Term};
 
@@ -43,21 +42,29 @@ impl EncodeToTerm for Header {
     }
 }
 
-impl DecodeFromTerm for Header {
-    fn from_term(t: &Term) -> Self {
-        let Term::PropList(pairs) = t else { unreachable!("Expected PropList for Header") };
-
-        Header {
-            prev_hash:      codec::pl_get_bytes(pairs, b"prev_hash").to_vec(),
-            height:         codec::pl_get_varint(pairs, b"height") as u64,
-            slot:           codec::pl_get_varint(pairs, b"slot") as u64,
-            prev_slot:      codec::pl_get_varint(pairs, b"prev_slot") as u64,
-            signer:         codec::pl_get_bytes(pairs, b"signer").to_vec(),
-            dr:             codec::pl_get_bytes(pairs, b"dr").to_vec(),
-            vr:             codec::pl_get_bytes(pairs, b"vr").to_vec(),
-            root_tx:        codec::pl_get_bytes(pairs, b"root_tx").to_vec(),
-            root_validator: codec::pl_get_bytes(pairs, b"root_validator").to_vec(),
-        }
+impl TryFrom<&Term> for Header {
+    type Error = DecodeError;
+
+    fn try_from(t: &Term) -> Result<Self, DecodeError> {
+        Header::decode(t, Mode::Strict)
+    }
+}
+
+impl Header {
+    pub fn decode(t: &Term, mode: Mode) -> Result<Self, DecodeError> {
+        let f = Fields::with_mode("header", t, &["prev_hash", "height", "slot", "prev_slot", "signer", "dr", "vr", "root_tx", "root_validator"], mode)?;
+
+        Ok(Header {
+            prev_hash:      f.bytes_len("prev_hash", 32)?,
+            height:         f.u64("height")?,
+            slot:           f.u64("slot")?,
+            prev_slot:      f.u64("prev_slot")?,
+            signer:         f.bytes_len("signer", 48)?,
+            dr:             f.bytes_len("dr", 32)?,
+            vr:             f.bytes_len("vr", 96)?,
+            root_tx:        f.bytes_len("root_tx", 32)?,
+            root_validator: f.bytes_len("root_validator", 32)?,
+        })
     }
 }
 
@@ -89,27 +96,33 @@ impl EncodeToTerm for Entry {
     }
 }
 
-impl DecodeFromTerm for Entry {
-    fn from_term(t: &Term) -> Self {
-        let Term::PropList(pairs) = t else { unreachable!("Expected PropList for Entry") };
+impl TryFrom<&Term> for Entry {
+    type Error = DecodeError;
 
-        let hash      = codec::pl_get_bytes(pairs, b"hash").to_vec();
-        let signature = codec::pl_get_bytes(pairs, b"signature").to_vec();
+    fn try_from(t: &Term) -> Result<Self, DecodeError> {
+        Entry::decode(t, Mode::Strict)
+    }
+}
 
-        let header_term = codec::pl_find(pairs, b"header");
-        let header      = Header::from_term(header_term);
+impl Entry {
+    pub fn decode(t: &Term, mode: Mode) -> Result<Self, DecodeError> {
+        let f = Fields::with_mode("entry", t, &["hash", "signature", "header", "txs", "mask", "mask_size", "mask_set_size"], mode)?;
 
-        let txs_term_list = codec::pl_get_list(pairs, b"txs");
-        let txs = txs_term_list.iter()
-            .map(|t| TXU::from_term(t))
-            .collect();
+        let hash      = f.bytes_len("hash", 32)?;
+        let signature = f.bytes_len("signature", 96)?;
 
-        let mask = codec::pl_get_bytes_opt(pairs, b"mask").map(|b| b.to_vec());
+        let header = Header::decode(f.find("header")?, mode)?;
 
-        let mask_size     = codec::pl_get_varint_opt(pairs, b"mask_size");
-        let mask_set_size = codec::pl_get_varint_opt(pairs, b"mask_set_size");
+        let txs = f.list("txs")?.iter()
+            .map(|t| TXU::decode(t, mode))
+            .collect::<Result<Vec<_>, _>>()?;
 
-        Entry {
+        let mask = f.bytes_opt("mask")?.map(|b| b.to_vec());
+
+        let mask_size     = f.varint_opt("mask_size")?;
+        let mask_set_size = f.varint_opt("mask_set_size")?;
+
+        Ok(Entry {
             hash,
             signature,
             header,
@@ -117,11 +130,17 @@ impl DecodeFromTerm for Entry {
             mask,
             mask_size,
             mask_set_size,
-        }
+        })
     }
 }
 
-pub fn from_bytes(data: &[u8]) -> Result<Entry, &'static str> {
+pub fn from_bytes(data: &[u8]) -> Result<Entry, DecodeError> {
+    let term = vecpak::decode(data)?;
+    Entry::try_from(&term)
+}
+
+// For entries the node already accepted and hands back re-encoded, see Mode::Lenient
+pub fn from_bytes_lenient(data: &[u8]) -> Result<Entry, DecodeError> {
     let term = vecpak::decode(data)?;
-    Ok(Entry::from_term(&term))
+    Entry::decode(&term, Mode::Lenient)
 }
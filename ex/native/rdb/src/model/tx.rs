@@ -1,5 +1,4 @@
-use crate::model::_codec as codec;
-use crate::model::_codec::{EncodeToTerm, DecodeFromTerm};
+use crate::model::_codec::{EncodeToTerm, DecodeError, Fields, Mode};
 use vecpak::{Term};
 
 #[derive(Debug, Clone)]
@@ -51,33 +50,34 @@ impl EncodeToTerm for Action {
     }
 }
 
-impl DecodeFromTerm for Action {
-    fn from_term(t: &Term) -> Self {
-        let Term::PropList(pairs) = t else { unreachable!("Expected PropList for Action") };
+impl TryFrom<&Term> for Action {
+    type Error = DecodeError;
 
-        let op       = codec::pl_get_bytes(pairs, b"op").to_vec();
-        let contract = codec::pl_get_bytes(pairs, b"contract").to_vec();
-        let function = codec::pl_get_bytes(pairs, b"function").to_vec();
+    fn try_from(t: &Term) -> Result<Self, DecodeError> {
+        Action::decode(t, Mode::Strict)
+    }
+}
+
+impl Action {
+    pub fn decode(t: &Term, mode: Mode) -> Result<Self, DecodeError> {
+        let f = Fields::with_mode("action", t, &["op", "contract", "function", "args", "attached_symbol", "attached_amount"], mode)?;
 
-        let args_term_list = codec::pl_get_list(pairs, b"args");
-        let args = args_term_list.iter().map(|item| {
-            match item {
-                Term::Binary(b) => b.clone(),
-                _ => Vec::new(),
-            }
-        }).collect();
+        let op       = f.bytes("op")?.to_vec();
+        let contract = f.bytes("contract")?.to_vec();
+        let function = f.bytes("function")?.to_vec();
+        let args     = f.list_of_bytes("args")?;
 
-        let attached_symbol = codec::pl_get_bytes_opt(pairs, b"attached_symbol").map(|b| b.to_vec());
-        let attached_amount = codec::pl_get_bytes_opt(pairs, b"attached_amount").map(|b| b.to_vec());
+        let attached_symbol = f.bytes_opt("attached_symbol")?.map(|b| b.to_vec());
+        let attached_amount = f.bytes_opt("attached_amount")?.map(|b| b.to_vec());
 
-        Action {
+        Ok(Action {
             op,
             contract,
             function,
             args,
             attached_symbol,
             attached_amount
-        }
+        })
     }
 }
 
@@ -91,17 +91,23 @@ impl EncodeToTerm for TX {
     }
 }
 
-impl DecodeFromTerm for TX {
-    fn from_term(t: &Term) -> Self {
-        let Term::PropList(pairs) = t else { unreachable!("Expected PropList for TX") };
+impl TryFrom<&Term> for TX {
+    type Error = DecodeError;
+
+    fn try_from(t: &Term) -> Result<Self, DecodeError> {
+        TX::decode(t, Mode::Strict)
+    }
+}
 
-        let signer = codec::pl_get_bytes(pairs, b"signer").to_vec();
-        let nonce  = codec::pl_get_varint(pairs, b"nonce") as u64;
+impl TX {
+    pub fn decode(t: &Term, mode: Mode) -> Result<Self, DecodeError> {
+        let f = Fields::with_mode("tx", t, &["signer", "nonce", "action"], mode)?;
 
-        let action_term = codec::pl_find(pairs, b"action");
-        let action = Action::from_term(action_term);
+        let signer = f.bytes_len("signer", 48)?;
+        let nonce  = f.u64("nonce")?;
+        let action = Action::decode(f.find("action")?, mode)?;
 
-        TX { signer, nonce, action }
+        Ok(TX { signer, nonce, action })
     }
 }
 
@@ -120,21 +126,27 @@ impl EncodeToTerm for TXU {
     }
 }
 
-impl DecodeFromTerm for TXU {
-    fn from_term(t: &Term) -> Self {
-        let Term::PropList(pairs) = t else { unreachable!("Expected PropList for TXU") };
+impl TryFrom<&Term> for TXU {
+    type Error = DecodeError;
+
+    fn try_from(t: &Term) -> Result<Self, DecodeError> {
+        TXU::decode(t, Mode::Strict)
+    }
+}
 
-        let hash      = codec::pl_get_bytes(pairs, b"hash").to_vec();
-        let signature = codec::pl_get_bytes(pairs, b"signature").to_vec();
+impl TXU {
+    pub fn decode(t: &Term, mode: Mode) -> Result<Self, DecodeError> {
+        let f = Fields::with_mode("txu", t, &["hash", "signature", "tx"], mode)?;
 
-        let tx_term = codec::pl_find(pairs, b"tx");
-        let tx = TX::from_term(tx_term);
+        let hash      = f.bytes_len("hash", 32)?;
+        let signature = f.bytes_len("signature", 96)?;
+        let tx        = TX::decode(f.find("tx")?, mode)?;
 
-        TXU { hash, signature, tx }
+        Ok(TXU { hash, signature, tx })
     }
 }
 
-pub fn from_bytes(data: &[u8]) -> Result<TXU, &'static str> {
+pub fn from_bytes(data: &[u8]) -> Result<TXU, DecodeError> {
     let term = vecpak::decode(data)?;
-    Ok(TXU::from_term(&term))
+    TXU::try_from(&term)
 }
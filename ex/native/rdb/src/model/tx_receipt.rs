@@ -1,5 +1,4 @@
-use crate::model::_codec as codec;
-use crate::model::_codec::{EncodeToTerm, DecodeFromTerm};
+use crate::model::_codec::{EncodeToTerm, DecodeError, Fields};
 use vecpak::{Term};
 
 #[derive(Debug, Clone)]
@@ -26,16 +25,23 @@ impl EncodeToTerm for TXReceipt {
     }
 }
 
-impl DecodeFromTerm for TXReceipt {
-    fn from_term(t: &Term) -> Self {
-        let Term::PropList(pairs) = t else { unreachable!() };
+impl TryFrom<&Term> for TXReceipt {
+    type Error = DecodeError;
 
-        let txid  = codec::pl_get_bytes(pairs,   b"txid").to_vec();
-        let success  = codec::pl_get_bool(pairs,   b"success");
-        let result  = codec::pl_get_bytes(pairs,   b"result").to_vec();
-        let exec_used  = codec::pl_get_bytes(pairs,   b"exec_used").to_vec();
-        let logs  = codec::pl_get_list_of_bytes(pairs,   b"logs");
+    fn try_from(t: &Term) -> Result<Self, DecodeError> {
+        let f = Fields::new("tx_receipt", t, &["txid", "success", "result", "exec_used", "logs"])?;
 
-        TXReceipt { txid, success, result, exec_used, logs }
+        let txid       = f.bytes_len("txid", 32)?;
+        let success    = f.bool("success")?;
+        let result     = f.bytes("result")?.to_vec();
+        let exec_used  = f.bytes("exec_used")?.to_vec();
+        let logs       = f.list_of_bytes("logs")?;
+
+        Ok(TXReceipt { txid, success, result, exec_used, logs })
     }
 }
+
+pub fn from_bytes(data: &[u8]) -> Result<TXReceipt, DecodeError> {
+    let term = vecpak::decode(data)?;
+    TXReceipt::try_from(&term)
+}
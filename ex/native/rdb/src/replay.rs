@@ -0,0 +1,198 @@
+use std::path::{Path, PathBuf};
+
+use rust_rocksdb::checkpoint::Checkpoint;
+use rust_rocksdb::DB;
+
+use crate::consensus::consensus_apply;
+use crate::consensus::consensus_muts::{self, Mutation};
+use crate::db_options::DbTuning;
+use crate::model::entry::Entry;
+use crate::{ColumnFamilyDescriptor, MultiThreaded, Options, Transaction, TransactionDB};
+
+// Replays main chain entries of a node DB and checks that today's apply_entry still
+// produces what was recorded. The node DB is only opened read only, to take a scratch
+// checkpoint of it: the rewind and every replayed entry are committed to that copy.
+
+pub type Txn<'db> = Transaction<'db, TransactionDB<MultiThreaded>>;
+
+pub struct ReplayOpts {
+    pub testnet: bool,
+    pub testnet_peddlebikes: Vec<Vec<u8>>,
+    pub speculate: bool,
+}
+
+#[derive(Debug)]
+pub struct Divergence {
+    pub height: u64,
+    pub entry_hash: Vec<u8>,
+    // root_receipts, root_contractstate, muts, muts_rev, apply_entry (it failed or panicked)
+    // or recorded (what the node stored for the entry could not be read)
+    pub field: &'static str,
+    pub detail: String,
+}
+
+// Sibling of the node DB dir so the checkpoint can hardlink the SSTs
+pub fn scratch_path(db_path: &Path) -> PathBuf {
+    let mut name = db_path.as_os_str().to_os_string();
+    name.push(format!(".replay_{}", std::process::id()));
+    PathBuf::from(name)
+}
+
+// A read only open takes no lock and never writes to db_path
+pub fn checkpoint(db_path: &Path, scratch: &Path) -> Result<(), String> {
+    let opts = Options::default();
+    let cf_names = DB::list_cf(&opts, db_path).map_err(|e| e.to_string())?;
+    let ro_db = DB::open_cf_for_read_only(&opts, db_path, cf_names, false).map_err(|e| e.to_string())?;
+    Checkpoint::new(&ro_db)
+        .and_then(|cp| cp.create_checkpoint(scratch))
+        .map_err(|e| e.to_string())
+}
+
+// Opens the scratch checkpoint with the options the node uses, never creating anything
+pub fn open(path: &Path) -> Result<TransactionDB<MultiThreaded>, String> {
+    let tuning = DbTuning::default();
+    let caches = tuning.caches();
+    let mut db_opts = tuning.db_options();
+    db_opts.create_if_missing(false);
+    db_opts.create_missing_column_families(false);
+
+    let cf_names = TransactionDB::<MultiThreaded>::list_cf(&db_opts, path).map_err(|e| e.to_string())?;
+    let cf_descriptors: Vec<_> = cf_names.iter()
+        .map(|name| ColumnFamilyDescriptor::new(name.as_str(), tuning.cf_tuning(name).options(&caches, tuning.max_total_wal_size)))
+        .collect();
+    TransactionDB::open_cf_descriptors(&db_opts, &tuning.txn_db_options(), path, cf_descriptors)
+        .map_err(|e| e.to_string())
+}
+
+fn get(txn: &Txn, db: &TransactionDB<MultiThreaded>, cf: &str, key: &[u8]) -> Result<Option<Vec<u8>>, String> {
+    let cf_h = db.cf_handle(cf).ok_or_else(|| format!("missing column family {}", cf))?;
+    txn.get_cf(&cf_h, key).map_err(|e| e.to_string())
+}
+
+fn entry_meta(txn: &Txn, db: &TransactionDB<MultiThreaded>, hash: &[u8], field: &str) -> Result<Option<Vec<u8>>, String> {
+    get(txn, db, "entry_meta", &crate::bcat(&[b"entry:", hash, b":", field.as_bytes()]))
+}
+
+pub fn tip_height(txn: &Txn, db: &TransactionDB<MultiThreaded>) -> Result<u64, String> {
+    let hash = get(txn, db, "sysconf", b"temporal_tip")?.ok_or("no temporal_tip")?;
+    Ok(entry_by_hash(txn, db, &hash)?.header.height)
+}
+
+fn entry_by_hash(txn: &Txn, db: &TransactionDB<MultiThreaded>, hash: &[u8]) -> Result<Entry, String> {
+    let bytes = get(txn, db, "entry", hash)?.ok_or_else(|| format!("entry {} not found", bs58::encode(hash).into_string()))?;
+    crate::model::entry::from_bytes_lenient(&bytes).map_err(|e| e.to_string())
+}
+
+// Same key layout as DB.Entry (heights zero padded to 12 digits)
+pub fn main_chain_entry(txn: &Txn, db: &TransactionDB<MultiThreaded>, height: u64) -> Result<Entry, String> {
+    let key = format!("by_height_in_main_chain:{:012}", height);
+    let hash = get(txn, db, "entry_meta", key.as_bytes())?.ok_or_else(|| format!("no main chain entry at {}", height))?;
+    entry_by_hash(txn, db, &hash)
+}
+
+// Undoes one entry the way DB.Chain.revert_muts does, last mutation first
+fn revert(txn: &Txn, db: &TransactionDB<MultiThreaded>, muts_rev: &[Mutation]) -> Result<(), String> {
+    for m in muts_rev.iter().rev() {
+        let table = std::str::from_utf8(m.table()).map_err(|_| "invalid table")?;
+        let cf_h = db.cf_handle(table).ok_or_else(|| format!("unknown table {}", table))?;
+        match m {
+            Mutation::Put { key, value, .. } => txn.put_cf(&cf_h, key, value).map_err(|e| e.to_string())?,
+            Mutation::Delete { key, .. } => txn.delete_cf(&cf_h, key).map_err(|e| e.to_string())?,
+            Mutation::ClearBit { key, value: bit_idx, .. } => {
+                let mut page = txn.get_cf(&cf_h, key).map_err(|e| e.to_string())?.ok_or("clear_bit on missing key")?;
+                let byte_idx = (bit_idx / 8) as usize;
+                if byte_idx < page.len() {
+                    page[byte_idx] &= !(1u8 << (7 - (bit_idx % 8) as u8));
+                    txn.put_cf(&cf_h, key, &page).map_err(|e| e.to_string())?;
+                }
+            }
+            Mutation::SetBit { .. } => return Err("set_bit in muts_rev".to_string()),
+        }
+    }
+    Ok(())
+}
+
+// Brings the txn's view of contractstate back to just before `height` by undoing every
+// main chain entry from the tip down to it. Cost grows with the distance to the tip.
+pub fn rewind(txn: &Txn, db: &TransactionDB<MultiThreaded>, height: u64) -> Result<(), String> {
+    let tip = tip_height(txn, db)?;
+    for h in (height..=tip).rev() {
+        let entry = main_chain_entry(txn, db, h)?;
+        let bytes = entry_meta(txn, db, &entry.hash, "muts_rev")?.ok_or_else(|| format!("no muts_rev at {}", h))?;
+        let muts_rev = consensus_muts::mutations_from_vecpak(&bytes).map_err(|e| format!("muts_rev at {}: {}", h, e))?;
+        revert(txn, db, &muts_rev)?;
+    }
+    Ok(())
+}
+
+fn diff_muts(got: &[Mutation], want: &[Mutation]) -> Option<String> {
+    let at = got.iter().zip(want.iter()).position(|(g, w)| g != w);
+    match at {
+        Some(i) => Some(format!("first difference at #{}: got {:?}, recorded {:?}", i, got[i], want[i])),
+        None if got.len() != want.len() => Some(format!("got {} mutations, recorded {}", got.len(), want.len())),
+        None => None,
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Applies one entry on top of txn and compares against what the node recorded for it.
+// Mutations are checked against the stored muts_rev always and the stored muts when
+// the node was archival. The txn comes back unless apply_entry failed or panicked.
+pub fn replay_entry<'db>(
+    db: &'db TransactionDB<MultiThreaded>,
+    txn: Txn<'db>,
+    entry: Entry,
+    opts: &ReplayOpts,
+) -> Result<(Txn<'db>, Option<Divergence>), Divergence> {
+    let height = entry.header.height;
+    let entry_hash = entry.hash.clone();
+    let divergence = |field: &'static str, detail: String| Divergence { height, entry_hash: entry_hash.clone(), field, detail };
+
+    let fields = consensus_apply::validate_entry(&entry).map_err(|reason| divergence("recorded", reason.to_string()))?;
+    // Speculation reads the committed db, so earlier entries must be committed by now
+    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        consensus_apply::apply_entry(db, txn, entry, &fields, &[], &[], opts.testnet, opts.testnet_peddlebikes.clone(), false, opts.speculate)
+    }));
+    let (txn, muts, muts_rev, _receipts, root_receipts, root_contractstate, _) = match res {
+        Ok(Ok(out)) => out,
+        Ok(Err(e)) => return Err(divergence("apply_entry", e.reason().to_string())),
+        Err(payload) => {
+            let reason = payload.downcast_ref::<&'static str>().map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown".to_string());
+            return Err(divergence("apply_entry", reason));
+        }
+    };
+
+    let recorded = |field: &str| entry_meta(&txn, db, &entry_hash, field);
+    let check = || -> Result<Option<Divergence>, String> {
+        let want = recorded("root_receipts")?.unwrap_or_default();
+        if want != root_receipts {
+            return Ok(Some(divergence("root_receipts", format!("got {}, recorded {}", hex(&root_receipts), hex(&want)))));
+        }
+        let want = recorded("root_contractstate")?.unwrap_or_default();
+        if want != root_contractstate {
+            return Ok(Some(divergence("root_contractstate", format!("got {}, recorded {}", hex(&root_contractstate), hex(&want)))));
+        }
+        if let Some(bytes) = recorded("muts")? {
+            let want = consensus_muts::mutations_from_vecpak(&bytes)?;
+            if let Some(detail) = diff_muts(&muts, &want) {
+                return Ok(Some(divergence("muts", detail)));
+            }
+        }
+        if let Some(bytes) = recorded("muts_rev")? {
+            let want = consensus_muts::mutations_from_vecpak(&bytes)?;
+            if let Some(detail) = diff_muts(&muts_rev, &want) {
+                return Ok(Some(divergence("muts_rev", detail)));
+            }
+        }
+        Ok(None)
+    };
+    match check() {
+        Ok(found) => Ok((txn, found)),
+        Err(e) => Err(divergence("recorded", e)),
+    }
+}
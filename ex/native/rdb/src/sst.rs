@@ -0,0 +1,113 @@
+use rustler::Error;
+use rust_rocksdb::{SstFileWriter, IngestExternalFileOptions};
+use std::path::{Path, PathBuf};
+
+use crate::atoms;
+use crate::to_nif_rdb_err;
+use crate::{TransactionDB, MultiThreaded, Options, ReadOptions, DBCompressionType, AsColumnFamilyRef};
+
+pub struct SstFile {
+    pub path: String,
+    pub keys: u64,
+    pub size: u64,
+    pub checksum: [u8; 32],
+    pub first_key: Vec<u8>,
+    pub last_key: Vec<u8>,
+}
+
+fn io_err(err: std::io::Error) -> Error {
+    Error::Term(Box::new((atoms::io_error(), err.to_string())))
+}
+
+pub fn file_checksum(path: &Path) -> Result<[u8; 32], Error> {
+    let file = std::fs::File::open(path).map_err(io_err)?;
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(file).map_err(io_err)?;
+    Ok(*hasher.finalize().as_bytes())
+}
+
+fn finish_file(writer: &mut SstFileWriter, path: PathBuf, keys: u64, first_key: Vec<u8>, last_key: Vec<u8>) -> Result<SstFile, Error> {
+    writer.finish().map_err(to_nif_rdb_err)?;
+    let size = std::fs::metadata(&path).map_err(io_err)?.len();
+    let checksum = file_checksum(&path)?;
+    Ok(SstFile { path: path.to_string_lossy().into_owned(), keys, size, checksum, first_key, last_key })
+}
+
+// Dumps [start, end) of a CF as of a private snapshot into dir/NNNNNN.sst,
+// rolling to a new file once max_file_size is reached. Empty ranges yield no files.
+pub fn export_cf(
+    db: &TransactionDB<MultiThreaded>,
+    cf: &impl AsColumnFamilyRef,
+    dir: &str,
+    start: Option<&[u8]>,
+    end: Option<&[u8]>,
+    max_file_size: u64,
+) -> Result<Vec<SstFile>, Error> {
+    std::fs::create_dir_all(dir).map_err(io_err)?;
+
+    let snap = db.snapshot();
+    let mut ro = ReadOptions::default();
+    ro.set_total_order_seek(true);
+    ro.set_snapshot(&snap);
+    if let Some(start) = start { ro.set_iterate_lower_bound(start.to_vec()); }
+    if let Some(end) = end { ro.set_iterate_upper_bound(end.to_vec()); }
+
+    let mut it = db.raw_iterator_cf_opt(cf, ro);
+    it.seek_to_first();
+
+    let mut opts = Options::default();
+    opts.set_compression_type(DBCompressionType::Zstd);
+
+    let mut files = Vec::new();
+    let mut writer = SstFileWriter::create(&opts);
+    let mut path = PathBuf::new();
+    let mut keys = 0u64;
+    let mut first_key = Vec::new();
+    let mut last_key = Vec::new();
+
+    while let Some((k, v)) = it.item() {
+        if keys == 0 {
+            path = Path::new(dir).join(format!("{:06}.sst", files.len()));
+            writer.open(&path).map_err(to_nif_rdb_err)?;
+            first_key = k.to_vec();
+        }
+        writer.put(k, v).map_err(to_nif_rdb_err)?;
+        keys += 1;
+        last_key.clear();
+        last_key.extend_from_slice(k);
+
+        if writer.file_size() >= max_file_size {
+            files.push(finish_file(&mut writer, std::mem::take(&mut path), keys,
+                std::mem::take(&mut first_key), last_key.clone())?);
+            keys = 0;
+        }
+        it.next();
+    }
+    it.status().map_err(to_nif_rdb_err)?;
+
+    if keys > 0 {
+        files.push(finish_file(&mut writer, path, keys, first_key, last_key)?);
+    }
+    Ok(files)
+}
+
+// Checksums are checked before anything touches the DB, ingestion is all or nothing
+pub fn ingest_cf(
+    db: &TransactionDB<MultiThreaded>,
+    cf: &impl AsColumnFamilyRef,
+    files: &[(String, Option<Vec<u8>>)],
+    move_files: bool,
+) -> Result<(), Error> {
+    for (path, checksum) in files {
+        if let Some(expected) = checksum {
+            if file_checksum(Path::new(path))?.as_slice() != expected.as_slice() {
+                return Err(Error::Term(Box::new((atoms::checksum_mismatch(), path.clone()))));
+            }
+        }
+    }
+
+    let mut opts = IngestExternalFileOptions::default();
+    opts.set_move_files(move_files);
+    let paths: Vec<&String> = files.iter().map(|(path, _)| path).collect();
+    db.ingest_external_file_cf_opts(cf, &opts, paths).map_err(to_nif_rdb_err)
+}
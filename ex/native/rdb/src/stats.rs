@@ -0,0 +1,72 @@
+use rustler::{Encoder, Env, Error, Term};
+use rust_rocksdb::statistics::{Ticker, Histogram};
+
+use crate::atoms;
+use crate::{DbResource, CfResource};
+
+// Per CF integer properties, keyed by the atom they are exported under
+const CF_PROPERTIES: &[(&str, fn() -> rustler::Atom)] = &[
+    ("rocksdb.size-all-mem-tables", atoms::mem_table_total),
+    ("rocksdb.cur-size-all-mem-tables", atoms::mem_table_unflushed),
+    ("rocksdb.cur-size-active-mem-table", atoms::mem_table_active),
+    ("rocksdb.estimate-table-readers-mem", atoms::table_readers_mem),
+    ("rocksdb.block-cache-usage", atoms::block_cache_usage),
+    ("rocksdb.block-cache-pinned-usage", atoms::block_cache_pinned_usage),
+    ("rocksdb.estimate-pending-compaction-bytes", atoms::pending_compaction_bytes),
+    ("rocksdb.estimate-num-keys", atoms::estimate_num_keys),
+    ("rocksdb.total-sst-files-size", atoms::total_sst_files_size),
+    ("rocksdb.live-sst-files-size", atoms::live_sst_files_size),
+    ("rocksdb.num-blob-files", atoms::num_blob_files),
+    ("rocksdb.total-blob-file-size", atoms::total_blob_file_size),
+    ("rocksdb.live-blob-file-size", atoms::live_blob_file_size),
+    ("rocksdb.live-blob-file-garbage-size", atoms::live_blob_file_garbage_size),
+];
+
+pub fn db_stats<'a>(env: Env<'a>, db: &DbResource) -> Result<Term<'a>, Error> {
+    let _db = db.db()?;
+    let mut tickers = Term::map_new(env);
+    for ticker in Ticker::iter() {
+        let count = db.opts.get_ticker_count(*ticker);
+        tickers = tickers.map_put(ticker.name(), count).ok().unwrap();
+    }
+
+    let mut histograms = Term::map_new(env);
+    for histogram in Histogram::iter() {
+        let data = db.opts.get_histogram_data(*histogram);
+        if data.count() == 0 {
+            continue;
+        }
+        let mut map = Term::map_new(env);
+        map = map.map_put(atoms::count(), data.count()).ok().unwrap();
+        map = map.map_put(atoms::sum(), data.sum()).ok().unwrap();
+        map = map.map_put(atoms::min(), data.min()).ok().unwrap();
+        map = map.map_put(atoms::max(), data.max()).ok().unwrap();
+        map = map.map_put(atoms::avg(), data.average()).ok().unwrap();
+        map = map.map_put(atoms::p50(), data.median()).ok().unwrap();
+        map = map.map_put(atoms::p95(), data.p95()).ok().unwrap();
+        map = map.map_put(atoms::p99(), data.p99()).ok().unwrap();
+        histograms = histograms.map_put(histogram.name(), map).ok().unwrap();
+    }
+
+    let mut map = Term::map_new(env);
+    map = map.map_put(atoms::tickers(), tickers).ok().unwrap();
+    map = map.map_put(atoms::histograms(), histograms).ok().unwrap();
+    map = map.map_put(atoms::block_cache_usage(), db.caches.block_cache.get_usage()).ok().unwrap();
+    map = map.map_put(atoms::block_cache_pinned_usage(), db.caches.block_cache.get_pinned_usage()).ok().unwrap();
+    map = map.map_put(atoms::row_cache_usage(), db.caches.row_cache.get_usage()).ok().unwrap();
+    Ok(map.encode(env))
+}
+
+// Properties the CF does not report are left out rather than zeroed
+pub fn cf_stats<'a>(env: Env<'a>, cf: &CfResource) -> Result<Term<'a>, Error> {
+    let db = cf.db.db()?;
+    let mut map = Term::map_new(env);
+    for (property, atom) in CF_PROPERTIES {
+        if let Ok(Some(value)) = db.property_value_cf(cf, *property) {
+            if let Ok(value) = value.trim().parse::<u64>() {
+                map = map.map_put(atom(), value).ok().unwrap();
+            }
+        }
+    }
+    Ok(map.encode(env))
+}